@@ -0,0 +1,55 @@
+#!/usr/bin/env python3
+"""Bundle the CID to Unicode tables of the Adobe character collections.
+
+Text in CID fonts without a ToUnicode map is decoded through the
+Adobe-GB1-UCS2, Adobe-CNS1-UCS2, Adobe-Japan1-UCS2 and Adobe-Korea1-UCS2
+CMaps of https://github.com/adobe-type-tools/cmap-resources. This copies the
+latest supplement of each from a checkout of that repository, gzipped, into
+src-tauri/resources/cmaps along with its license:
+
+    git clone https://github.com/adobe-type-tools/cmap-resources
+    python3 scripts/bundle_cid_tables.py cmap-resources
+"""
+
+import glob
+import gzip
+import os
+import re
+import shutil
+import sys
+
+OUTPUT = os.path.join(os.path.dirname(__file__), '..', 'src-tauri', 'resources', 'cmaps')
+ORDERINGS = ['GB1', 'CNS1', 'Japan1', 'Korea1']
+
+
+def latest_table(checkout, ordering):
+    """Path of the UCS2 CMap of the highest supplement of a collection."""
+    found = []
+    for path in glob.glob(os.path.join(checkout, f'Adobe-{ordering}-*', 'CMap', f'Adobe-{ordering}-UCS2')):
+        supplement = re.search(rf'Adobe-{ordering}-(\d+)', path)
+        if supplement:
+            found.append((int(supplement.group(1)), path))
+    if not found:
+        sys.exit(f'No Adobe-{ordering}-UCS2 in {checkout}')
+    return max(found)[1]
+
+
+def main():
+    if len(sys.argv) != 2:
+        sys.exit(f'usage: {sys.argv[0]} <cmap-resources checkout>')
+    checkout = sys.argv[1]
+    os.makedirs(OUTPUT, exist_ok=True)
+    for ordering in ORDERINGS:
+        source = latest_table(checkout, ordering)
+        target = os.path.join(OUTPUT, f'Adobe-{ordering}-UCS2.gz')
+        with open(source, 'rb') as f:
+            data = f.read()
+        # A fixed mtime keeps the output the same from run to run
+        with open(target, 'wb') as f:
+            f.write(gzip.compress(data, compresslevel=9, mtime=0))
+        print(f'{source} -> {target} ({len(data)} bytes)')
+    shutil.copy(os.path.join(checkout, 'LICENSE.md'), os.path.join(OUTPUT, 'LICENSE.md'))
+
+
+if __name__ == '__main__':
+    main()
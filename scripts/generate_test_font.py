@@ -0,0 +1,183 @@
+#!/usr/bin/env python3
+"""Generate the small TrueType font the Rust tests embed in stamps.
+
+Every printable ASCII character gets a plain box as its glyph, which is all
+the tests need: real cmap, metrics and outlines, without depending on the
+fonts installed on the machine running them.
+"""
+
+import os
+import struct
+
+OUTPUT = os.path.join(os.path.dirname(__file__), '..', 'src-tauri', 'tests', 'fixtures', 'PdfcraftTest.ttf')
+
+UNITS_PER_EM = 1000
+ASCENDER = 800
+DESCENDER = -200
+FIRST_CHAR = 0x20
+LAST_CHAR = 0x7E
+
+FAMILY = 'Pdfcraft Test'
+POSTSCRIPT_NAME = 'PdfcraftTest-Regular'
+COPYRIGHT = 'Copyright the PDFCraft authors'
+LICENSE = 'This Font Software is licensed under the SIL Open Font License, Version 1.1.'
+LICENSE_URL = 'https://openfontlicense.org'
+
+
+def advance(code):
+    """Advance width of a character; narrow, normal and wide boxes."""
+    if code == 0x20:
+        return 250
+    if chr(code) in 'fijlrt.,:;!|\'':
+        return 300
+    if chr(code) in 'MWmw@':
+        return 800
+    return 550
+
+
+def box(width, height):
+    """A simple glyph with one rectangular contour."""
+    x_min, x_max = 50, width - 50
+    points = [(x_min, 0), (x_min, height), (x_max, height), (x_max, 0)]
+    data = struct.pack('>hhhhh', 1, x_min, 0, x_max, height)
+    data += struct.pack('>HH', len(points) - 1, 0)
+    data += bytes([0x01] * len(points))
+    previous = 0
+    for x, _ in points:
+        data += struct.pack('>h', x - previous)
+        previous = x
+    previous = 0
+    for _, y in points:
+        data += struct.pack('>h', y - previous)
+        previous = y
+    return data
+
+
+def glyphs():
+    """(advance, outline) of .notdef followed by every printable character."""
+    result = [(500, box(500, 700))]
+    for code in range(FIRST_CHAR, LAST_CHAR + 1):
+        width = advance(code)
+        if code == 0x20:
+            result.append((width, b''))
+        else:
+            height = 500 if chr(code).islower() else 700
+            result.append((width, box(width, height)))
+    return result
+
+
+def cmap_table():
+    seg_count = 2
+    id_delta = (1 - FIRST_CHAR) & 0xFFFF
+    subtable = struct.pack('>HHHHHHH', 4, 0, 0, seg_count * 2, 4, 1, 0)
+    subtable += struct.pack('>HH', LAST_CHAR, 0xFFFF)
+    subtable += struct.pack('>H', 0)
+    subtable += struct.pack('>HH', FIRST_CHAR, 0xFFFF)
+    subtable += struct.pack('>HH', id_delta, 1)
+    subtable += struct.pack('>HH', 0, 0)
+    subtable = subtable[:2] + struct.pack('>H', len(subtable)) + subtable[4:]
+    return struct.pack('>HHHHI', 0, 1, 3, 1, 12) + subtable
+
+
+def name_table():
+    records = [
+        (0, COPYRIGHT),
+        (1, FAMILY),
+        (2, 'Regular'),
+        (4, FAMILY + ' Regular'),
+        (6, POSTSCRIPT_NAME),
+        (13, LICENSE),
+        (14, LICENSE_URL),
+    ]
+    strings = b''
+    entries = b''
+    for name_id, text in records:
+        encoded = text.encode('utf-16-be')
+        entries += struct.pack('>HHHHHH', 3, 1, 0x409, name_id, len(encoded), len(strings))
+        strings += encoded
+    header = struct.pack('>HHH', 0, len(records), 6 + len(entries))
+    return header + entries + strings
+
+
+def os2_table(glyph_list):
+    average = sum(width for width, _ in glyph_list) // len(glyph_list)
+    data = struct.pack('>HhHHH', 4, average, 400, 5, 0)
+    data += struct.pack('>hhhhhhhhhh', 650, 600, 0, 75, 650, 600, 0, 350, 50, 250)
+    data += struct.pack('>h', 0)
+    data += bytes(10)
+    data += struct.pack('>IIII', 1, 0, 0, 0)
+    data += b'PDFC'
+    data += struct.pack('>HHH', 0x40, FIRST_CHAR, LAST_CHAR)
+    data += struct.pack('>hhhHH', ASCENDER, DESCENDER, 200, ASCENDER, -DESCENDER)
+    data += struct.pack('>II', 1, 0)
+    data += struct.pack('>hhHHH', 500, 700, 0, 0x20, 1)
+    return data
+
+
+def checksum(data):
+    data += bytes(-len(data) % 4)
+    return sum(struct.unpack('>%dI' % (len(data) // 4), data)) & 0xFFFFFFFF
+
+
+def build():
+    glyph_list = glyphs()
+    glyf = b''
+    offsets = []
+    for _, outline in glyph_list:
+        offsets.append(len(glyf))
+        glyf += outline + bytes(len(outline) % 2)
+    offsets.append(len(glyf))
+    loca = b''.join(struct.pack('>H', offset // 2) for offset in offsets)
+    hmtx = b''.join(struct.pack('>Hh', width, 50) for width, _ in glyph_list)
+    max_width = max(width for width, _ in glyph_list)
+
+    head = struct.pack('>IIIIHH', 0x00010000, 0x00010000, 0, 0x5F0F3CF5, 0x000B, UNITS_PER_EM)
+    head += bytes(16)
+    head += struct.pack('>hhhh', 0, 0, max_width - 50, 700)
+    head += struct.pack('>HHhhh', 0, 8, 2, 0, 0)
+    hhea = struct.pack('>Ihhh', 0x00010000, ASCENDER, DESCENDER, 0)
+    hhea += struct.pack('>Hhhh', max_width, 0, 0, max_width - 50)
+    hhea += struct.pack('>hhh', 1, 0, 0) + bytes(8) + struct.pack('>hH', 0, len(glyph_list))
+    maxp = struct.pack('>IHHHHHHHHHHHHHH', 0x00010000, len(glyph_list), 4, 1, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0)
+    post = struct.pack('>IIhhIIIII', 0x00030000, 0, -100, 50, 0, 0, 0, 0, 0)
+
+    tables = {
+        b'OS/2': os2_table(glyph_list),
+        b'cmap': cmap_table(),
+        b'glyf': glyf,
+        b'head': head,
+        b'hhea': hhea,
+        b'hmtx': hmtx,
+        b'loca': loca,
+        b'maxp': maxp,
+        b'name': name_table(),
+        b'post': post,
+    }
+
+    count = len(tables)
+    power = 1
+    while power * 2 <= count:
+        power *= 2
+    directory = struct.pack('>IHHHH', 0x00010000, count, power * 16, power.bit_length() - 1, count * 16 - power * 16)
+    offset = len(directory) + count * 16
+    records = b''
+    body = b''
+    for tag in sorted(tables):
+        data = tables[tag]
+        records += struct.pack('>4sIII', tag, checksum(data), offset + len(body), len(data))
+        body += data + bytes(-len(data) % 4)
+    font = bytearray(directory + records + body)
+
+    # head.checkSumAdjustment makes the whole file sum to a fixed value
+    head_record = records.index(b'head')
+    head_start = struct.unpack('>I', records[head_record + 8:head_record + 12])[0]
+    adjustment = (0xB1B0AFBA - checksum(bytes(font))) & 0xFFFFFFFF
+    font[head_start + 8:head_start + 12] = struct.pack('>I', adjustment)
+    return bytes(font)
+
+
+if __name__ == '__main__':
+    os.makedirs(os.path.dirname(OUTPUT), exist_ok=True)
+    with open(OUTPUT, 'wb') as f:
+        f.write(build())
+    print(f'Test font saved to {OUTPUT}')
@@ -9,6 +9,9 @@ use crate::services::pdf_parser::PdfParserService;
 use crate::utils::error::AppError;
 use crate::utils::progress::emit_progress;
 
+/// Share of decoded characters below which a page is reported as garbled
+const MIN_TEXT_QUALITY: f32 = 0.9;
+
 // Global cancellation flag (simple implementation)
 static CANCEL_FLAG: AtomicBool = AtomicBool::new(false);
 
@@ -32,15 +35,30 @@ pub async fn convert_pdf_to_ebook(
         // Stage 1 & 2: Extract text and analyze
         emit_progress(&app, &tid, 5, "extracting_text", "Extracting text...");
         
-        let pages = PdfParserService::extract_all_text(&config.input_path)?;
+        let page_texts = PdfParserService::extract_all_pages(&config.input_path, config.password.as_deref())?;
+        let pages: Vec<String> = page_texts.iter().map(PdfParserService::plain_text).collect();
         let total_chars: usize = pages.iter().map(|p| p.trim().len()).sum();
         let is_scanned = total_chars < 50;
 
+        let poorly_decoded: Vec<String> = page_texts
+            .iter()
+            .filter(|page| page.quality.score < MIN_TEXT_QUALITY)
+            .map(|page| page.page.to_string())
+            .collect();
+        if !poorly_decoded.is_empty() {
+            let message = format!(
+                "Some text could not be decoded on page(s) {}",
+                poorly_decoded.join(", ")
+            );
+            log::warn!("{}", message);
+            emit_progress(&app, &tid, 15, "text_quality", &message);
+        }
+
         if CANCEL_FLAG.load(Ordering::SeqCst) {
             return Err(AppError::Cancelled);
         }
 
-        if is_scanned && config.ocr.enabled {
+        if (is_scanned || !poorly_decoded.is_empty()) && config.ocr.enabled {
             // OCR path: for scanned PDFs, we'd render pages to images and OCR them
             emit_progress(&app, &tid, 20, "ocr_processing", "Running OCR...");
             log::warn!("OCR processing requested but Tesseract sidecar integration is pending");
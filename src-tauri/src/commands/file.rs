@@ -1,5 +1,6 @@
 use tauri::command;
 use crate::models::config::AppConfig;
+use crate::services::document_cache::DocumentCacheService;
 use crate::utils::error::AppError;
 
 /// Get application configuration
@@ -21,3 +22,14 @@ pub async fn update_app_config(config: AppConfig) -> Result<(), AppError> {
     // In a full implementation, persist to disk
     Ok(())
 }
+
+/// Release the parsed copy of a PDF kept between operations, or of every
+/// PDF when no path is given
+#[command]
+pub async fn clear_document_cache(path: Option<String>) -> Result<(), AppError> {
+    match path {
+        Some(path) => DocumentCacheService::evict(&path),
+        None => DocumentCacheService::clear(),
+    }
+    Ok(())
+}
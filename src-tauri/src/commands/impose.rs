@@ -0,0 +1,12 @@
+use tauri::command;
+use crate::models::pdf::{ImpositionConfig, ImpositionResult};
+use crate::services::pdf_imposer::PdfImposerService;
+use crate::utils::error::AppError;
+
+/// Lay out the pages of a PDF N-up or as a booklet
+#[command]
+pub async fn impose_pdf(config: ImpositionConfig) -> Result<ImpositionResult, AppError> {
+    tokio::task::spawn_blocking(move || PdfImposerService::impose(&config))
+        .await
+        .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
+}
@@ -1,20 +1,26 @@
 use tauri::command;
-use crate::models::pdf::{MergeConfig, PdfInfo};
+use crate::models::pdf::{MergeConfig, MergeResult, PdfInfo};
 use crate::services::pdf_merger::PdfMergerService;
 use crate::services::pdf_parser::PdfParserService;
+use crate::services::pdf_renderer::{DEFAULT_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE, MIN_THUMBNAIL_SIZE};
 use crate::utils::error::AppError;
 
 /// Get PDF file info (page count, metadata, thumbnail)
 #[command]
-pub async fn get_pdf_info(path: String) -> Result<PdfInfo, AppError> {
-    tokio::task::spawn_blocking(move || PdfParserService::get_info(&path))
+pub async fn get_pdf_info(
+    path: String,
+    password: Option<String>,
+    thumbnail_size: Option<u32>,
+) -> Result<PdfInfo, AppError> {
+    let thumbnail_size = thumbnail_size.unwrap_or(DEFAULT_THUMBNAIL_SIZE).clamp(MIN_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE);
+    tokio::task::spawn_blocking(move || PdfParserService::get_info(&path, password.as_deref(), thumbnail_size))
         .await
         .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
 }
 
 /// Merge multiple PDF files into one
 #[command]
-pub async fn merge_pdfs(config: MergeConfig) -> Result<String, AppError> {
+pub async fn merge_pdfs(config: MergeConfig) -> Result<MergeResult, AppError> {
     tokio::task::spawn_blocking(move || PdfMergerService::merge(&config))
         .await
         .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
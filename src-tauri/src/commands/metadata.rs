@@ -0,0 +1,20 @@
+use tauri::command;
+use crate::models::pdf::{MetadataUpdate, PdfMetadata};
+use crate::services::pdf_parser::PdfParserService;
+use crate::utils::error::AppError;
+
+/// Read the document properties, XMP and viewer settings of a PDF
+#[command]
+pub async fn get_pdf_metadata(path: String, password: Option<String>) -> Result<PdfMetadata, AppError> {
+    tokio::task::spawn_blocking(move || PdfParserService::get_metadata(&path, password.as_deref()))
+        .await
+        .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
+}
+
+/// Replace the metadata of a PDF with an incremental update
+#[command]
+pub async fn update_pdf_metadata(update: MetadataUpdate) -> Result<PdfMetadata, AppError> {
+    tokio::task::spawn_blocking(move || PdfParserService::update_metadata(&update))
+        .await
+        .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
+}
@@ -1,3 +1,10 @@
 pub mod merge;
 pub mod convert;
 pub mod file;
+pub mod impose;
+pub mod metadata;
+pub mod optimize;
+pub mod organize;
+pub mod split;
+pub mod stamp;
+pub mod thumbnail;
@@ -0,0 +1,12 @@
+use tauri::command;
+use crate::models::pdf::{OptimizeConfig, OptimizeResult};
+use crate::services::pdf_optimizer::PdfOptimizerService;
+use crate::utils::error::AppError;
+
+/// Shrink a PDF and report its size before and after
+#[command]
+pub async fn optimize_pdf(config: OptimizeConfig) -> Result<OptimizeResult, AppError> {
+    tokio::task::spawn_blocking(move || PdfOptimizerService::optimize(&config))
+        .await
+        .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
+}
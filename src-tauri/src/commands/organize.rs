@@ -0,0 +1,12 @@
+use tauri::command;
+use crate::models::pdf::{OrganizeConfig, OrganizeResult};
+use crate::services::pdf_organizer::PdfOrganizerService;
+use crate::utils::error::AppError;
+
+/// Delete, reorder, duplicate, rotate or extract pages of one PDF
+#[command]
+pub async fn organize_pages(config: OrganizeConfig) -> Result<OrganizeResult, AppError> {
+    tokio::task::spawn_blocking(move || PdfOrganizerService::organize(&config))
+        .await
+        .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
+}
@@ -0,0 +1,12 @@
+use tauri::command;
+use crate::models::pdf::{SplitConfig, SplitPart};
+use crate::services::pdf_splitter::PdfSplitterService;
+use crate::utils::error::AppError;
+
+/// Split one PDF into several files
+#[command]
+pub async fn split_pdf(config: SplitConfig) -> Result<Vec<SplitPart>, AppError> {
+    tokio::task::spawn_blocking(move || PdfSplitterService::split(&config))
+        .await
+        .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
+}
@@ -0,0 +1,12 @@
+use tauri::command;
+use crate::models::pdf::{StampConfig, StampResult};
+use crate::services::pdf_stamper::PdfStamperService;
+use crate::utils::error::AppError;
+
+/// Draw watermarks and stamps onto an existing PDF
+#[command]
+pub async fn stamp_pdf(config: StampConfig) -> Result<StampResult, AppError> {
+    tokio::task::spawn_blocking(move || PdfStamperService::stamp(&config))
+        .await
+        .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)))?
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use tauri::{command, AppHandle, Emitter, Manager};
+
+use crate::models::pdf::{PageThumbnailRequest, PageThumbnailsResult};
+use crate::services::pdf_renderer::PdfRendererService;
+use crate::utils::error::AppError;
+
+/// Cancellation flags of the running thumbnail tasks by task id
+static RUNNING_TASKS: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+
+fn running_tasks() -> MutexGuard<'static, HashMap<String, Arc<AtomicBool>>> {
+    RUNNING_TASKS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Render thumbnails for a page range, emitting a `page-thumbnail` event for
+/// every page as soon as it is ready
+#[command]
+pub async fn render_page_thumbnails(
+    app: AppHandle,
+    request: PageThumbnailRequest,
+) -> Result<PageThumbnailsResult, AppError> {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    running_tasks().insert(request.task_id.clone(), cancelled.clone());
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map(|dir| dir.join("thumbnails"))
+        .unwrap_or_else(|_| std::env::temp_dir().join("pdfcraft-thumbnails"));
+    let task_id = request.task_id.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        PdfRendererService::page_thumbnails(&request, &cache_dir, &cancelled, |thumbnail| {
+            let _ = app.emit("page-thumbnail", thumbnail);
+        })
+    })
+    .await
+    .map_err(|e| AppError::PdfError(format!("Task join error: {}", e)));
+    running_tasks().remove(&task_id);
+    result?
+}
+
+/// Stop a running thumbnail task; pages already sent stay valid
+#[command]
+pub async fn cancel_page_thumbnails(task_id: String) -> Result<(), AppError> {
+    if let Some(cancelled) = running_tasks().get(&task_id) {
+        cancelled.store(true, Ordering::SeqCst);
+        log::info!("Thumbnail task {} cancelled", task_id);
+    }
+    Ok(())
+}
@@ -3,7 +3,8 @@ pub mod models;
 pub mod services;
 pub mod utils;
 
-use commands::{convert, file, merge};
+use commands::{convert, file, impose, merge, metadata, optimize, organize, split, stamp, thumbnail};
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -12,14 +13,30 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
+        .setup(|app| {
+            if let Ok(dir) = app.path().resource_dir() {
+                utils::cmap::set_collection_dir(dir.join("cmaps"));
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             merge::get_pdf_info,
             merge::merge_pdfs,
+            split::split_pdf,
+            organize::organize_pages,
+            stamp::stamp_pdf,
+            impose::impose_pdf,
+            optimize::optimize_pdf,
+            metadata::get_pdf_metadata,
+            metadata::update_pdf_metadata,
+            thumbnail::render_page_thumbnails,
+            thumbnail::cancel_page_thumbnails,
             convert::convert_pdf_to_ebook,
             convert::cancel_conversion,
             convert::check_calibre_installed,
             file::get_app_config,
             file::update_app_config,
+            file::clear_document_cache,
         ])
         .run(tauri::generate_context!())
         .expect("error while running PDFCraft");
@@ -64,6 +64,9 @@ pub struct ConvertConfig {
     pub keep_images: bool,
     pub detect_tables: bool,
     pub font_size: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
 /// Progress payload sent to frontend
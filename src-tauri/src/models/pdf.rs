@@ -1,10 +1,40 @@
 use serde::{Deserialize, Serialize};
 
 /// PDF file entry for merge operations
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PdfFileEntry {
     pub path: String,
     pub selected_pages: Option<Vec<usize>>,
+    /// Password for an encrypted file
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Page range such as "1-3,7,10-"; takes precedence over `selected_pages`
+    #[serde(default)]
+    pub page_range: Option<String>,
+    /// Ordered page list that may repeat pages; takes precedence over
+    /// `page_range` and `selected_pages`
+    #[serde(default)]
+    pub pages: Option<Vec<PageSelection>>,
+    /// Take this file's pages in reverse order
+    #[serde(default)]
+    pub reverse: bool,
+    /// Append a blank page when this file contributes an odd number of pages,
+    /// so the next file starts on a front side when printed duplex
+    #[serde(default)]
+    pub pad_to_even: bool,
+}
+
+/// One output page taken from an input file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageSelection {
+    /// 1-based page number in the source file
+    pub page: usize,
+    /// Clockwise rotation in degrees added to the page (0, 90, 180, 270)
+    #[serde(default)]
+    pub rotation: i64,
+    /// Insert a blank page after this one
+    #[serde(default)]
+    pub blank_after: bool,
 }
 
 /// PDF document metadata
@@ -15,6 +45,211 @@ pub struct PdfInfo {
     pub title: Option<String>,
     pub author: Option<String>,
     pub thumbnail: String,
+    #[serde(default)]
+    pub encrypted: bool,
+    /// What the file's security settings allow; `None` when not encrypted
+    #[serde(default)]
+    pub permissions: Option<PdfPermissions>,
+}
+
+/// Thumbnails for a range of pages, delivered one page at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageThumbnailRequest {
+    /// Chosen by the caller so it can match events and cancel the task
+    pub task_id: String,
+    pub path: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Page range such as "1-3,7"; every page when unset
+    #[serde(default)]
+    pub pages: Option<String>,
+    /// Longer side of each thumbnail in pixels
+    #[serde(default = "default_thumbnail_size")]
+    pub size: u32,
+}
+
+fn default_thumbnail_size() -> u32 {
+    160
+}
+
+/// One rendered page of a thumbnail task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageThumbnail {
+    pub task_id: String,
+    /// 1-based page number
+    pub page: usize,
+    /// PNG data URL
+    pub thumbnail: String,
+}
+
+/// Outcome of a thumbnail task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageThumbnailsResult {
+    pub task_id: String,
+    pub rendered: usize,
+    /// Pages taken from the thumbnail cache
+    pub cached: usize,
+}
+
+/// The text of a page as runs placed on it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageText {
+    /// 1-based page number
+    pub page: usize,
+    /// Size of the page as displayed, in points
+    pub width: f32,
+    pub height: f32,
+    /// Runs in the order the page draws them
+    pub runs: Vec<TextRun>,
+    pub quality: TextQuality,
+}
+
+/// How much of the text of a page could be decoded to Unicode
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextQuality {
+    /// Visible characters shown, not counting white space
+    pub characters: usize,
+    /// Characters whose font doesn't tell what they are
+    pub unmapped: usize,
+    /// Share of the characters that were decoded, 1 for a page without text
+    pub score: f32,
+    /// Fonts of the unmapped characters
+    pub fonts: Vec<String>,
+}
+
+/// Text drawn along one baseline in one font, size and color
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextRun {
+    pub text: String,
+    /// Start of the baseline, in points from the top left corner of the page
+    /// as displayed
+    pub x: f32,
+    pub y: f32,
+    /// Length of the baseline in points
+    pub width: f32,
+    /// Font size in points as drawn, after the text and page transformations
+    pub font_size: f32,
+    /// PostScript name of the font without its subset tag
+    pub font_name: String,
+    pub bold: bool,
+    pub italic: bool,
+    /// Fill color as `#rrggbb`
+    pub color: String,
+    /// Direction of the baseline in degrees counterclockwise; 0 for
+    /// horizontal text
+    pub angle: f32,
+    /// Drawn invisibly, as the text layer of an OCRed scan is
+    pub invisible: bool,
+}
+
+/// Document-level metadata of a PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfMetadata {
+    #[serde(flatten)]
+    pub info: DocumentInfo,
+    /// Info `/CreationDate` and `/ModDate` in ISO 8601, or as stored when
+    /// they are not valid PDF dates
+    pub creation_date: Option<String>,
+    pub modification_date: Option<String>,
+    /// XMP packet of the catalog's `/Metadata` stream
+    pub xmp: Option<String>,
+    /// Natural language of the document (`/Lang`), e.g. "en-US"
+    pub language: Option<String>,
+    pub page_layout: Option<PageLayout>,
+    pub page_mode: Option<PageMode>,
+    pub viewer_preferences: ViewerPreferences,
+    pub pdf_version: String,
+    pub page_count: usize,
+    pub file_size: u64,
+    pub encrypted: bool,
+    /// Number of digital signatures in the file
+    pub signatures: usize,
+}
+
+/// Page arrangement used when the document is opened (`/PageLayout`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageLayout {
+    SinglePage,
+    OneColumn,
+    TwoColumnLeft,
+    TwoColumnRight,
+    TwoPageLeft,
+    TwoPageRight,
+}
+
+/// Panel shown when the document is opened (`/PageMode`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageMode {
+    UseNone,
+    UseOutlines,
+    UseThumbs,
+    FullScreen,
+    UseOc,
+    UseAttachments,
+}
+
+/// Flags of the catalog's `/ViewerPreferences`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewerPreferences {
+    #[serde(default)]
+    pub hide_toolbar: bool,
+    #[serde(default)]
+    pub hide_menubar: bool,
+    #[serde(default)]
+    pub hide_window_ui: bool,
+    #[serde(default)]
+    pub fit_window: bool,
+    #[serde(default)]
+    pub center_window: bool,
+    /// Show the title instead of the file name in the window title bar
+    #[serde(default)]
+    pub display_doc_title: bool,
+    /// Pages are read from right to left
+    #[serde(default)]
+    pub right_to_left: bool,
+}
+
+/// New metadata for a PDF. Every field replaces the current value, so fields
+/// left out are removed from the document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataUpdate {
+    pub input_path: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Where to save the result; the input file is updated in place when not set
+    #[serde(default)]
+    pub output_path: Option<String>,
+    #[serde(flatten)]
+    pub info: DocumentInfo,
+    /// XMP packet to store as is. When not set, the XMP metadata is
+    /// regenerated from the Info fields.
+    #[serde(default)]
+    pub xmp: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub page_layout: Option<PageLayout>,
+    #[serde(default)]
+    pub page_mode: Option<PageMode>,
+    #[serde(default)]
+    pub viewer_preferences: ViewerPreferences,
+}
+
+/// Permission flags of an encrypted PDF
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PdfPermissions {
+    pub print: bool,
+    pub modify: bool,
+    pub copy: bool,
+    pub annotate: bool,
+    pub fill_forms: bool,
+    pub extract_for_accessibility: bool,
+    pub assemble: bool,
+    pub print_high_quality: bool,
 }
 
 /// Merge configuration
@@ -24,4 +259,540 @@ pub struct MergeConfig {
     pub output_path: String,
     pub keep_bookmarks: bool,
     pub page_size: String,
+    /// How the pages of the input files are combined
+    #[serde(default)]
+    pub merge_mode: MergeMode,
+    /// Restart page labels for every input file instead of keeping the
+    /// labels of the sources
+    #[serde(default)]
+    pub restart_page_labels: bool,
+    /// Label prefix used when `restart_page_labels` is set. `{letter}` (A, B,
+    /// ...), `{index}` (1, 2, ...) and `{name}` (file name) are substituted.
+    #[serde(default = "default_page_label_prefix")]
+    pub page_label_prefix: String,
+    /// Password protection for the output file
+    #[serde(default)]
+    pub security: Option<OutputSecurity>,
+    /// Watermarks and stamps drawn onto the merged pages
+    #[serde(default)]
+    pub stamps: Vec<Stamp>,
+    /// Headers, footers and Bates numbers, numbered across all merged pages
+    #[serde(default)]
+    pub headers_footers: Vec<HeaderFooter>,
+    /// Recompress images and write a compact file
+    #[serde(default)]
+    pub optimize: Option<OptimizeOptions>,
+    /// Document properties of the output; fields left out are taken from the
+    /// first input file
+    #[serde(flatten)]
+    pub info: DocumentInfo,
+}
+
+/// Document properties stored in the Info dictionary and XMP metadata
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentInfo {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub keywords: Option<String>,
+    /// Application that created the original document
+    #[serde(default)]
+    pub creator: Option<String>,
+    /// Application that produced the PDF
+    #[serde(default)]
+    pub producer: Option<String>,
+}
+
+/// Passwords and permissions written to a protected output file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputSecurity {
+    /// Password needed to open the file; empty opens it without prompting
+    #[serde(default)]
+    pub user_password: String,
+    /// Password that lifts the restrictions; a random one is used when empty
+    #[serde(default)]
+    pub owner_password: String,
+    #[serde(default)]
+    pub allow_print: bool,
+    #[serde(default)]
+    pub allow_copy: bool,
+    #[serde(default)]
+    pub allow_modify: bool,
+    #[serde(default)]
+    pub allow_annotate: bool,
+}
+
+/// Page ordering of a merge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMode {
+    /// All pages of the first file, then all pages of the second, ...
+    #[default]
+    Concatenate,
+    /// One page from each file in turn, e.g. odd and even pages of a duplex scan
+    Interleave,
+}
+
+impl Default for MergeConfig {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            output_path: String::new(),
+            keep_bookmarks: true,
+            page_size: "original".to_string(),
+            merge_mode: MergeMode::default(),
+            restart_page_labels: false,
+            page_label_prefix: default_page_label_prefix(),
+            security: None,
+            stamps: Vec::new(),
+            headers_footers: Vec::new(),
+            optimize: None,
+            info: DocumentInfo::default(),
+        }
+    }
+}
+
+fn default_page_label_prefix() -> String {
+    "{letter}-".to_string()
+}
+
+/// Outcome of a merge operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeResult {
+    pub output_path: String,
+    /// Number of duplicate objects collapsed into a shared copy
+    pub deduplicated_objects: usize,
+    /// Stream bytes no longer written thanks to deduplication
+    pub bytes_saved: u64,
+    /// Problems worth telling the user about that did not stop the merge
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// How a document is cut into parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitMode {
+    /// A new part every `pages_per_part` pages
+    EveryNPages,
+    /// One part per entry of `ranges`
+    Ranges,
+    /// A new part at every top-level bookmark
+    Bookmarks,
+    /// As many pages per part as fit in `max_file_size`
+    MaxSize,
+}
+
+/// Split configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitConfig {
+    pub input_path: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
+    pub output_dir: String,
+    pub mode: SplitMode,
+    #[serde(default)]
+    pub pages_per_part: usize,
+    /// Page ranges such as "1-3,7", one per part
+    #[serde(default)]
+    pub ranges: Vec<String>,
+    /// Maximum size of each part in bytes
+    #[serde(default)]
+    pub max_file_size: u64,
+    /// File name of each part without extension. `{name}` (input file name),
+    /// `{index}`, `{title}` (bookmark title), `{start}` and `{end}` (page
+    /// numbers) are substituted.
+    #[serde(default = "default_split_name_template")]
+    pub name_template: String,
+    /// Replace files in `output_dir` that have the name of a part
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+fn default_split_name_template() -> String {
+    "{name}_{index}".to_string()
+}
+
+/// One file written by a split
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitPart {
+    pub path: String,
+    /// 1-based page numbers of the input file, in output order
+    pub pages: Vec<usize>,
+    pub title: Option<String>,
+    pub file_size: u64,
+}
+
+/// One step of a page organizer edit. Page numbers are 1-based range strings
+/// such as "1-3,7" and refer to the document as left by the previous step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PageOperation {
+    Delete {
+        pages: String,
+    },
+    /// New page order; every page has to be listed exactly once
+    Reorder {
+        order: String,
+    },
+    /// Insert copies right after each page
+    Duplicate {
+        pages: String,
+        #[serde(default = "default_copies")]
+        copies: usize,
+    },
+    /// Rotate clockwise by a multiple of 90 degrees
+    Rotate {
+        pages: String,
+        degrees: i64,
+    },
+    /// Keep only these pages, in the order given
+    Extract {
+        pages: String,
+    },
+}
+
+fn default_copies() -> usize {
+    1
+}
+
+/// Page organizer configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizeConfig {
+    pub input_path: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
+    pub output_path: String,
+    pub operations: Vec<PageOperation>,
+}
+
+/// Outcome of a page organizer edit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizeResult {
+    pub output_path: String,
+    pub page_count: usize,
+}
+
+/// A watermark or stamp drawn on top of (or behind) existing page content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stamp {
+    #[serde(flatten)]
+    pub content: StampContent,
+    /// Pages to stamp as a range string such as "1-3,7"; every page when unset
+    #[serde(default)]
+    pub pages: Option<String>,
+    #[serde(default)]
+    pub position: StampPosition,
+    /// Counter-clockwise rotation in degrees
+    #[serde(default)]
+    pub rotation: f32,
+    /// 0.0 (invisible) to 1.0 (opaque)
+    #[serde(default = "default_stamp_opacity")]
+    pub opacity: f32,
+    /// Width of image and PDF page stamps as a fraction of the page width
+    #[serde(default = "default_stamp_scale")]
+    pub scale: f32,
+    /// Draw underneath the page content instead of over it
+    #[serde(default)]
+    pub behind: bool,
+}
+
+/// What a stamp draws
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StampContent {
+    /// One or more lines of text; `\n` starts a new line
+    Text {
+        text: String,
+        #[serde(default = "default_stamp_font_size")]
+        font_size: f32,
+        /// `#RRGGBB`
+        #[serde(default = "default_stamp_color")]
+        color: String,
+        /// TrueType/OpenType font to embed; an installed font covering the
+        /// text is picked when unset
+        #[serde(default)]
+        font_path: Option<String>,
+    },
+    /// A PNG, JPEG or other raster image
+    Image { path: String },
+    /// A page of another PDF, kept as vector content
+    Pdf {
+        path: String,
+        #[serde(default = "default_stamp_page")]
+        page: usize,
+        #[serde(default)]
+        password: Option<String>,
+    },
+}
+
+/// Where a stamp sits on the page, as seen in a viewer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StampPosition {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+fn default_stamp_opacity() -> f32 {
+    0.5
+}
+
+fn default_stamp_scale() -> f32 {
+    0.5
+}
+
+fn default_stamp_font_size() -> f32 {
+    48.0
+}
+
+fn default_stamp_color() -> String {
+    "#808080".to_string()
+}
+
+fn default_stamp_page() -> usize {
+    1
+}
+
+/// A line of text repeated on every page, such as "Page {page} of {total}"
+/// or a Bates number like "ABC{page}"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderFooter {
+    /// Text with the tokens `{page}`, `{total}` (number of pages), `{last}`
+    /// (number of the last page), `{file}` (source file name) and `{date}`
+    pub template: String,
+    #[serde(default = "default_header_footer_position")]
+    pub position: StampPosition,
+    /// Distance from the left or right page edge, in points
+    #[serde(default = "default_header_footer_margin_x")]
+    pub margin_x: f32,
+    /// Distance from the top or bottom page edge, in points
+    #[serde(default = "default_header_footer_margin_y")]
+    pub margin_y: f32,
+    #[serde(default = "default_header_footer_font_size")]
+    pub font_size: f32,
+    /// `#RRGGBB`
+    #[serde(default = "default_header_footer_color")]
+    pub color: String,
+    #[serde(default)]
+    pub font_path: Option<String>,
+    /// Number of the first page
+    #[serde(default = "default_start_number")]
+    pub start_number: u64,
+    /// Minimum number of digits of `{page}`, padded with zeros
+    #[serde(default)]
+    pub zero_padding: usize,
+    /// Text for `{date}`; today's date as YYYY-MM-DD when unset
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+fn default_header_footer_position() -> StampPosition {
+    StampPosition::Bottom
+}
+
+fn default_header_footer_margin_x() -> f32 {
+    36.0
+}
+
+fn default_header_footer_margin_y() -> f32 {
+    24.0
+}
+
+fn default_header_footer_font_size() -> f32 {
+    10.0
+}
+
+fn default_header_footer_color() -> String {
+    "#000000".to_string()
+}
+
+fn default_start_number() -> u64 {
+    1
+}
+
+/// Stamp configuration for an existing PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StampConfig {
+    pub input_path: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
+    pub output_path: String,
+    pub stamps: Vec<Stamp>,
+}
+
+/// Outcome of stamping an existing PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StampResult {
+    pub output_path: String,
+    /// Number of pages that received at least one stamp
+    pub stamped_pages: usize,
+}
+
+/// How source pages are arranged on the output sheets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImpositionLayout {
+    /// `columns` x `rows` pages per sheet, in reading order
+    #[default]
+    NUp,
+    /// Two pages per side, ordered for duplex printing, folding and
+    /// saddle stitching
+    Booklet,
+}
+
+/// Order in which an N-up grid is filled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridOrder {
+    /// Row by row, each from left to right
+    #[default]
+    LeftToRight,
+    /// Column by column, each from top to bottom
+    TopToBottom,
+    /// Row by row, each from right to left
+    RightToLeft,
+}
+
+/// Imposition configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpositionConfig {
+    pub input_path: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
+    pub output_path: String,
+    #[serde(default)]
+    pub layout: ImpositionLayout,
+    /// Grid size of an N-up layout; booklets always use 2 x 1
+    #[serde(default = "default_imposition_columns")]
+    pub columns: usize,
+    #[serde(default = "default_imposition_rows")]
+    pub rows: usize,
+    #[serde(default)]
+    pub order: GridOrder,
+    /// "a4", "letter" or "original" (the size of the first page). Portrait
+    /// or landscape is picked to fit the pages best.
+    #[serde(default = "default_sheet_size")]
+    pub sheet_size: String,
+    /// Space between the sheet edges and the pages, in points
+    #[serde(default = "default_imposition_margin")]
+    pub margin: f32,
+    /// Space between neighbouring pages, in points
+    #[serde(default = "default_imposition_gutter")]
+    pub gutter: f32,
+    #[serde(default)]
+    pub crop_marks: bool,
+    /// Pages per booklet signature, a multiple of 4. 0 folds the whole
+    /// document as one signature.
+    #[serde(default)]
+    pub signature_size: usize,
+}
+
+fn default_imposition_columns() -> usize {
+    2
+}
+
+fn default_imposition_rows() -> usize {
+    1
+}
+
+fn default_sheet_size() -> String {
+    "original".to_string()
+}
+
+fn default_imposition_margin() -> f32 {
+    18.0
+}
+
+fn default_imposition_gutter() -> f32 {
+    12.0
+}
+
+/// Outcome of an imposition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpositionResult {
+    pub output_path: String,
+    /// Number of output pages (sheet sides)
+    pub page_count: usize,
+}
+
+/// Size reductions applied by an optimization pass. Unused objects,
+/// duplicate streams, page thumbnails and private application data are
+/// always removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizeOptions {
+    /// Re-encode large images as JPEG
+    #[serde(default = "default_true")]
+    pub recompress_images: bool,
+    /// Resolution images are downsampled to, measured at the size they are
+    /// displayed on the page
+    #[serde(default = "default_image_dpi")]
+    pub image_dpi: f32,
+    /// JPEG quality from 1 to 100
+    #[serde(default = "default_jpeg_quality")]
+    pub jpeg_quality: u8,
+    /// Pack objects into compressed object streams with a cross-reference
+    /// stream (PDF 1.5)
+    #[serde(default = "default_true")]
+    pub object_streams: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            recompress_images: true,
+            image_dpi: default_image_dpi(),
+            jpeg_quality: default_jpeg_quality(),
+            object_streams: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_image_dpi() -> f32 {
+    150.0
+}
+
+fn default_jpeg_quality() -> u8 {
+    75
+}
+
+/// Optimization of an existing PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizeConfig {
+    pub input_path: String,
+    /// Password for an encrypted input file
+    #[serde(default)]
+    pub password: Option<String>,
+    pub output_path: String,
+    #[serde(flatten)]
+    pub options: OptimizeOptions,
+}
+
+/// Before/after report of an optimization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizeResult {
+    pub output_path: String,
+    pub original_size: u64,
+    pub optimized_size: u64,
+    pub images_recompressed: usize,
+    /// Unused and duplicate objects that were dropped
+    pub objects_removed: usize,
 }
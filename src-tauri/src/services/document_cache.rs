@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::SystemTime;
+
+use lopdf::{Document, Object};
+use sha2::{Digest, Sha256};
+
+use crate::models::pdf::PdfPermissions;
+use crate::services::pdf_security::PdfSecurityService;
+use crate::utils::error::AppError;
+
+/// Documents kept by the shared cache
+const MAX_CACHED_DOCUMENTS: usize = 8;
+/// Estimated memory the shared cache may hold
+const MAX_CACHE_BYTES: usize = 512 * 1024 * 1024;
+
+/// Parsed documents shared by all operations, so that listing, previewing
+/// and converting a file parse it only once. Documents are found by
+/// canonical path, modification time and size, so a changed file is loaded
+/// again.
+pub struct DocumentCacheService;
+
+static SHARED_CACHE: OnceLock<Mutex<DocumentCache>> = OnceLock::new();
+
+impl DocumentCacheService {
+    /// Load a decrypted PDF through the shared cache
+    pub fn load(path: &str, password: Option<&str>) -> Result<Arc<Document>, AppError> {
+        Self::load_with_permissions(path, password).map(|(doc, _)| doc)
+    }
+
+    /// Like [`PdfSecurityService::load_with_permissions`], through the
+    /// shared cache. An encrypted document is only handed out again for the
+    /// password that opened it.
+    pub fn load_with_permissions(
+        path: &str,
+        password: Option<&str>,
+    ) -> Result<(Arc<Document>, Option<PdfPermissions>), AppError> {
+        let key = FileKey::of(path)?;
+        if let Some(hit) = Self::shared().get(&key, password) {
+            return Ok(hit);
+        }
+        let (doc, permissions) = PdfSecurityService::load_with_permissions(path, password)?;
+        let doc = Arc::new(doc);
+        Self::shared().insert(key, doc.clone(), permissions.clone(), password);
+        Ok((doc, permissions))
+    }
+
+    /// Drop every cached version of the file at `path`
+    pub fn evict(path: &str) {
+        if let Ok(path) = fs::canonicalize(path) {
+            Self::shared().evict(&path);
+        }
+    }
+
+    /// Drop all cached documents
+    pub fn clear() {
+        Self::shared().clear();
+    }
+
+    fn shared() -> std::sync::MutexGuard<'static, DocumentCache> {
+        SHARED_CACHE
+            .get_or_init(|| Mutex::new(DocumentCache::new(MAX_CACHED_DOCUMENTS, MAX_CACHE_BYTES)))
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Identifies the contents of a file on disk
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FileKey {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    size: u64,
+}
+
+impl FileKey {
+    fn of(path: &str) -> Result<Self, AppError> {
+        let canonical = fs::canonicalize(path).map_err(|_| AppError::FileNotFound(path.to_string()))?;
+        let metadata = fs::metadata(&canonical)?;
+        Ok(Self {
+            path: canonical,
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+        })
+    }
+}
+
+struct CachedDocument {
+    doc: Arc<Document>,
+    permissions: Option<PdfPermissions>,
+    /// Digest of the password that opened an encrypted document
+    password: Option<[u8; 32]>,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Least recently used documents, limited by count and estimated memory
+struct DocumentCache {
+    entries: HashMap<FileKey, CachedDocument>,
+    max_documents: usize,
+    max_bytes: usize,
+    total_bytes: usize,
+    clock: u64,
+}
+
+impl DocumentCache {
+    fn new(max_documents: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            max_documents,
+            max_bytes,
+            total_bytes: 0,
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: &FileKey, password: Option<&str>) -> Option<(Arc<Document>, Option<PdfPermissions>)> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        if entry.permissions.is_some() && entry.password != Some(password_digest(password)) {
+            return None;
+        }
+        entry.last_used = self.clock;
+        Some((entry.doc.clone(), entry.permissions.clone()))
+    }
+
+    fn insert(&mut self, key: FileKey, doc: Arc<Document>, permissions: Option<PdfPermissions>, password: Option<&str>) {
+        // Older versions of the file won't be asked for again
+        self.evict(&key.path);
+        let bytes = estimated_size(&doc);
+        if bytes > self.max_bytes || self.max_documents == 0 {
+            return;
+        }
+        while self.entries.len() >= self.max_documents || self.total_bytes + bytes > self.max_bytes {
+            let Some(oldest) = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+        self.clock += 1;
+        self.total_bytes += bytes;
+        let password = permissions.as_ref().map(|_| password_digest(password));
+        self.entries.insert(key, CachedDocument {
+            doc,
+            permissions,
+            password,
+            bytes,
+            last_used: self.clock,
+        });
+    }
+
+    fn evict(&mut self, path: &std::path::Path) {
+        let keys: Vec<FileKey> = self.entries.keys().filter(|key| key.path == path).cloned().collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.total_bytes = 0;
+    }
+
+    fn remove(&mut self, key: &FileKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.total_bytes -= entry.bytes;
+        }
+    }
+}
+
+fn password_digest(password: Option<&str>) -> [u8; 32] {
+    Sha256::digest(password.unwrap_or("").as_bytes()).into()
+}
+
+/// Rough memory use of a parsed document
+fn estimated_size(doc: &Document) -> usize {
+    fn object_size(obj: &Object) -> usize {
+        match obj {
+            Object::String(bytes, _) | Object::Name(bytes) => 32 + bytes.len(),
+            Object::Array(items) => 32 + items.iter().map(object_size).sum::<usize>(),
+            Object::Dictionary(dict) => 48 + dict.iter().map(|(key, value)| key.len() + object_size(value)).sum::<usize>(),
+            Object::Stream(stream) => {
+                48 + stream.content.len() + stream.dict.iter().map(|(key, value)| key.len() + object_size(value)).sum::<usize>()
+            }
+            _ => 32,
+        }
+    }
+    doc.objects.values().map(object_size).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pdf::OutputSecurity;
+    use crate::services::test_support::*;
+
+    fn key(dir: &tempfile::TempDir, name: &str, pages: usize) -> (FileKey, Arc<Document>) {
+        let mut doc = build_test_document(pages);
+        let path = save_test_document(&mut doc, dir.path(), name);
+        (FileKey::of(&path).unwrap(), Arc::new(doc))
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = DocumentCache::new(2, usize::MAX);
+        let (a, doc_a) = key(&dir, "a.pdf", 1);
+        let (b, doc_b) = key(&dir, "b.pdf", 1);
+        let (c, doc_c) = key(&dir, "c.pdf", 1);
+
+        cache.insert(a.clone(), doc_a, None, None);
+        cache.insert(b.clone(), doc_b, None, None);
+        assert!(cache.get(&a, None).is_some());
+        cache.insert(c.clone(), doc_c, None, None);
+        assert!(cache.get(&a, None).is_some());
+        assert!(cache.get(&b, None).is_none());
+        assert!(cache.get(&c, None).is_some());
+
+        cache.evict(&a.path);
+        assert!(cache.get(&a, None).is_none());
+        assert_eq!(cache.total_bytes, cache.entries.values().map(|entry| entry.bytes).sum::<usize>());
+    }
+
+    #[test]
+    fn test_cache_respects_memory_limit_and_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (small, small_doc) = key(&dir, "small.pdf", 1);
+        let (large, large_doc) = key(&dir, "large.pdf", 50);
+        let mut cache = DocumentCache::new(8, estimated_size(&large_doc) - 1);
+
+        cache.insert(large.clone(), large_doc, None, None);
+        assert!(cache.get(&large, None).is_none());
+        cache.insert(small.clone(), small_doc, None, None);
+        assert!(cache.get(&small, None).is_some());
+
+        let changed = FileKey {
+            size: small.size + 1,
+            ..small.clone()
+        };
+        assert!(cache.get(&changed, None).is_none());
+        cache.insert(changed.clone(), Arc::new(build_test_document(2)), None, None);
+        assert!(cache.get(&small, None).is_none());
+        assert!(cache.get(&changed, None).is_some());
+    }
+
+    #[test]
+    fn test_shared_cache_checks_passwords() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(1);
+        let security = OutputSecurity {
+            user_password: "secret".to_string(),
+            ..Default::default()
+        };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let path = save_test_document(&mut doc, dir.path(), "encrypted.pdf");
+
+        let (first, permissions) = DocumentCacheService::load_with_permissions(&path, Some("secret")).unwrap();
+        assert!(permissions.is_some());
+        let second = DocumentCacheService::load(&path, Some("secret")).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(matches!(DocumentCacheService::load(&path, None), Err(AppError::EncryptedDocument(_))));
+        assert!(matches!(DocumentCacheService::load(&path, Some("wrong")), Err(AppError::EncryptedDocument(_))));
+
+        DocumentCacheService::evict(&path);
+        let third = DocumentCacheService::load(&path, Some("secret")).unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+    }
+}
@@ -1,11 +1,29 @@
+pub mod document_cache;
 pub mod epub_builder;
 pub mod format_converter;
+pub mod pdf_imposer;
 pub mod pdf_merger;
+pub mod pdf_optimizer;
+pub mod pdf_organizer;
 pub mod pdf_parser;
+pub mod pdf_renderer;
+pub mod pdf_security;
+pub mod pdf_splitter;
+pub mod pdf_stamper;
 pub mod ocr_engine;
 
+#[cfg(test)]
+mod test_support;
+
 pub mod mod_prelude {
+    pub use super::document_cache::DocumentCacheService;
+    pub use super::pdf_imposer::PdfImposerService;
     pub use super::pdf_merger::PdfMergerService;
+    pub use super::pdf_optimizer::PdfOptimizerService;
+    pub use super::pdf_organizer::PdfOrganizerService;
     pub use super::pdf_parser::PdfParserService;
+    pub use super::pdf_renderer::PdfRendererService;
+    pub use super::pdf_splitter::PdfSplitterService;
+    pub use super::pdf_stamper::PdfStamperService;
     pub use super::epub_builder::EpubBuilderService;
 }
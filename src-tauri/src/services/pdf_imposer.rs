@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use crate::models::pdf::{GridOrder, ImpositionConfig, ImpositionLayout, ImpositionResult};
+use crate::services::pdf_merger::PdfMergerService;
+use crate::services::pdf_security::PdfSecurityService;
+use crate::utils::error::AppError;
+
+/// Gap between a page corner and its crop marks, in points
+const CROP_MARK_OFFSET: f32 = 3.0;
+/// Length of a crop mark, in points
+const CROP_MARK_LENGTH: f32 = 12.0;
+
+pub struct PdfImposerService;
+
+impl PdfImposerService {
+    /// Place the pages of a PDF onto sheets, N-up or as a booklet, and save
+    /// the result to `config.output_path`
+    pub fn impose(config: &ImpositionConfig) -> Result<ImpositionResult, AppError> {
+        let (columns, rows) = match config.layout {
+            ImpositionLayout::NUp => (config.columns, config.rows),
+            ImpositionLayout::Booklet => (2, 1),
+        };
+        if columns == 0 || rows == 0 {
+            return Err(AppError::ConfigError("The grid needs at least one column and one row".to_string()));
+        }
+        if config.margin < 0.0 || config.gutter < 0.0 {
+            return Err(AppError::ConfigError("Margins and gutters cannot be negative".to_string()));
+        }
+
+        let (source, cipher) = PdfSecurityService::load_with_cipher(&config.input_path, config.password.as_deref())?;
+        let page_ids: Vec<ObjectId> = source.page_iter().collect();
+        if page_ids.is_empty() {
+            return Err(AppError::PdfError(format!("{} has no pages", config.input_path)));
+        }
+        let sheets = match config.layout {
+            ImpositionLayout::NUp => Self::grid_sheets(page_ids.len(), columns, rows, config.order),
+            ImpositionLayout::Booklet => Self::booklet_sheets(page_ids.len(), config.signature_size)?,
+        };
+
+        let mut imposed = Document::with_version(&source.version);
+        let mut id_map = BTreeMap::new();
+        let mut forms = Vec::new();
+        for &page_id in &page_ids {
+            forms.push(PdfMergerService::page_to_form(&source, page_id, &mut id_map, &mut imposed)?);
+        }
+
+        let grid = Grid {
+            columns,
+            rows,
+            margin: config.margin,
+            gutter: config.gutter,
+            sheet: Self::sheet_size(&config.sheet_size, forms[0].1, columns, rows, config)?,
+            toward_fold: config.layout == ImpositionLayout::Booklet,
+            crop_marks: config.crop_marks,
+        };
+
+        let pages_id = imposed.new_object_id();
+        let mut kids = Vec::new();
+        for cells in &sheets {
+            let content = Self::sheet_content(&grid, cells, &forms)?;
+            let xobjects: Dictionary = cells
+                .iter()
+                .enumerate()
+                .filter_map(|(cell, page)| Some((format!("P{}", cell), Object::Reference(forms[(*page)?].0))))
+                .collect();
+            let content_id = imposed.add_object(Stream::new(Dictionary::new(), content));
+            let page = Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Page".to_vec())),
+                ("Parent", Object::Reference(pages_id)),
+                (
+                    "MediaBox",
+                    Object::Array(vec![0.into(), 0.into(), Object::Real(grid.sheet.0), Object::Real(grid.sheet.1)]),
+                ),
+                ("Resources", Object::Dictionary(Dictionary::from_iter(vec![("XObject", Object::Dictionary(xobjects))]))),
+                ("Contents", Object::Reference(content_id)),
+            ]);
+            kids.push(Object::Reference(imposed.add_object(page)));
+        }
+        imposed.objects.insert(pages_id, Object::Dictionary(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Count", Object::Integer(kids.len() as i64)),
+            ("Kids", Object::Array(kids)),
+        ])));
+        let catalog_id = imposed.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+        ]));
+        imposed.trailer.set("Root", Object::Reference(catalog_id));
+
+        // Keep the document title, author and so on
+        if let Ok((_, info @ Object::Dictionary(_))) = source.trailer.get(b"Info").and_then(|info| source.dereference(info)) {
+            let info = PdfMergerService::copy_with_dependencies(&source, info, &mut id_map, &mut imposed);
+            let info_id = imposed.add_object(info);
+            imposed.trailer.set("Info", Object::Reference(info_id));
+        }
+
+        imposed.compress();
+        if let Some(cipher) = &cipher {
+            PdfSecurityService::reencrypt(&mut imposed, cipher)?;
+        }
+        imposed.save(&config.output_path)
+            .map_err(|e| AppError::PdfError(format!("Failed to save imposed PDF: {}", e)))?;
+
+        log::info!(
+            "Imposed {} pages of {} onto {} sheets",
+            page_ids.len(),
+            config.input_path,
+            sheets.len()
+        );
+        Ok(ImpositionResult {
+            output_path: config.output_path.clone(),
+            page_count: sheets.len(),
+        })
+    }
+
+    /// Source page indices for each cell (row by row from the top left) of
+    /// every N-up sheet
+    fn grid_sheets(page_count: usize, columns: usize, rows: usize, order: GridOrder) -> Vec<Vec<Option<usize>>> {
+        let per_sheet = columns * rows;
+        (0..page_count)
+            .collect::<Vec<_>>()
+            .chunks(per_sheet)
+            .map(|pages| {
+                let mut cells = vec![None; per_sheet];
+                for (k, &page) in pages.iter().enumerate() {
+                    let (row, column) = match order {
+                        GridOrder::LeftToRight => (k / columns, k % columns),
+                        GridOrder::TopToBottom => (k % rows, k / rows),
+                        GridOrder::RightToLeft => (k / columns, columns - 1 - k % columns),
+                    };
+                    cells[row * columns + column] = Some(page);
+                }
+                cells
+            })
+            .collect()
+    }
+
+    /// Left and right page of every booklet sheet side, front then back.
+    /// Pages are padded with blanks to a multiple of 4 and folded in
+    /// signatures of `signature_size` pages.
+    fn booklet_sheets(page_count: usize, signature_size: usize) -> Result<Vec<Vec<Option<usize>>>, AppError> {
+        if signature_size % 4 != 0 {
+            return Err(AppError::ConfigError(format!(
+                "Signature size must be a multiple of 4, got {}",
+                signature_size
+            )));
+        }
+        let padded = page_count.div_ceil(4) * 4;
+        let signature_size = if signature_size == 0 { padded } else { signature_size };
+        let page = |index: usize| (index < page_count).then_some(index);
+
+        let mut sides = Vec::new();
+        for start in (0..padded).step_by(signature_size) {
+            let size = signature_size.min(padded - start);
+            for sheet in 0..size / 4 {
+                let (outer, inner) = (start + size - 1 - 2 * sheet, start + 2 * sheet);
+                sides.push(vec![page(outer), page(inner)]);
+                sides.push(vec![page(inner + 1), page(outer - 1)]);
+            }
+        }
+        Ok(sides)
+    }
+
+    /// Sheet size in points, turned to whichever orientation lets the first
+    /// page appear largest
+    fn sheet_size(
+        sheet_size: &str,
+        page_size: (f32, f32),
+        columns: usize,
+        rows: usize,
+        config: &ImpositionConfig,
+    ) -> Result<(f32, f32), AppError> {
+        let (short, long) = match PdfMergerService::target_page_size(sheet_size)? {
+            Some((width, height)) => (width.min(height), width.max(height)),
+            None => (page_size.0.min(page_size.1), page_size.0.max(page_size.1)),
+        };
+        let fit = |(width, height): (f32, f32)| {
+            let cell_width = (width - 2.0 * config.margin - (columns - 1) as f32 * config.gutter) / columns as f32;
+            let cell_height = (height - 2.0 * config.margin - (rows - 1) as f32 * config.gutter) / rows as f32;
+            (cell_width / page_size.0).min(cell_height / page_size.1)
+        };
+
+        let (portrait, landscape) = ((short, long), (long, short));
+        let sheet = if fit(landscape) > fit(portrait) { landscape } else { portrait };
+        if fit(sheet) <= 0.0 {
+            return Err(AppError::ConfigError("Margins and gutters leave no room for the pages".to_string()));
+        }
+        Ok(sheet)
+    }
+
+    /// Content stream drawing the pages in `cells` onto one sheet
+    fn sheet_content(grid: &Grid, cells: &[Option<usize>], forms: &[(ObjectId, (f32, f32))]) -> Result<Vec<u8>, AppError> {
+        let (sheet_width, sheet_height) = grid.sheet;
+        let cell_width = (sheet_width - 2.0 * grid.margin - (grid.columns - 1) as f32 * grid.gutter) / grid.columns as f32;
+        let cell_height = (sheet_height - 2.0 * grid.margin - (grid.rows - 1) as f32 * grid.gutter) / grid.rows as f32;
+
+        let mut operations = Vec::new();
+        let mut marks = Vec::new();
+        for (cell, page) in cells.iter().enumerate() {
+            let Some(page) = *page else {
+                continue;
+            };
+            let (width, height) = forms[page].1;
+            let (row, column) = (cell / grid.columns, cell % grid.columns);
+            let cell_x = grid.margin + column as f32 * (cell_width + grid.gutter);
+            let cell_y = sheet_height - grid.margin - (row + 1) as f32 * cell_height - row as f32 * grid.gutter;
+
+            let scale = (cell_width / width).min(cell_height / height);
+            let (width, height) = (width * scale, height * scale);
+            // Booklet pages sit against the fold instead of centered
+            let x = match (grid.toward_fold, column) {
+                (true, 0) => cell_x + cell_width - width,
+                (true, _) => cell_x,
+                (false, _) => cell_x + (cell_width - width) / 2.0,
+            };
+            let y = cell_y + (cell_height - height) / 2.0;
+
+            operations.push(Operation::new("q", vec![]));
+            operations.push(Operation::new(
+                "cm",
+                [scale, 0.0, 0.0, scale, x, y].into_iter().map(Object::Real).collect(),
+            ));
+            operations.push(Operation::new("Do", vec![Object::Name(format!("P{}", cell).into_bytes())]));
+            operations.push(Operation::new("Q", vec![]));
+            if grid.crop_marks {
+                Self::crop_marks(&mut marks, [x, y, x + width, y + height]);
+            }
+        }
+
+        if !marks.is_empty() {
+            operations.push(Operation::new("q", vec![]));
+            operations.push(Operation::new("G", vec![0.into()]));
+            operations.push(Operation::new("w", vec![Object::Real(0.25)]));
+            operations.extend(marks);
+            operations.push(Operation::new("S", vec![]));
+            operations.push(Operation::new("Q", vec![]));
+        }
+        Ok(Content { operations }.encode()?)
+    }
+
+    /// Path segments for the crop marks around the corners of `rect`
+    fn crop_marks(out: &mut Vec<Operation>, rect: [f32; 4]) {
+        let mut line = |from: (f32, f32), to: (f32, f32)| {
+            out.push(Operation::new("m", vec![Object::Real(from.0), Object::Real(from.1)]));
+            out.push(Operation::new("l", vec![Object::Real(to.0), Object::Real(to.1)]));
+        };
+        for (x, dx) in [(rect[0], -1.0), (rect[2], 1.0)] {
+            for (y, dy) in [(rect[1], -1.0), (rect[3], 1.0)] {
+                line(
+                    (x + dx * CROP_MARK_OFFSET, y),
+                    (x + dx * (CROP_MARK_OFFSET + CROP_MARK_LENGTH), y),
+                );
+                line(
+                    (x, y + dy * CROP_MARK_OFFSET),
+                    (x, y + dy * (CROP_MARK_OFFSET + CROP_MARK_LENGTH)),
+                );
+            }
+        }
+    }
+}
+
+/// Cell layout shared by every sheet
+struct Grid {
+    columns: usize,
+    rows: usize,
+    margin: f32,
+    gutter: f32,
+    sheet: (f32, f32),
+    /// Align pages toward the center fold (booklets)
+    toward_fold: bool,
+    crop_marks: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pdf::OutputSecurity;
+    use crate::services::test_support::*;
+
+    fn config(input_path: String, output_path: String, layout: ImpositionLayout) -> ImpositionConfig {
+        ImpositionConfig {
+            input_path,
+            password: None,
+            output_path,
+            layout,
+            columns: 2,
+            rows: 1,
+            order: GridOrder::LeftToRight,
+            sheet_size: "original".to_string(),
+            margin: 18.0,
+            gutter: 12.0,
+            crop_marks: false,
+            signature_size: 0,
+        }
+    }
+
+    #[test]
+    fn test_grid_sheets_orders() {
+        let sheets = PdfImposerService::grid_sheets(5, 2, 2, GridOrder::LeftToRight);
+        assert_eq!(sheets, vec![vec![Some(0), Some(1), Some(2), Some(3)], vec![Some(4), None, None, None]]);
+        let sheets = PdfImposerService::grid_sheets(4, 2, 2, GridOrder::TopToBottom);
+        assert_eq!(sheets, vec![vec![Some(0), Some(2), Some(1), Some(3)]]);
+        let sheets = PdfImposerService::grid_sheets(3, 3, 1, GridOrder::RightToLeft);
+        assert_eq!(sheets, vec![vec![Some(2), Some(1), Some(0)]]);
+    }
+
+    #[test]
+    fn test_booklet_sheets() {
+        // 6 pages pad to 8: outer sheet 8|1, 2|7, inner sheet 6|3, 4|5
+        let sides = PdfImposerService::booklet_sheets(6, 0).unwrap();
+        assert_eq!(sides, vec![
+            vec![None, Some(0)],
+            vec![Some(1), None],
+            vec![Some(5), Some(2)],
+            vec![Some(3), Some(4)],
+        ]);
+        // Two 4-page signatures
+        let sides = PdfImposerService::booklet_sheets(8, 4).unwrap();
+        assert_eq!(sides[0], vec![Some(3), Some(0)]);
+        assert_eq!(sides[2], vec![Some(7), Some(4)]);
+        assert!(PdfImposerService::booklet_sheets(8, 6).is_err());
+    }
+
+    #[test]
+    fn test_impose_two_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(3);
+        let rotated = doc.page_iter().nth(2).unwrap();
+        doc.get_dictionary_mut(rotated).unwrap().set("Rotate", Object::Integer(90));
+        let input = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let output = dir.path().join("2up.pdf").to_string_lossy().to_string();
+
+        let config = ImpositionConfig {
+            crop_marks: true,
+            ..config(input, output.clone(), ImpositionLayout::NUp)
+        };
+        let result = PdfImposerService::impose(&config).unwrap();
+        assert_eq!(result.page_count, 2);
+
+        let imposed = Document::load(&output).unwrap();
+        let sheets: Vec<ObjectId> = imposed.page_iter().collect();
+        // Two portrait Letter pages fit best on a landscape Letter sheet
+        let media_box = imposed.get_dictionary(sheets[0]).unwrap().get(b"MediaBox").unwrap().as_array().unwrap();
+        let size: Vec<f32> = media_box.iter().map(|v| v.as_float().unwrap()).collect();
+        assert_eq!(size, vec![0.0, 0.0, 792.0, 612.0]);
+
+        let content = Content::decode(&imposed.get_page_content(sheets[0]).unwrap()).unwrap();
+        let drawn: Vec<&Operation> = content.operations.iter().filter(|op| op.operator == "Do").collect();
+        assert_eq!(drawn.len(), 2);
+        assert_eq!(content.operations.iter().filter(|op| op.operator == "m").count(), 16);
+
+        // The rotated page is drawn upright through the form matrix
+        let xobjects = imposed.get_dictionary(sheets[1]).unwrap().get(b"Resources").unwrap().as_dict().unwrap();
+        let form_ref = xobjects.get(b"XObject").unwrap().as_dict().unwrap().get(b"P0").unwrap();
+        let form = imposed.get_object(form_ref.as_reference().unwrap()).unwrap().as_stream().unwrap();
+        let matrix: Vec<f32> = form.dict.get(b"Matrix").unwrap().as_array().unwrap().iter().map(|v| v.as_float().unwrap()).collect();
+        assert_eq!(matrix, vec![0.0, -1.0, 1.0, 0.0, 0.0, 612.0]);
+        let drawing = Content::decode(&form.get_plain_content().unwrap()).unwrap();
+        assert_eq!(drawing.operations[1].operands[0].as_i64().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_impose_booklet() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = save_test_document(&mut build_test_document(5), dir.path(), "input.pdf");
+        let output = dir.path().join("booklet.pdf").to_string_lossy().to_string();
+
+        let result = PdfImposerService::impose(&config(input, output.clone(), ImpositionLayout::Booklet)).unwrap();
+        assert_eq!(result.page_count, 4);
+
+        let imposed = Document::load(&output).unwrap();
+        let sheets: Vec<ObjectId> = imposed.page_iter().collect();
+        // Front of the outer sheet: blank page 8 on the left, page 1 on the right
+        let content = Content::decode(&imposed.get_page_content(sheets[0]).unwrap()).unwrap();
+        let cells: Vec<&[u8]> = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "Do")
+            .map(|op| op.operands[0].as_name().unwrap())
+            .collect();
+        assert_eq!(cells, vec![b"P1".as_slice()]);
+        // The right page starts at the fold
+        let cm = content.operations.iter().find(|op| op.operator == "cm").unwrap();
+        assert_eq!(cm.operands[4].as_float().unwrap(), 396.0 + 6.0);
+    }
+
+    #[test]
+    fn test_impose_keeps_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(4);
+        let security = OutputSecurity { user_password: "secret".to_string(), ..Default::default() };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let input = save_test_document(&mut doc, dir.path(), "locked.pdf");
+        let output = dir.path().join("2up.pdf").to_string_lossy().to_string();
+
+        let config = ImpositionConfig {
+            password: Some("secret".to_string()),
+            ..config(input, output.clone(), ImpositionLayout::NUp)
+        };
+        assert_eq!(PdfImposerService::impose(&config).unwrap().page_count, 2);
+        assert!(matches!(PdfSecurityService::load(&output, None), Err(AppError::EncryptedDocument(_))));
+        assert_eq!(PdfSecurityService::load(&output, Some("secret")).unwrap().get_pages().len(), 2);
+    }
+
+    #[test]
+    fn test_impose_rejects_invalid_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = save_test_document(&mut build_test_document(2), dir.path(), "input.pdf");
+        let output = dir.path().join("out.pdf").to_string_lossy().to_string();
+
+        let empty_grid = ImpositionConfig { columns: 0, ..config(input.clone(), output.clone(), ImpositionLayout::NUp) };
+        assert!(PdfImposerService::impose(&empty_grid).is_err());
+        let no_room = ImpositionConfig { margin: 400.0, ..config(input, output, ImpositionLayout::NUp) };
+        assert!(PdfImposerService::impose(&no_room).is_err());
+    }
+}
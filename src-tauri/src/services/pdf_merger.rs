@@ -1,35 +1,84 @@
-use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use lopdf::content::{Content, Operation};
 use lopdf::{Document, Object, ObjectId, Dictionary};
 use crate::utils::error::AppError;
-use crate::models::pdf::MergeConfig;
+use crate::models::pdf::{DocumentInfo, MergeConfig, MergeMode, MergeResult, PageSelection, PdfFileEntry};
+use crate::services::document_cache::DocumentCacheService;
+use crate::services::pdf_optimizer::{Optimization, PdfOptimizerService};
+use crate::services::pdf_parser::PdfParserService;
+use crate::services::pdf_security::{DocumentCipher, PdfSecurityService};
+use crate::services::pdf_stamper::PdfStamperService;
+use crate::utils::page_range::parse_page_range;
+
+/// A4 paper size in PDF points
+const A4_SIZE: (f32, f32) = (595.28, 841.89);
+/// US Letter paper size in PDF points
+const LETTER_SIZE: (f32, f32) = (612.0, 792.0);
+
+/// Page attributes that may be inherited from ancestor Pages nodes
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Dictionary types that belong to one place in the document structure and
+/// must never be shared, even when two of them happen to be identical
+const UNSHAREABLE_TYPES: [&[u8]; 5] = [b"Page", b"Pages", b"Catalog", b"Outlines", b"Annot"];
+
+/// Annotation entries holding coordinates in default user space
+const ANNOT_COORDINATE_KEYS: [&[u8]; 5] = [b"Rect", b"QuadPoints", b"L", b"Vertices", b"InkList"];
 
 pub struct PdfMergerService;
 
 impl PdfMergerService {
     /// Merge multiple PDF files into one
-    pub fn merge(config: &MergeConfig) -> Result<String, AppError> {
+    pub fn merge(config: &MergeConfig) -> Result<MergeResult, AppError> {
         if config.files.is_empty() {
             return Err(AppError::ConfigError("No files to merge".to_string()));
         }
 
-        if config.files.len() == 1 {
-            let src = &config.files[0].path;
-            fs::copy(src, &config.output_path)?;
-            return Ok(config.output_path.clone());
+        // A single file keeps its encryption unless new security is set,
+        // which needs the keys only a fresh load returns
+        if let ([file_entry], None) = (config.files.as_slice(), &config.security) {
+            let (doc, cipher) = PdfSecurityService::load_with_cipher(&file_entry.path, file_entry.password.as_deref())?;
+            return Self::merge_documents(config, vec![doc], cipher.as_ref());
         }
 
-        // Load all documents
-        let mut documents: Vec<Document> = Vec::new();
+        // The cached documents are shared, so each one is cloned before its
+        // pages are edited
+        let mut warnings = Vec::new();
+        let mut documents = Vec::with_capacity(config.files.len());
         for file_entry in &config.files {
-            let path = &file_entry.path;
-            if !Path::new(path).exists() {
-                return Err(AppError::FileNotFound(path.clone()));
+            let (doc, permissions) =
+                DocumentCacheService::load_with_permissions(&file_entry.path, file_entry.password.as_deref())?;
+            if permissions.is_some() && config.security.is_none() {
+                let warning =
+                    format!("{} is encrypted, but the merged file is saved without a password", file_entry.path);
+                log::warn!("{}", warning);
+                warnings.push(warning);
             }
-            let doc = Document::load(path)
-                .map_err(|e| AppError::PdfError(format!("Failed to load {}: {}", path, e)))?;
-            documents.push(doc);
+            documents.push((*doc).clone());
+        }
+        let mut result = Self::merge_documents(config, documents, None)?;
+        result.warnings = warnings;
+        Ok(result)
+    }
+
+    /// Merge documents already loaded for the entries of `config.files`.
+    /// Without `config.security`, the output is encrypted with `cipher`
+    /// when one is given.
+    pub fn merge_documents(
+        config: &MergeConfig,
+        mut documents: Vec<Document>,
+        cipher: Option<&DocumentCipher>,
+    ) -> Result<MergeResult, AppError> {
+        let target_size = Self::target_page_size(&config.page_size)?;
+        for doc in &mut documents {
+            // Pages are re-parented under a new Pages tree below, so anything
+            // they inherit from the old one has to live on the page itself
+            Self::resolve_inherited_attributes(doc);
         }
 
         // Determine max PDF version across all documents
@@ -40,55 +89,158 @@ impl PdfMergerService {
             .unwrap_or_else(|| "1.5".to_string());
 
         let mut merged = Document::with_version(&max_version);
-        let mut all_page_ids: Vec<ObjectId> = Vec::new();
+        let mut outline_roots: Vec<OutlineNode> = Vec::new();
+        let mut acroform = MergedAcroForm::default();
+        let mut named_dests: BTreeMap<Vec<u8>, Object> = BTreeMap::new();
+        let mut doc_pages: Vec<Vec<(ObjectId, Option<PageLabel>)>> = Vec::new();
+        let mut has_source_labels = false;
+        let mut link_sources: Vec<LinkSource> = Vec::new();
+        // Input file index of every merged page
+        let mut page_sources: HashMap<ObjectId, usize> = HashMap::new();
 
         for (doc_idx, doc) in documents.iter().enumerate() {
             // Determine which pages to include
             let page_ids: Vec<ObjectId> = doc.page_iter().collect();
-            let selected_pages: Vec<ObjectId> = if let Some(ref selected) = config.files[doc_idx].selected_pages {
-                page_ids
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| selected.contains(&(i + 1)))
-                    .map(|(_, &id)| id)
-                    .collect()
-            } else {
-                page_ids.clone()
-            };
+            let selections = Self::page_selections(&config.files[doc_idx], page_ids.len())?;
+            let selected_pages: Vec<ObjectId> = selections.iter().map(|s| page_ids[s.page - 1]).collect();
 
             if selected_pages.is_empty() {
                 continue;
             }
 
-            // Build a mapping from old object IDs to new ones.
-            // We copy ALL objects from this document to avoid broken references.
+            // Only copy what the selected pages actually use, so the output
+            // size scales with the pages kept rather than the source files
+            let reachable = Self::collect_reachable(doc, &selected_pages);
+
             let mut id_map: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+            Self::copy_objects(doc, &reachable, &mut id_map, &mut merged);
 
-            // First pass: assign new IDs for every object in the source document
-            for &old_id in doc.objects.keys() {
-                let new_id = merged.new_object_id();
-                id_map.insert(old_id, new_id);
-            }
+            let source_labels = Self::read_page_labels(doc, page_ids.len());
+            has_source_labels |= source_labels.is_some();
+            let label_prefix = Self::expand_label_prefix(&config.page_label_prefix, doc_idx, &config.files[doc_idx].path);
 
-            // Second pass: deep-copy every object, remapping all references
-            for (&old_id, obj) in &doc.objects {
-                let new_id = id_map[&old_id];
-                let remapped_obj = Self::remap_object(obj, &id_map);
-                merged.objects.insert(new_id, remapped_obj);
+            let restart_label = |number: usize| PageLabel {
+                style: Some(b"D".to_vec()),
+                prefix: label_prefix.clone(),
+                number: number as i64 + 1,
+            };
+
+            // Track which pages to include in the final Pages tree. A page
+            // selected more than once gets its own copy of the page object.
+            let mut page_map: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+            let mut pages = Vec::new();
+            let mut merged_pages = Vec::new();
+            let mut rotations = Vec::new();
+            for selection in &selections {
+                let page_id = page_ids[selection.page - 1];
+                let Some(&copied_id) = id_map.get(&page_id) else {
+                    continue;
+                };
+                let new_page_id = match page_map.entry(page_id) {
+                    Entry::Occupied(_) => Self::duplicate_page(&mut merged, copied_id),
+                    Entry::Vacant(slot) => *slot.insert(copied_id),
+                };
+
+                let label = if config.restart_page_labels {
+                    Some(restart_label(pages.len()))
+                } else {
+                    source_labels.as_ref().map(|labels| labels[selection.page - 1].clone())
+                };
+                pages.push((new_page_id, label));
+                merged_pages.push(new_page_id);
+                if selection.rotation != 0 {
+                    rotations.push((new_page_id, selection.rotation));
+                }
+                if selection.blank_after {
+                    let label = config.restart_page_labels.then(|| restart_label(pages.len()));
+                    pages.push((Self::add_blank_page(&mut merged, new_page_id), label));
+                }
+            }
+            if config.files[doc_idx].pad_to_even && pages.len() % 2 == 1 {
+                let label = config.restart_page_labels.then(|| restart_label(pages.len()));
+                pages.push((Self::add_blank_page(&mut merged, pages[pages.len() - 1].0), label));
+            }
+            // Rotate only once every duplicate has been cloned from the original
+            for (page_id, rotation) in rotations {
+                Self::rotate_page(&mut merged, page_id, rotation)?;
             }
+            page_sources.extend(pages.iter().map(|&(page_id, _)| (page_id, doc_idx)));
+            doc_pages.push(pages);
 
-            // Track which pages to include in the final Pages tree
-            for &page_id in &selected_pages {
-                if let Some(&new_page_id) = id_map.get(&page_id) {
-                    all_page_ids.push(new_page_id);
+            Self::collect_acroform(doc, &config.files[doc_idx].path, &mut id_map, &mut merged, &mut acroform);
+
+            let dest_names = Self::collect_named_dests(doc, &config.files[doc_idx].path, &page_map, &mut named_dests);
+            link_sources.push(LinkSource {
+                path: fs::canonicalize(&config.files[doc_idx].path).ok(),
+                page_ids,
+                page_map: page_map.clone(),
+                dest_names,
+                merged_pages: merged_pages.clone(),
+            });
+
+            if config.keep_bookmarks {
+                let children = Self::collect_outlines(doc, &id_map, &page_map);
+                if config.files.len() == 1 {
+                    // A single file keeps its outline as it is
+                    outline_roots.extend(children);
+                } else {
+                    outline_roots.push(OutlineNode {
+                        title: Self::document_title(doc, &config.files[doc_idx].path),
+                        dest: Some(vec![Object::Reference(merged_pages[0]), Object::Name(b"Fit".to_vec())]),
+                        action: None,
+                        open: false,
+                        extra: Dictionary::new(),
+                        children,
+                    });
                 }
             }
         }
 
+        let (all_page_ids, page_labels): (Vec<ObjectId>, Vec<Option<PageLabel>>) =
+            Self::order_pages(doc_pages, config.merge_mode).into_iter().unzip();
         if all_page_ids.is_empty() {
             return Err(AppError::PdfError("No pages to merge".to_string()));
         }
 
+        // Links can point into files merged later, so they are fixed up last
+        Self::fix_links(&mut merged, &link_sources);
+
+        // Scale every page onto a common paper size if requested
+        if let Some(size) = target_size {
+            let mut transformed_annots = HashSet::new();
+            let mut page_matrices = HashMap::new();
+            for &page_id in &all_page_ids {
+                if let Some(matrix) = Self::fit_page_to_size(&mut merged, page_id, size, &mut transformed_annots)? {
+                    page_matrices.insert(page_id, matrix);
+                }
+            }
+            // Destinations give positions on their page, which moved with the content
+            Self::transform_link_dests(&mut merged, &all_page_ids, &page_matrices);
+            Self::transform_outline_dests(&mut outline_roots, &page_matrices);
+            for dest in named_dests.values_mut() {
+                if let Object::Array(dest) = dest {
+                    Self::transform_dest(dest, &page_matrices);
+                }
+            }
+        }
+
+        // Stamps go on last so they are placed on the final page geometry
+        if !config.stamps.is_empty() {
+            PdfStamperService::apply(&mut merged, &all_page_ids, &config.stamps)?;
+        }
+        if !config.headers_footers.is_empty() {
+            let sources: Vec<String> = all_page_ids
+                .iter()
+                .map(|id| {
+                    Path::new(&config.files[page_sources[id]].path)
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                })
+                .collect();
+            PdfStamperService::apply_headers_footers(&mut merged, &all_page_ids, &sources, &config.headers_footers)?;
+        }
+
         // Build the Pages tree
         let pages_id = merged.new_object_id();
         let mut pages_dict = Dictionary::new();
@@ -113,121 +265,2446 @@ impl PdfMergerService {
         let mut catalog = Dictionary::new();
         catalog.set("Type", Object::Name(b"Catalog".to_vec()));
         catalog.set("Pages", Object::Reference(pages_id));
+        if !outline_roots.is_empty() {
+            let outlines_id = Self::write_outlines(&mut merged, outline_roots);
+            catalog.set("Outlines", Object::Reference(outlines_id));
+        }
+        if !named_dests.is_empty() {
+            let names: Vec<Object> = named_dests
+                .into_iter()
+                .flat_map(|(name, dest)| [Object::String(name, lopdf::StringFormat::Literal), dest])
+                .collect();
+            let dests = Dictionary::from_iter(vec![("Names", Object::Array(names))]);
+            let names = Dictionary::from_iter(vec![("Dests", Object::Reference(merged.add_object(dests)))]);
+            catalog.set("Names", Object::Dictionary(names));
+        }
+        if config.restart_page_labels || has_source_labels {
+            let labels = Self::build_page_labels(&page_labels);
+            catalog.set("PageLabels", Object::Reference(merged.add_object(labels)));
+        }
+        if !acroform.fields.is_empty() {
+            let acroform_id = merged.add_object(acroform.into_dictionary());
+            catalog.set("AcroForm", Object::Reference(acroform_id));
+        }
+        // Viewers show the document title instead of the file name
+        let viewer_preferences = Dictionary::from_iter(vec![("DisplayDocTitle", Object::Boolean(true))]);
+        catalog.set("ViewerPreferences", Object::Dictionary(viewer_preferences));
         merged.objects.insert(catalog_id, Object::Dictionary(catalog));
 
         merged.trailer.set("Root", Object::Reference(catalog_id));
 
+        let source_info = PdfParserService::document_info(&documents[0]);
+        let info = DocumentInfo {
+            title: config.info.title.clone().or(source_info.title),
+            author: config.info.author.clone().or(source_info.author),
+            subject: config.info.subject.clone().or(source_info.subject),
+            keywords: config.info.keywords.clone().or(source_info.keywords),
+            creator: config.info.creator.clone().or(source_info.creator),
+            producer: config.info.producer.clone().or(source_info.producer),
+        };
+        PdfParserService::write_metadata(&mut merged, &info)?;
+
+        // Share fonts, images and other resources that several inputs embed
+        let (deduplicated_objects, bytes_saved) = Self::deduplicate_objects(&mut merged);
+
         // Compress streams to reduce file size
         merged.compress();
+        let optimization = match &config.optimize {
+            Some(options) => PdfOptimizerService::optimize_document(&mut merged, options)?,
+            None => Optimization::default(),
+        };
+
+        // Encryption has to come last: compressed or rewritten data would
+        // otherwise no longer match its ciphertext
+        match (&config.security, cipher) {
+            (Some(security), _) => PdfSecurityService::encrypt(&mut merged, security)?,
+            (None, Some(cipher)) => PdfSecurityService::reencrypt(&mut merged, cipher)?,
+            (None, None) => {}
+        }
 
         // Save
-        merged.save(&config.output_path)
-            .map_err(|e| AppError::PdfError(format!("Failed to save merged PDF: {}", e)))?;
+        PdfOptimizerService::save(&mut merged, &config.output_path, &optimization)?;
 
         log::info!(
-            "Successfully merged {} files ({} pages) into {}",
+            "Successfully merged {} files ({} pages) into {}, {} duplicate objects removed ({} bytes)",
             config.files.len(),
             all_page_ids.len(),
-            config.output_path
+            config.output_path,
+            deduplicated_objects,
+            bytes_saved
         );
-        Ok(config.output_path.clone())
+        Ok(MergeResult {
+            output_path: config.output_path.clone(),
+            deduplicated_objects,
+            bytes_saved,
+            warnings: Vec::new(),
+        })
     }
 
-    /// Recursively remap all ObjectId references in an Object
-    fn remap_object(obj: &Object, id_map: &BTreeMap<ObjectId, ObjectId>) -> Object {
-        match obj {
-            Object::Reference(old_id) => {
-                if let Some(&new_id) = id_map.get(old_id) {
-                    Object::Reference(new_id)
-                } else {
-                    // Reference to an object not in our map; keep as-is
-                    obj.clone()
+    /// Parse `MergeConfig.page_size` into a portrait paper size in points, or
+    /// `None` when pages keep their original size.
+    pub(crate) fn target_page_size(page_size: &str) -> Result<Option<(f32, f32)>, AppError> {
+        match page_size.to_lowercase().as_str() {
+            "" | "original" => Ok(None),
+            "a4" => Ok(Some(A4_SIZE)),
+            "letter" => Ok(Some(LETTER_SIZE)),
+            other => Err(AppError::ConfigError(format!("Unsupported page size: {}", other))),
+        }
+    }
+
+    /// Scale and center a page onto a `width` x `height` MediaBox, keeping its
+    /// aspect ratio. The target is turned landscape for landscape pages, and
+    /// `/Rotate` is left in place so rotated pages still display the same way.
+    /// Annotations are moved along with the content. Returns the matrix the
+    /// page was transformed with, or `None` if it was left alone.
+    fn fit_page_to_size(
+        merged: &mut Document,
+        page_id: ObjectId,
+        (width, height): (f32, f32),
+        transformed_annots: &mut HashSet<ObjectId>,
+    ) -> Result<Option<[f32; 6]>, AppError> {
+        let media_box = Self::inherited_attribute(merged, page_id, b"MediaBox")
+            .and_then(|b| Self::rect_of(merged, &b))
+            .unwrap_or([0.0, 0.0, LETTER_SIZE.0, LETTER_SIZE.1]);
+        let visible = Self::inherited_attribute(merged, page_id, b"CropBox")
+            .and_then(|b| Self::rect_of(merged, &b))
+            .map(|crop| {
+                [
+                    crop[0].max(media_box[0]),
+                    crop[1].max(media_box[1]),
+                    crop[2].min(media_box[2]),
+                    crop[3].min(media_box[3]),
+                ]
+            })
+            .unwrap_or(media_box);
+        let rotate = Self::inherited_attribute(merged, page_id, b"Rotate");
+
+        let (src_w, src_h) = (visible[2] - visible[0], visible[3] - visible[1]);
+        if src_w <= 0.0 || src_h <= 0.0 {
+            log::warn!("Skipping page {:?} with an empty page box", page_id);
+            return Ok(None);
+        }
+
+        let (target_w, target_h) = if (src_w > src_h) == (width > height) {
+            (width, height)
+        } else {
+            (height, width)
+        };
+        let scale = (target_w / src_w).min(target_h / src_h);
+        let matrix = [
+            scale,
+            0.0,
+            0.0,
+            scale,
+            (target_w - src_w * scale) / 2.0 - visible[0] * scale,
+            (target_h - src_h * scale) / 2.0 - visible[1] * scale,
+        ];
+
+        // Wrap the existing content streams in a transform clipped to the
+        // original visible area
+        let prefix = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new("cm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+                Operation::new("re", vec![
+                    Object::Real(visible[0]),
+                    Object::Real(visible[1]),
+                    Object::Real(src_w),
+                    Object::Real(src_h),
+                ]),
+                Operation::new("W", vec![]),
+                Operation::new("n", vec![]),
+            ],
+        }
+        .encode()?;
+        let prefix_id = merged.add_object(lopdf::Stream::new(Dictionary::new(), [prefix, b"\n".to_vec()].concat()));
+        let suffix_id = merged.add_object(lopdf::Stream::new(Dictionary::new(), b"\nQ".to_vec()));
+
+        let annots = Self::page_annotation_ids(merged, page_id);
+        let mut contents = vec![Object::Reference(prefix_id)];
+        contents.extend(merged.get_page_contents(page_id).into_iter().map(Object::Reference));
+        contents.push(Object::Reference(suffix_id));
+
+        let page = merged.get_dictionary_mut(page_id)?;
+        page.set("Contents", Object::Array(contents));
+        page.set("MediaBox", Object::Array(vec![
+            0.into(),
+            0.into(),
+            Object::Real(target_w),
+            Object::Real(target_h),
+        ]));
+        page.remove(b"CropBox");
+        if let Some(rotate) = rotate {
+            page.set("Rotate", rotate);
+        }
+        for key in [&b"BleedBox"[..], &b"TrimBox"[..], &b"ArtBox"[..]] {
+            if let Ok(Object::Array(values)) = page.get_mut(key) {
+                *values = Self::transform_coordinates(values, &matrix);
+            }
+        }
+
+        // Move annotations along with the content
+        for annot_id in annots {
+            if !transformed_annots.insert(annot_id) {
+                continue;
+            }
+            if let Ok(annot) = merged.get_dictionary_mut(annot_id) {
+                for key in ANNOT_COORDINATE_KEYS {
+                    if let Ok(Object::Array(values)) = annot.get_mut(key) {
+                        *values = Self::transform_coordinates(values, &matrix);
+                    }
                 }
             }
-            Object::Array(arr) => {
-                let new_arr: Vec<Object> = arr
-                    .iter()
-                    .map(|item| Self::remap_object(item, id_map))
-                    .collect();
-                Object::Array(new_arr)
+        }
+
+        Ok(Some(matrix))
+    }
+
+    /// Move the explicit destinations of link annotations on `page_ids` to
+    /// where their target pages were fitted. [`Self::fix_links`] has already
+    /// left these as direct `/Dest` arrays or `/GoTo` actions.
+    fn transform_link_dests(merged: &mut Document, page_ids: &[ObjectId], matrices: &HashMap<ObjectId, [f32; 6]>) {
+        let mut visited = HashSet::new();
+        for &page_id in page_ids {
+            for annot_id in Self::page_annotation_ids(merged, page_id) {
+                if !visited.insert(annot_id) {
+                    continue;
+                }
+                let Ok(annot) = merged.get_dictionary_mut(annot_id) else {
+                    continue;
+                };
+                if annot.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Link") {
+                    continue;
+                }
+                if let Ok(Object::Array(dest)) = annot.get_mut(b"Dest") {
+                    Self::transform_dest(dest, matrices);
+                } else if let Ok(Object::Dictionary(action)) = annot.get_mut(b"A") {
+                    if action.get(b"S").and_then(Object::as_name).ok() == Some(b"GoTo") {
+                        if let Ok(Object::Array(dest)) = action.get_mut(b"D") {
+                            Self::transform_dest(dest, matrices);
+                        }
+                    }
+                }
             }
-            Object::Dictionary(dict) => {
-                let mut new_dict = Dictionary::new();
-                for (key, value) in dict.iter() {
-                    new_dict.set(key.clone(), Self::remap_object(value, id_map));
+        }
+    }
+
+    fn transform_outline_dests(nodes: &mut [OutlineNode], matrices: &HashMap<ObjectId, [f32; 6]>) {
+        for node in nodes {
+            if let Some(dest) = &mut node.dest {
+                Self::transform_dest(dest, matrices);
+            }
+            Self::transform_outline_dests(&mut node.children, matrices);
+        }
+    }
+
+    /// Map the coordinates of an explicit destination (`/XYZ left top`,
+    /// `/FitH top`, `/FitV left`, `/FitR left bottom right top` and the
+    /// `/FitB*` variants) through the matrix its page was fitted with.
+    /// Null coordinates keep meaning "unchanged".
+    fn transform_dest(dest: &mut [Object], matrices: &HashMap<ObjectId, [f32; 6]>) {
+        let Some(matrix) = dest.first().and_then(|page| page.as_reference().ok()).and_then(|page| matrices.get(&page))
+        else {
+            return;
+        };
+        let horizontal: &[bool] = match dest.get(1).and_then(|kind| kind.as_name().ok()) {
+            Some(b"XYZ") => &[true, false],
+            Some(b"FitH") | Some(b"FitBH") => &[false],
+            Some(b"FitV") | Some(b"FitBV") => &[true],
+            Some(b"FitR") => &[true, false, true, false],
+            _ => &[],
+        };
+        for (value, &horizontal) in dest.iter_mut().skip(2).zip(horizontal) {
+            if let Ok(v) = value.as_float() {
+                *value = match horizontal {
+                    true => Object::Real(v * matrix[0] + matrix[4]),
+                    false => Object::Real(v * matrix[3] + matrix[5]),
+                };
+            }
+        }
+    }
+
+    /// Ids of the annotation dictionaries listed in a page's `/Annots`
+    fn page_annotation_ids(doc: &Document, page_id: ObjectId) -> Vec<ObjectId> {
+        doc.get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| doc.dereference(annots))
+            .and_then(|(_, annots)| annots.as_array())
+            .map(|annots| annots.iter().filter_map(|a| a.as_reference().ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Apply a scale-and-translate matrix to a flat list of x/y coordinates.
+    /// Nested arrays (such as `/InkList` paths) are transformed recursively.
+    fn transform_coordinates(values: &[Object], matrix: &[f32; 6]) -> Vec<Object> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| match value {
+                Object::Array(inner) => Object::Array(Self::transform_coordinates(inner, matrix)),
+                _ => match value.as_float() {
+                    Ok(v) if i % 2 == 0 => Object::Real(v * matrix[0] + matrix[4]),
+                    Ok(v) => Object::Real(v * matrix[3] + matrix[5]),
+                    Err(_) => value.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// Copy inheritable attributes (`Resources`, `MediaBox`, `CropBox`,
+    /// `Rotate`) from ancestor Pages nodes down into every page dictionary that
+    /// does not define them itself. Pages without any MediaBox get US Letter,
+    /// which is what viewers assume.
+    pub fn resolve_inherited_attributes(doc: &mut Document) {
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        for page_id in page_ids {
+            let inherited: Vec<(&[u8], Object)> = INHERITABLE_PAGE_KEYS
+                .iter()
+                .filter_map(|&key| Self::inherited_attribute(doc, page_id, key).map(|value| (key, value)))
+                .collect();
+
+            if let Ok(page) = doc.get_dictionary_mut(page_id) {
+                for (key, value) in inherited {
+                    if !page.has(key) {
+                        page.set(key, value);
+                    }
+                }
+                if !page.has(b"MediaBox") {
+                    page.set("MediaBox", Object::Array(vec![
+                        0.into(),
+                        0.into(),
+                        Object::Real(LETTER_SIZE.0),
+                        Object::Real(LETTER_SIZE.1),
+                    ]));
                 }
-                Object::Dictionary(new_dict)
             }
-            Object::Stream(stream) => {
-                let mut new_dict = Dictionary::new();
-                for (key, value) in stream.dict.iter() {
-                    new_dict.set(key.clone(), Self::remap_object(value, id_map));
+        }
+    }
+
+    /// Collect the ids of every object reachable from the given pages,
+    /// including values they inherit from ancestor Pages nodes. Page tree
+    /// nodes other than the given pages are never entered, so links to other
+    /// pages do not drag unselected pages, old catalogs or Pages trees along.
+    pub(crate) fn collect_reachable(doc: &Document, pages: &[ObjectId]) -> BTreeSet<ObjectId> {
+        let mut reachable: BTreeSet<ObjectId> = pages.iter().copied().collect();
+        let mut pending: Vec<ObjectId> = Vec::new();
+
+        for &page_id in pages {
+            if let Ok(page) = doc.get_dictionary(page_id) {
+                for (key, value) in page.iter() {
+                    if key.as_slice() != b"Parent" {
+                        Self::collect_references(value, &mut pending);
+                    }
+                }
+            }
+            for key in INHERITABLE_PAGE_KEYS {
+                if let Some(value) = Self::inherited_attribute(doc, page_id, key) {
+                    Self::collect_references(&value, &mut pending);
                 }
-                Object::Stream(lopdf::Stream::new(new_dict, stream.content.clone()))
             }
-            // All other types (Boolean, Integer, Real, String, Name, Null) have no references
-            _ => obj.clone(),
         }
+
+        Self::extend_reachable(doc, pending, &mut reachable);
+        reachable
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::models::pdf::PdfFileEntry;
+    /// Follow `pending` references transitively, adding every object found to
+    /// `reachable`. Objects already in `reachable` are not walked again.
+    fn extend_reachable(doc: &Document, mut pending: Vec<ObjectId>, reachable: &mut BTreeSet<ObjectId>) {
+        while let Some(id) = pending.pop() {
+            if reachable.contains(&id) {
+                continue;
+            }
+            let Ok(obj) = doc.get_object(id) else {
+                continue;
+            };
+            let is_page_tree_node = obj
+                .as_dict()
+                .map(|dict| dict.type_is(b"Page") || dict.type_is(b"Pages"))
+                .unwrap_or(false);
+            if is_page_tree_node {
+                continue;
+            }
+            reachable.insert(id);
+            Self::collect_references(obj, &mut pending);
+        }
+    }
 
-    #[test]
-    fn test_merge_config_validation() {
-        let config = MergeConfig {
-            files: vec![],
-            output_path: "out.pdf".to_string(),
-            keep_bookmarks: true,
-            page_size: "original".to_string(),
+    /// Copy source objects into `merged`, extending `id_map`. New ids are
+    /// assigned before anything is copied so references between the copied
+    /// objects resolve.
+    pub(crate) fn copy_objects(
+        doc: &Document,
+        ids: &BTreeSet<ObjectId>,
+        id_map: &mut BTreeMap<ObjectId, ObjectId>,
+        merged: &mut Document,
+    ) {
+        for &old_id in ids {
+            id_map.insert(old_id, merged.new_object_id());
+        }
+        for &old_id in ids {
+            if let Ok(obj) = doc.get_object(old_id) {
+                merged.objects.insert(id_map[&old_id], Self::remap_object(obj, id_map));
+            }
+        }
+    }
+
+    /// Copy whatever `obj` references that was not copied along with the
+    /// pages (such as form resources), then return `obj` remapped.
+    pub(crate) fn copy_with_dependencies(
+        doc: &Document,
+        obj: &Object,
+        id_map: &mut BTreeMap<ObjectId, ObjectId>,
+        merged: &mut Document,
+    ) -> Object {
+        let mut pending = Vec::new();
+        Self::collect_references(obj, &mut pending);
+
+        let mut reachable: BTreeSet<ObjectId> = id_map.keys().copied().collect();
+        Self::extend_reachable(doc, pending, &mut reachable);
+        let missing: BTreeSet<ObjectId> = reachable.into_iter().filter(|id| !id_map.contains_key(id)).collect();
+
+        Self::copy_objects(doc, &missing, id_map, merged);
+        Self::remap_object(obj, id_map)
+    }
+
+    /// Copy a page into `target` as a form XObject showing the page's
+    /// visible area the way viewers display it, with `/Rotate` applied.
+    /// Annotations are not page content and are left out. Returns the form's
+    /// id and its displayed width and height.
+    pub(crate) fn page_to_form(
+        doc: &Document,
+        page_id: ObjectId,
+        id_map: &mut BTreeMap<ObjectId, ObjectId>,
+        target: &mut Document,
+    ) -> Result<(ObjectId, (f32, f32)), AppError> {
+        let ([x0, y0, x1, y1], matrix, size) = Self::visible_area(doc, page_id);
+        let mut form = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"XObject".to_vec())),
+            ("Subtype", Object::Name(b"Form".to_vec())),
+            ("BBox", Object::Array([x0, y0, x1, y1].into_iter().map(Object::Real).collect())),
+            ("Matrix", Object::Array(matrix.into_iter().map(Object::Real).collect())),
+        ]);
+        if let Some(resources) = Self::inherited_attribute(doc, page_id, b"Resources") {
+            form.set("Resources", resources);
+        }
+        if let Ok(group) = doc.get_dictionary(page_id)?.get(b"Group") {
+            form.set("Group", group.clone());
+        }
+        let content = doc.get_page_content(page_id)?;
+
+        let form = Self::copy_with_dependencies(doc, &Object::Stream(lopdf::Stream::new(form, content)), id_map, target);
+        Ok((target.add_object(form), size))
+    }
+
+    /// The visible area of a page (CropBox, else MediaBox), a matrix mapping
+    /// it to (0, 0)-(width, height) as displayed with `/Rotate` applied, and
+    /// that displayed width and height
+    pub(crate) fn visible_area(doc: &Document, page_id: ObjectId) -> ([f32; 4], [f32; 6], (f32, f32)) {
+        let [x0, y0, x1, y1] = [b"CropBox".as_slice(), b"MediaBox"]
+            .into_iter()
+            .find_map(|key| Self::inherited_attribute(doc, page_id, key).and_then(|obj| Self::rect_of(doc, &obj)))
+            .filter(|rect| rect[2] > rect[0] && rect[3] > rect[1])
+            .unwrap_or([0.0, 0.0, LETTER_SIZE.0, LETTER_SIZE.1]);
+        let rotate = Self::inherited_attribute(doc, page_id, b"Rotate")
+            .and_then(|rotate| rotate.as_i64().ok())
+            .unwrap_or(0)
+            .rem_euclid(360);
+        let (matrix, size) = match rotate {
+            90 => ([0.0, -1.0, 1.0, 0.0, -y0, x1], (y1 - y0, x1 - x0)),
+            180 => ([-1.0, 0.0, 0.0, -1.0, x1, y1], (x1 - x0, y1 - y0)),
+            270 => ([0.0, 1.0, -1.0, 0.0, y1, -x0], (y1 - y0, x1 - x0)),
+            _ => ([1.0, 0.0, 0.0, 1.0, -x0, -y0], (x1 - x0, y1 - y0)),
         };
-        let result = PdfMergerService::merge(&config);
-        assert!(result.is_err());
+        ([x0, y0, x1, y1], matrix, size)
     }
 
-    #[test]
-    fn test_merge_missing_file() {
-        let config = MergeConfig {
-            files: vec![
-                PdfFileEntry { path: "/nonexistent/a.pdf".to_string(), selected_pages: None },
-                PdfFileEntry { path: "/nonexistent/b.pdf".to_string(), selected_pages: None },
-            ],
-            output_path: "out.pdf".to_string(),
-            keep_bookmarks: true,
-            page_size: "original".to_string(),
+    /// Push every reference directly contained in `obj` onto `out`
+    fn collect_references(obj: &Object, out: &mut Vec<ObjectId>) {
+        match obj {
+            Object::Reference(id) => out.push(*id),
+            Object::Array(arr) => arr.iter().for_each(|item| Self::collect_references(item, out)),
+            Object::Dictionary(dict) => dict.iter().for_each(|(_, v)| Self::collect_references(v, out)),
+            Object::Stream(stream) => stream.dict.iter().for_each(|(_, v)| Self::collect_references(v, out)),
+            _ => {}
+        }
+    }
+
+    /// Look up a page attribute, following `/Parent` links for the
+    /// attributes that pages inherit from the Pages tree.
+    pub(crate) fn inherited_attribute(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<Object> {
+        let mut node = doc.get_dictionary(page_id).ok()?;
+        for _ in 0..64 {
+            if let Ok(value) = node.get(key) {
+                return Some(value.clone());
+            }
+            let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+            node = doc.get_dictionary(parent).ok()?;
+        }
+        None
+    }
+
+    /// Read a rectangle such as a MediaBox as normalized `[llx, lly, urx, ury]`
+    pub(crate) fn rect_of(doc: &Document, obj: &Object) -> Option<[f32; 4]> {
+        let (_, obj) = doc.dereference(obj).ok()?;
+        let values = obj
+            .as_array()
+            .ok()?
+            .iter()
+            .map(|v| doc.dereference(v).ok().and_then(|(_, v)| v.as_float().ok()))
+            .collect::<Option<Vec<f32>>>()?;
+        if values.len() != 4 {
+            return None;
+        }
+        Some([
+            values[0].min(values[2]),
+            values[1].min(values[3]),
+            values[0].max(values[2]),
+            values[1].max(values[3]),
+        ])
+    }
+
+    /// Add the interactive form of a source document to `form`. Only fields
+    /// whose widgets were copied with the selected pages are kept. A root field
+    /// whose name is already taken by an earlier file is renamed with a prefix
+    /// taken from the file name, so values of unrelated fields do not get
+    /// linked. Conflicting `/DR` resource names are renamed as well, and the
+    /// `/DA` strings of the file's fields are rewritten to match.
+    fn collect_acroform(
+        doc: &Document,
+        path: &str,
+        id_map: &mut BTreeMap<ObjectId, ObjectId>,
+        merged: &mut Document,
+        form: &mut MergedAcroForm,
+    ) {
+        let Ok(source) = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"AcroForm"))
+            .and_then(|acroform| doc.dereference(acroform))
+            .and_then(|(_, acroform)| acroform.as_dict())
+        else {
+            return;
         };
-        let result = PdfMergerService::merge(&config);
-        assert!(result.is_err());
+
+        let roots: Vec<ObjectId> = source
+            .get(b"Fields")
+            .and_then(|fields| doc.dereference(fields))
+            .and_then(|(_, fields)| fields.as_array())
+            .map(|fields| fields.iter().filter_map(|f| f.as_reference().ok()).collect())
+            .unwrap_or_default();
+        let roots: Vec<ObjectId> = roots.into_iter().filter_map(|id| id_map.get(&id).copied()).collect();
+        if roots.is_empty() {
+            return;
+        }
+
+        if source.has(b"XFA") {
+            log::warn!("Dropping XFA form data of {}; only the AcroForm fields are merged", path);
+        }
+
+        // Merge the default resources, renaming entries that clash
+        let mut renames: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+        let file_prefix = Self::name_prefix(path);
+        if let Ok(dr) = source.get(b"DR") {
+            let dr = Self::copy_with_dependencies(doc, dr, id_map, merged);
+            let dr = merged.dereference(&dr).ok().and_then(|(_, dr)| dr.as_dict().ok()).cloned();
+            for (category, entries) in dr.iter().flat_map(|dr| dr.iter()) {
+                let entries = match merged.dereference(entries) {
+                    Ok((_, Object::Dictionary(entries))) => entries.clone(),
+                    _ => continue,
+                };
+                let target = form.resources.entry(category.clone()).or_default();
+                for (name, value) in entries.iter() {
+                    let new_name = match target.get(name) {
+                        Err(_) => name.clone(),
+                        Ok(existing) if Self::same_resource(merged, existing, value) => continue,
+                        Ok(_) => {
+                            let mut n = 1;
+                            let mut candidate = format!("{}_{}", String::from_utf8_lossy(name), file_prefix).into_bytes();
+                            while target.has(&candidate) {
+                                n += 1;
+                                candidate = format!("{}_{}{}", String::from_utf8_lossy(name), file_prefix, n).into_bytes();
+                            }
+                            renames.insert(name.clone(), candidate.clone());
+                            candidate
+                        }
+                    };
+                    target.set(new_name, value.clone());
+                }
+            }
+        }
+
+        let default_appearance = source.get(b"DA").ok().cloned();
+        let quadding = source.get(b"Q").ok().cloned();
+        for root in roots {
+            let Ok(field) = merged.get_dictionary_mut(root) else {
+                continue;
+            };
+
+            // Fields may rely on the form-wide defaults, which do not survive
+            // when forms from several files share one AcroForm
+            if let (Some(da), false) = (&default_appearance, field.has(b"DA")) {
+                field.set("DA", da.clone());
+            }
+            if let (Some(q), false) = (&quadding, field.has(b"Q")) {
+                field.set("Q", q.clone());
+            }
+
+            if let Ok(name) = field.get(b"T").and_then(lopdf::decode_text_string) {
+                let mut unique = name.clone();
+                let mut n = 1;
+                while form.field_names.contains(&unique) {
+                    unique = if n == 1 {
+                        format!("{}_{}", file_prefix, name)
+                    } else {
+                        format!("{}{}_{}", file_prefix, n, name)
+                    };
+                    n += 1;
+                }
+                if unique != name {
+                    log::info!("Renaming form field '{}' of {} to '{}'", name, path, unique);
+                    field.set("T", lopdf::text_string(&unique));
+                }
+                form.field_names.insert(unique);
+            }
+
+            if !renames.is_empty() {
+                Self::rename_appearance_resources(merged, root, &renames, 0);
+            }
+            form.fields.push(Object::Reference(root));
+        }
+
+        if form.default_appearance.is_none() {
+            form.default_appearance = default_appearance;
+        }
+        form.need_appearances |= source
+            .get(b"NeedAppearances")
+            .and_then(Object::as_bool)
+            .unwrap_or(false);
+        form.sig_flags |= source.get(b"SigFlags").and_then(Object::as_i64).unwrap_or(0);
+        if let Ok(order) = source.get(b"CO").and_then(Object::as_array) {
+            form.calculation_order.extend(
+                order
+                    .iter()
+                    .map(|field| Self::remap_object(field, id_map))
+                    .filter(|field| !field.is_null()),
+            );
+        }
     }
 
-    #[test]
-    fn test_remap_object_reference() {
-        let mut id_map = BTreeMap::new();
-        id_map.insert((1, 0), (100, 0));
-        id_map.insert((2, 0), (200, 0));
+    /// Add the named destinations of a source document that still point at a
+    /// merged page to `tree`. Names already used by an earlier file get a
+    /// prefix taken from the file name. Returns the mapping from the source's
+    /// names to the names used in the merged document.
+    fn collect_named_dests(
+        doc: &Document,
+        path: &str,
+        page_map: &BTreeMap<ObjectId, ObjectId>,
+        tree: &mut BTreeMap<Vec<u8>, Object>,
+    ) -> HashMap<Vec<u8>, Vec<u8>> {
+        let mut entries: Vec<(Vec<u8>, Object)> = Vec::new();
+        if let Ok(catalog) = doc.catalog() {
+            // PDF 1.1 style dictionary keyed by name objects
+            if let Ok((_, Object::Dictionary(dests))) = catalog.get(b"Dests").and_then(|d| doc.dereference(d)) {
+                entries.extend(dests.iter().map(|(name, dest)| (name.clone(), dest.clone())));
+            }
+            if let Ok(tree_root) = catalog
+                .get(b"Names")
+                .and_then(|names| doc.dereference(names))
+                .and_then(|(_, names)| names.as_dict())
+                .and_then(|names| names.get(b"Dests"))
+            {
+                Self::name_tree_entries(doc, tree_root, &mut entries, 0);
+            }
+        }
 
-        // Test reference remapping
-        let obj = Object::Reference((1, 0));
-        let remapped = PdfMergerService::remap_object(&obj, &id_map);
-        assert_eq!(remapped, Object::Reference((100, 0)));
+        let prefix = Self::name_prefix(path);
+        let mut renames = HashMap::new();
+        for (name, dest) in entries {
+            let Some(dest) = Self::resolve_dest(doc, &dest).and_then(|d| Self::map_dest(&d, page_map)) else {
+                continue;
+            };
+            let mut new_name = name.clone();
+            let mut n = 1;
+            while tree.contains_key(&new_name) {
+                new_name = if n == 1 {
+                    [prefix.as_bytes(), b"_", &name].concat()
+                } else {
+                    [format!("{}{}_", prefix, n).as_bytes(), &name].concat()
+                };
+                n += 1;
+            }
+            tree.insert(new_name.clone(), Object::Array(dest));
+            renames.insert(name, new_name);
+        }
+        renames
+    }
 
-        // Test array with references
-        let arr = Object::Array(vec![
-            Object::Reference((1, 0)),
-            Object::Reference((2, 0)),
-            Object::Integer(42),
-        ]);
-        let remapped_arr = PdfMergerService::remap_object(&arr, &id_map);
-        match remapped_arr {
-            Object::Array(items) => {
-                assert_eq!(items[0], Object::Reference((100, 0)));
-                assert_eq!(items[1], Object::Reference((200, 0)));
-                assert_eq!(items[2], Object::Integer(42));
+    /// Collect all key/value pairs of a PDF name tree
+    fn name_tree_entries(doc: &Document, node: &Object, out: &mut Vec<(Vec<u8>, Object)>, depth: usize) {
+        if depth > 32 {
+            return;
+        }
+        let Ok((_, Object::Dictionary(node))) = doc.dereference(node) else {
+            return;
+        };
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            for pair in names.chunks(2) {
+                if let [Object::String(name, _), value] = pair {
+                    out.push((name.clone(), value.clone()));
+                }
+            }
+        }
+        if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+            for kid in kids {
+                Self::name_tree_entries(doc, kid, out, depth + 1);
+            }
+        }
+    }
+
+    /// Fix link annotations on the merged pages: named destinations follow
+    /// renames, links to pages that were left out are removed, and `GoToR`
+    /// links into another file of the same merge become internal `GoTo` links.
+    fn fix_links(merged: &mut Document, sources: &[LinkSource]) {
+        for source in sources {
+            for &page_id in &source.merged_pages {
+                let annots = Self::page_annotation_ids(merged, page_id);
+                let mut removed = HashSet::new();
+
+                for annot_id in annots.iter().copied() {
+                    let Ok(annot) = merged.get_dictionary(annot_id) else {
+                        continue;
+                    };
+                    if annot.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Link") {
+                        continue;
+                    }
+
+                    let fixed = if let Ok(dest) = annot.get(b"Dest") {
+                        Self::map_link_dest(merged, dest, &source.dest_names).map(|dest| ("Dest", dest))
+                    } else {
+                        match annot.get(b"A").and_then(|a| merged.dereference(a)) {
+                            Ok((_, Object::Dictionary(action))) => match action.get(b"S").and_then(Object::as_name) {
+                                Ok(b"GoTo") => action
+                                    .get(b"D")
+                                    .ok()
+                                    .and_then(|dest| Self::map_link_dest(merged, dest, &source.dest_names))
+                                    .map(|dest| ("A", Self::goto_action(dest))),
+                                Ok(b"GoToR") => match Self::remote_link_target(merged, action, source, sources) {
+                                    RemoteTarget::External => continue,
+                                    RemoteTarget::Merged(dest) => dest.map(|dest| ("A", Self::goto_action(dest))),
+                                },
+                                _ => continue,
+                            },
+                            _ => continue,
+                        }
+                    };
+
+                    match fixed {
+                        Some((key, value)) => {
+                            if let Ok(annot) = merged.get_dictionary_mut(annot_id) {
+                                annot.set(key, value);
+                            }
+                        }
+                        None => {
+                            removed.insert(annot_id);
+                        }
+                    }
+                }
+
+                if !removed.is_empty() {
+                    let kept: Vec<Object> = annots
+                        .into_iter()
+                        .filter(|id| !removed.contains(id))
+                        .map(Object::Reference)
+                        .collect();
+                    if let Ok(page) = merged.get_dictionary_mut(page_id) {
+                        page.set("Annots", Object::Array(kept));
+                    }
+                    for id in removed {
+                        merged.objects.remove(&id);
+                    }
+                }
             }
-            _ => panic!("Expected array"),
         }
     }
+
+    /// Map the destination of a link within its own file. Named destinations
+    /// follow `dest_names`; explicit ones were remapped during the copy and
+    /// lost their page if it was not selected.
+    fn map_link_dest(merged: &Document, dest: &Object, dest_names: &HashMap<Vec<u8>, Vec<u8>>) -> Option<Object> {
+        let (_, dest) = merged.dereference(dest).ok()?;
+        match dest {
+            Object::Array(arr) => match arr.first() {
+                Some(Object::Reference(_)) => Some(dest.clone()),
+                _ => None,
+            },
+            Object::Name(name) | Object::String(name, _) => dest_names
+                .get(name)
+                .map(|new_name| Object::String(new_name.clone(), lopdf::StringFormat::Literal)),
+            Object::Dictionary(dict) => Self::map_link_dest(merged, dict.get(b"D").ok()?, dest_names),
+            _ => None,
+        }
+    }
+
+    /// Resolve a `GoToR` action against the files of this merge
+    fn remote_link_target(
+        merged: &Document,
+        action: &Dictionary,
+        source: &LinkSource,
+        sources: &[LinkSource],
+    ) -> RemoteTarget {
+        let file = action
+            .get(b"F")
+            .and_then(|f| merged.dereference(f))
+            .ok()
+            .and_then(|(_, f)| match f {
+                Object::Dictionary(spec) => spec.get(b"UF").or_else(|_| spec.get(b"F")).ok().cloned(),
+                other => Some(other.clone()),
+            })
+            .and_then(|f| lopdf::decode_text_string(&f).ok());
+        let (Some(file), Some(base)) = (file, source.path.as_ref().and_then(|p| p.parent())) else {
+            return RemoteTarget::External;
+        };
+        let Some(target) = fs::canonicalize(base.join(file))
+            .ok()
+            .and_then(|path| sources.iter().find(|s| s.path.as_ref() == Some(&path)))
+        else {
+            return RemoteTarget::External;
+        };
+
+        let dest = action.get(b"D").and_then(|d| merged.dereference(d)).map(|(_, d)| d.clone());
+        let mapped = match dest {
+            // Remote explicit destinations address pages by zero-based index
+            Ok(Object::Array(arr)) => arr.first().and_then(|page| page.as_i64().ok()).and_then(|index| {
+                let page = target.page_ids.get(usize::try_from(index).ok()?)?;
+                let mut arr = arr.clone();
+                arr[0] = Object::Reference(*target.page_map.get(page)?);
+                Some(Object::Array(arr))
+            }),
+            Ok(Object::Name(name)) | Ok(Object::String(name, _)) => target
+                .dest_names
+                .get(&name)
+                .map(|new_name| Object::String(new_name.clone(), lopdf::StringFormat::Literal)),
+            _ => None,
+        };
+        RemoteTarget::Merged(mapped)
+    }
+
+    fn goto_action(dest: Object) -> Object {
+        Object::Dictionary(Dictionary::from_iter(vec![
+            ("S", Object::Name(b"GoTo".to_vec())),
+            ("D", dest),
+        ]))
+    }
+
+    /// Resolve the pages an input file contributes, in output order
+    fn page_selections(entry: &PdfFileEntry, page_count: usize) -> Result<Vec<PageSelection>, AppError> {
+        let mut selections = if let Some(pages) = &entry.pages {
+            if let Some(invalid) = pages.iter().find(|s| s.page == 0 || s.page > page_count) {
+                return Err(AppError::ConfigError(format!(
+                    "Page {} is outside 1-{} in {}",
+                    invalid.page, page_count, entry.path
+                )));
+            }
+            pages.clone()
+        } else {
+            let numbers = if let Some(range) = &entry.page_range {
+                parse_page_range(range, page_count)?
+            } else if let Some(selected) = &entry.selected_pages {
+                selected.iter().copied().filter(|&page| page >= 1 && page <= page_count).collect()
+            } else {
+                (1..=page_count).collect()
+            };
+            numbers
+                .into_iter()
+                .map(|page| PageSelection { page, ..Default::default() })
+                .collect()
+        };
+
+        if let Some(invalid) = selections.iter().find(|s| s.rotation % 90 != 0) {
+            return Err(AppError::ConfigError(format!(
+                "Rotation must be a multiple of 90 degrees, got {}",
+                invalid.rotation
+            )));
+        }
+        if entry.reverse {
+            selections.reverse();
+        }
+        Ok(selections)
+    }
+
+    /// Add a second copy of an already merged page. Content and resources are
+    /// shared; annotations are cloned because each belongs to a single page.
+    fn duplicate_page(merged: &mut Document, page_id: ObjectId) -> ObjectId {
+        let new_page_id = merged.new_object_id();
+        let mut page = merged.get_dictionary(page_id).cloned().unwrap_or_default();
+
+        let annots: Vec<Object> = Self::page_annotation_ids(merged, page_id)
+            .into_iter()
+            .filter_map(|annot_id| {
+                let mut annot = merged.get_dictionary(annot_id).ok()?.clone();
+                if annot.has(b"P") {
+                    annot.set("P", Object::Reference(new_page_id));
+                }
+                Some(Object::Reference(merged.add_object(annot)))
+            })
+            .collect();
+        if !annots.is_empty() {
+            page.set("Annots", Object::Array(annots));
+        }
+
+        merged.objects.insert(new_page_id, Object::Dictionary(page));
+        new_page_id
+    }
+
+    /// Add an empty page with the same size as `like_page`
+    fn add_blank_page(merged: &mut Document, like_page: ObjectId) -> ObjectId {
+        let media_box = merged
+            .get_dictionary(like_page)
+            .and_then(|page| page.get(b"MediaBox"))
+            .cloned()
+            .unwrap_or_else(|_| {
+                Object::Array(vec![0.into(), 0.into(), Object::Real(LETTER_SIZE.0), Object::Real(LETTER_SIZE.1)])
+            });
+        merged.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("MediaBox", media_box),
+            ("Resources", Object::Dictionary(Dictionary::new())),
+        ]))
+    }
+
+    /// Add a clockwise rotation to a page's `/Rotate`
+    fn rotate_page(merged: &mut Document, page_id: ObjectId, rotation: i64) -> Result<(), AppError> {
+        let page = merged.get_dictionary_mut(page_id)?;
+        let current = page.get(b"Rotate").and_then(Object::as_i64).unwrap_or(0);
+        page.set("Rotate", Object::Integer((current + rotation).rem_euclid(360)));
+        Ok(())
+    }
+
+    /// Combine the pages of every input file into the final page order
+    fn order_pages<T>(doc_pages: Vec<Vec<T>>, mode: MergeMode) -> Vec<T> {
+        match mode {
+            MergeMode::Concatenate => doc_pages.into_iter().flatten().collect(),
+            MergeMode::Interleave => {
+                // Files that run out of pages simply drop out of the rotation
+                let mut iters: Vec<_> = doc_pages.into_iter().map(Vec::into_iter).collect();
+                let mut ordered = Vec::new();
+                loop {
+                    let before = ordered.len();
+                    ordered.extend(iters.iter_mut().filter_map(Iterator::next));
+                    if ordered.len() == before {
+                        break ordered;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Read the `/PageLabels` number tree of a document and expand it into one
+    /// label per page, or `None` when the document has no labels
+    fn read_page_labels(doc: &Document, page_count: usize) -> Option<Vec<PageLabel>> {
+        let tree = doc.catalog().ok()?.get(b"PageLabels").ok()?;
+        let mut ranges: Vec<(i64, Dictionary)> = Vec::new();
+        Self::number_tree_entries(doc, tree, &mut ranges, 0);
+        if ranges.is_empty() {
+            return None;
+        }
+        ranges.sort_by_key(|(start, _)| *start);
+
+        let labels = (0..page_count as i64)
+            .map(|index| match ranges.iter().rev().find(|(start, _)| *start <= index) {
+                Some((start, range)) => PageLabel {
+                    style: range.get(b"S").and_then(Object::as_name).ok().map(|s| s.to_vec()),
+                    prefix: range.get(b"P").and_then(Object::as_str).map(|p| p.to_vec()).unwrap_or_default(),
+                    number: range.get(b"St").and_then(Object::as_i64).unwrap_or(1) + index - start,
+                },
+                // Pages before the first range have no label of their own
+                None => PageLabel::decimal(index + 1),
+            })
+            .collect();
+        Some(labels)
+    }
+
+    /// Collect the entries of a PDF number tree whose values are dictionaries
+    fn number_tree_entries(doc: &Document, node: &Object, out: &mut Vec<(i64, Dictionary)>, depth: usize) {
+        if depth > 32 {
+            return;
+        }
+        let Ok((_, Object::Dictionary(node))) = doc.dereference(node) else {
+            return;
+        };
+        if let Ok(nums) = node.get(b"Nums").and_then(Object::as_array) {
+            for pair in nums.chunks(2) {
+                if let [Object::Integer(key), value] = pair {
+                    if let Ok((_, Object::Dictionary(value))) = doc.dereference(value) {
+                        out.push((*key, value.clone()));
+                    }
+                }
+            }
+        }
+        if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+            for kid in kids {
+                Self::number_tree_entries(doc, kid, out, depth + 1);
+            }
+        }
+    }
+
+    /// Build a `/PageLabels` number tree with one range per run of pages whose
+    /// labels continue each other
+    fn build_page_labels(labels: &[Option<PageLabel>]) -> Dictionary {
+        let mut nums = Vec::new();
+        let mut previous: Option<PageLabel> = None;
+        for (index, label) in labels.iter().enumerate() {
+            // Unlabelled pages keep showing their position in the output
+            let label = label.clone().unwrap_or_else(|| PageLabel::decimal(index as i64 + 1));
+            let continues = previous
+                .map(|p| p.style == label.style && p.prefix == label.prefix && p.number + 1 == label.number)
+                .unwrap_or(false);
+            if !continues {
+                let mut range = Dictionary::new();
+                if let Some(style) = &label.style {
+                    range.set("S", Object::Name(style.clone()));
+                }
+                if !label.prefix.is_empty() {
+                    range.set("P", Object::String(label.prefix.clone(), lopdf::StringFormat::Literal));
+                }
+                if label.number != 1 {
+                    range.set("St", Object::Integer(label.number));
+                }
+                nums.push(Object::Integer(index as i64));
+                nums.push(Object::Dictionary(range));
+            }
+            previous = Some(label);
+        }
+        Dictionary::from_iter(vec![("Nums", Object::Array(nums))])
+    }
+
+    /// Expand the per-file label prefix template
+    fn expand_label_prefix(template: &str, doc_idx: usize, path: &str) -> Vec<u8> {
+        let mut letter = String::new();
+        let mut n = doc_idx + 1;
+        while n > 0 {
+            letter.insert(0, (b'A' + ((n - 1) % 26) as u8) as char);
+            n = (n - 1) / 26;
+        }
+        let name = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let prefix = template
+            .replace("{letter}", &letter)
+            .replace("{index}", &(doc_idx + 1).to_string())
+            .replace("{name}", &name);
+        match lopdf::text_string(&prefix) {
+            Object::String(bytes, _) => bytes,
+            _ => prefix.into_bytes(),
+        }
+    }
+
+    /// Prefix used to make names from one input file unique, derived from
+    /// its file name. Periods separate form field name parts, so they are
+    /// replaced.
+    fn name_prefix(path: &str) -> String {
+        Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().replace(['.', ' '], "_"))
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or_else(|| "file".to_string())
+    }
+
+    /// Whether two resource entries resolve to identical objects
+    fn same_resource(doc: &Document, a: &Object, b: &Object) -> bool {
+        match (doc.dereference(a), doc.dereference(b)) {
+            (Ok((_, a)), Ok((_, b))) => Self::same_object(a, b),
+            _ => false,
+        }
+    }
+
+    /// Rewrite resource names in the `/DA` strings of a field and all its
+    /// descendants (including widgets)
+    fn rename_appearance_resources(
+        merged: &mut Document,
+        field_id: ObjectId,
+        renames: &HashMap<Vec<u8>, Vec<u8>>,
+        depth: usize,
+    ) {
+        if depth > 32 {
+            return;
+        }
+        let Ok(field) = merged.get_dictionary_mut(field_id) else {
+            return;
+        };
+        if let Ok(Object::String(da, _)) = field.get_mut(b"DA") {
+            *da = Self::rename_operands(da, renames);
+        }
+        let kids: Vec<ObjectId> = field
+            .get(b"Kids")
+            .and_then(Object::as_array)
+            .map(|kids| kids.iter().filter_map(|k| k.as_reference().ok()).collect())
+            .unwrap_or_default();
+        for kid in kids {
+            Self::rename_appearance_resources(merged, kid, renames, depth + 1);
+        }
+    }
+
+    /// Replace `/Name` operands in a small content string such as `/DA`
+    fn rename_operands(content: &[u8], renames: &HashMap<Vec<u8>, Vec<u8>>) -> Vec<u8> {
+        let text = String::from_utf8_lossy(content);
+        text.split(' ')
+            .map(|token| match token.strip_prefix('/') {
+                Some(name) => match renames.get(name.as_bytes()) {
+                    Some(new_name) => format!("/{}", String::from_utf8_lossy(new_name)),
+                    None => token.to_string(),
+                },
+                None => token.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+            .into_bytes()
+    }
+
+    /// Title for the top-level bookmark of a source file: its Info `/Title`,
+    /// falling back to the file name without extension.
+    fn document_title(doc: &Document, path: &str) -> Object {
+        let info_title = doc
+            .trailer
+            .get(b"Info")
+            .and_then(|info| doc.dereference(info))
+            .and_then(|(_, info)| info.as_dict())
+            .and_then(|info| info.get(b"Title"))
+            .and_then(|title| doc.dereference(title))
+            .and_then(|(_, title)| lopdf::decode_text_string(title))
+            .ok()
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty());
+
+        let title = info_title.unwrap_or_else(|| {
+            Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string())
+        });
+        lopdf::text_string(&title)
+    }
+
+    /// Collect the outline tree of a source document, mapping destinations onto
+    /// the merged pages. Items pointing at pages that were not selected are
+    /// dropped, unless they still have children, in which case they point at
+    /// their first remaining child instead.
+    pub(crate) fn collect_outlines(
+        doc: &Document,
+        id_map: &BTreeMap<ObjectId, ObjectId>,
+        page_map: &BTreeMap<ObjectId, ObjectId>,
+    ) -> Vec<OutlineNode> {
+        let first = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(|outlines| doc.dereference(outlines))
+            .and_then(|(_, outlines)| outlines.as_dict())
+            .and_then(|outlines| outlines.get(b"First"))
+            .and_then(Object::as_reference);
+
+        match first {
+            Ok(first) => {
+                let mut visited = HashSet::new();
+                Self::collect_outline_items(doc, first, id_map, page_map, &mut visited)
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn collect_outline_items(
+        doc: &Document,
+        first: ObjectId,
+        id_map: &BTreeMap<ObjectId, ObjectId>,
+        page_map: &BTreeMap<ObjectId, ObjectId>,
+        visited: &mut HashSet<ObjectId>,
+    ) -> Vec<OutlineNode> {
+        let mut nodes = Vec::new();
+        let mut current = Some(first);
+
+        while let Some(item_id) = current {
+            // Guard against malformed outlines whose sibling chain loops
+            if !visited.insert(item_id) {
+                break;
+            }
+            let item = match doc.get_dictionary(item_id) {
+                Ok(item) => item,
+                Err(_) => break,
+            };
+            current = item.get(b"Next").and_then(Object::as_reference).ok();
+
+            let children = match item.get(b"First").and_then(Object::as_reference) {
+                Ok(child) => Self::collect_outline_items(doc, child, id_map, page_map, visited),
+                Err(_) => Vec::new(),
+            };
+
+            let mut action = None;
+            let target = match item.get(b"Dest") {
+                Ok(dest) => Some(dest),
+                Err(_) => match item.get(b"A").and_then(|a| doc.dereference(a)) {
+                    Ok((_, Object::Dictionary(a))) if a.get(b"S").and_then(Object::as_name).ok() == Some(b"GoTo") => {
+                        a.get(b"D").ok()
+                    }
+                    Ok((_, a)) => {
+                        action = Some(Self::remap_object(a, id_map));
+                        None
+                    }
+                    Err(_) => None,
+                },
+            };
+
+            let mut dest = None;
+            if let Some(target) = target {
+                dest = Self::resolve_dest(doc, target)
+                    .and_then(|explicit| Self::map_dest(&explicit, page_map));
+                if dest.is_none() {
+                    // The target page was not selected (or is unresolvable)
+                    match children.first() {
+                        Some(child) if child.dest.is_some() => dest = child.dest.clone(),
+                        _ if children.is_empty() => continue,
+                        _ => {}
+                    }
+                }
+            }
+
+            let title = item
+                .get(b"Title")
+                .and_then(|title| doc.dereference(title))
+                .map(|(_, title)| title.clone())
+                .unwrap_or_else(|_| lopdf::text_string(""));
+
+            let mut extra = Dictionary::new();
+            for key in [&b"C"[..], &b"F"[..]] {
+                if let Ok(value) = item.get(key) {
+                    extra.set(key, value.clone());
+                }
+            }
+
+            nodes.push(OutlineNode {
+                title,
+                dest,
+                action,
+                open: item.get(b"Count").and_then(Object::as_i64).map(|c| c > 0).unwrap_or(false),
+                extra,
+                children,
+            });
+        }
+
+        nodes
+    }
+
+    /// Resolve a destination (explicit array, named destination or `/D` wrapper
+    /// dictionary) into an explicit destination array in the source document.
+    pub(crate) fn resolve_dest(doc: &Document, dest: &Object) -> Option<Vec<Object>> {
+        let (_, dest) = doc.dereference(dest).ok()?;
+        match dest {
+            Object::Array(arr) => Some(arr.clone()),
+            Object::Dictionary(dict) => Self::resolve_dest(doc, dict.get(b"D").ok()?),
+            Object::Name(name) => {
+                let dests = doc.catalog().ok()?.get(b"Dests").ok()?;
+                let (_, dests) = doc.dereference(dests).ok()?;
+                let target = dests.as_dict().ok()?.get(name).ok()?;
+                Self::resolve_dest(doc, target)
+            }
+            Object::String(name, _) => {
+                let names = doc.catalog().ok()?.get(b"Names").ok()?;
+                let (_, names) = doc.dereference(names).ok()?;
+                let tree = names.as_dict().ok()?.get(b"Dests").ok()?;
+                let target = Self::lookup_name_tree(doc, tree, name, 0)?;
+                Self::resolve_dest(doc, &target)
+            }
+            _ => None,
+        }
+    }
+
+    /// Look up a key in a PDF name tree
+    fn lookup_name_tree(doc: &Document, node: &Object, key: &[u8], depth: usize) -> Option<Object> {
+        if depth > 32 {
+            return None;
+        }
+        let (_, node) = doc.dereference(node).ok()?;
+        let node = node.as_dict().ok()?;
+
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            for pair in names.chunks(2) {
+                if let [name, value] = pair {
+                    if name.as_str().ok() == Some(key) {
+                        return Some(value.clone());
+                    }
+                }
+            }
+        }
+
+        if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+            for kid in kids {
+                if let Some(value) = Self::lookup_name_tree(doc, kid, key, depth + 1) {
+                    return Some(value);
+                }
+            }
+        }
+        None
+    }
+
+    /// Point an explicit destination at the corresponding merged page, or
+    /// return `None` when its page is not part of the output.
+    fn map_dest(dest: &[Object], page_map: &BTreeMap<ObjectId, ObjectId>) -> Option<Vec<Object>> {
+        let page = dest.first()?.as_reference().ok()?;
+        let new_page = page_map.get(&page)?;
+        let mut mapped = dest.to_vec();
+        mapped[0] = Object::Reference(*new_page);
+        Some(mapped)
+    }
+
+    /// Write an outline tree into the merged document and return the id of the
+    /// `/Outlines` dictionary.
+    pub(crate) fn write_outlines(merged: &mut Document, roots: Vec<OutlineNode>) -> ObjectId {
+        let outlines_id = merged.new_object_id();
+        let (first, last, visible) = Self::write_outline_items(merged, roots, outlines_id);
+
+        let mut outlines = Dictionary::new();
+        outlines.set("Type", Object::Name(b"Outlines".to_vec()));
+        if let (Some(first), Some(last)) = (first, last) {
+            outlines.set("First", Object::Reference(first));
+            outlines.set("Last", Object::Reference(last));
+        }
+        outlines.set("Count", Object::Integer(visible));
+        merged.objects.insert(outlines_id, Object::Dictionary(outlines));
+        outlines_id
+    }
+
+    /// Write sibling outline items under `parent`. Returns the first and last
+    /// item ids and the number of visible items, as needed for `/Count`.
+    fn write_outline_items(
+        merged: &mut Document,
+        nodes: Vec<OutlineNode>,
+        parent: ObjectId,
+    ) -> (Option<ObjectId>, Option<ObjectId>, i64) {
+        let ids: Vec<ObjectId> = nodes.iter().map(|_| merged.new_object_id()).collect();
+        let mut visible = 0;
+
+        for (i, node) in nodes.into_iter().enumerate() {
+            let mut item = node.extra;
+            item.set("Title", node.title);
+            item.set("Parent", Object::Reference(parent));
+            if i > 0 {
+                item.set("Prev", Object::Reference(ids[i - 1]));
+            }
+            if i + 1 < ids.len() {
+                item.set("Next", Object::Reference(ids[i + 1]));
+            }
+            if let Some(dest) = node.dest {
+                item.set("Dest", Object::Array(dest));
+            } else if let Some(action) = node.action {
+                item.set("A", action);
+            }
+
+            let child_count = node.children.len() as i64;
+            let (first, last, child_visible) = Self::write_outline_items(merged, node.children, ids[i]);
+            if let (Some(first), Some(last)) = (first, last) {
+                item.set("First", Object::Reference(first));
+                item.set("Last", Object::Reference(last));
+                if node.open {
+                    item.set("Count", Object::Integer(child_visible));
+                    visible += child_visible;
+                } else {
+                    item.set("Count", Object::Integer(-child_count));
+                }
+            }
+
+            visible += 1;
+            merged.objects.insert(ids[i], Object::Dictionary(item));
+        }
+
+        (ids.first().copied(), ids.last().copied(), visible)
+    }
+
+    /// Collapse identical streams and dictionaries into one shared object.
+    /// Runs until nothing changes, so fonts collapse in layers: first the font
+    /// file streams, then the descriptors pointing at them, then the font
+    /// dictionaries. Returns the number of objects removed and the size of the
+    /// stream data they held.
+    pub fn deduplicate_objects(doc: &mut Document) -> (usize, u64) {
+        let mut removed = 0;
+        let mut bytes_saved = 0;
+
+        loop {
+            let mut canonical: HashMap<u64, Vec<ObjectId>> = HashMap::new();
+            let mut replacements: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+
+            for (&id, obj) in &doc.objects {
+                if !Self::is_shareable(obj) {
+                    continue;
+                }
+                let mut hasher = DefaultHasher::new();
+                Self::hash_object(obj, &mut hasher);
+                let candidates = canonical.entry(hasher.finish()).or_default();
+                match candidates.iter().find(|&&other| Self::same_object(&doc.objects[&other], obj)) {
+                    Some(&other) => {
+                        replacements.insert(id, other);
+                    }
+                    None => candidates.push(id),
+                }
+            }
+
+            if replacements.is_empty() {
+                break;
+            }
+
+            for id in replacements.keys() {
+                if let Some(Object::Stream(stream)) = doc.objects.remove(id) {
+                    bytes_saved += stream.content.len() as u64;
+                }
+                removed += 1;
+            }
+            for obj in doc.objects.values_mut() {
+                Self::replace_references(obj, &replacements);
+            }
+            for (_, value) in doc.trailer.iter_mut() {
+                Self::replace_references(value, &replacements);
+            }
+        }
+
+        (removed, bytes_saved)
+    }
+
+    /// Whether an indirect object may be merged with an identical copy
+    fn is_shareable(obj: &Object) -> bool {
+        let dict = match obj {
+            Object::Stream(stream) => &stream.dict,
+            Object::Dictionary(dict) => dict,
+            _ => return false,
+        };
+        if UNSHAREABLE_TYPES.iter().any(|&t| dict.type_is(t)) {
+            return false;
+        }
+        // Tree nodes and form fields have an identity of their own
+        ![&b"Parent"[..], b"Kids", b"FT", b"T"].iter().any(|&key| dict.has(key))
+    }
+
+    /// Hash an object in a way that agrees with `same_object`: dictionary keys
+    /// are visited in sorted order, and stream positions are ignored.
+    fn hash_object<H: Hasher>(obj: &Object, state: &mut H) {
+        std::mem::discriminant(obj).hash(state);
+        match obj {
+            Object::Null => {}
+            Object::Boolean(b) => b.hash(state),
+            Object::Integer(i) => i.hash(state),
+            Object::Real(r) => r.to_bits().hash(state),
+            Object::Name(name) => name.hash(state),
+            Object::String(bytes, _) => bytes.hash(state),
+            Object::Array(arr) => {
+                arr.len().hash(state);
+                arr.iter().for_each(|item| Self::hash_object(item, state));
+            }
+            Object::Dictionary(dict) => Self::hash_dictionary(dict, state),
+            Object::Stream(stream) => {
+                Self::hash_dictionary(&stream.dict, state);
+                stream.content.hash(state);
+            }
+            Object::Reference(id) => id.hash(state),
+        }
+    }
+
+    fn hash_dictionary<H: Hasher>(dict: &Dictionary, state: &mut H) {
+        let mut entries: Vec<(&Vec<u8>, &Object)> = dict.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.len().hash(state);
+        for (key, value) in entries {
+            key.hash(state);
+            Self::hash_object(value, state);
+        }
+    }
+
+    /// Structural equality that, unlike `Object::eq`, ignores where a stream
+    /// was read from
+    fn same_object(a: &Object, b: &Object) -> bool {
+        match (a, b) {
+            (Object::Stream(a), Object::Stream(b)) => a.dict == b.dict && a.content == b.content,
+            _ => a == b,
+        }
+    }
+
+    /// Rewrite references in place according to `replacements`
+    fn replace_references(obj: &mut Object, replacements: &BTreeMap<ObjectId, ObjectId>) {
+        match obj {
+            Object::Reference(id) => {
+                if let Some(&new_id) = replacements.get(id) {
+                    *id = new_id;
+                }
+            }
+            Object::Array(arr) => arr.iter_mut().for_each(|item| Self::replace_references(item, replacements)),
+            Object::Dictionary(dict) => dict
+                .iter_mut()
+                .for_each(|(_, value)| Self::replace_references(value, replacements)),
+            Object::Stream(stream) => stream
+                .dict
+                .iter_mut()
+                .for_each(|(_, value)| Self::replace_references(value, replacements)),
+            _ => {}
+        }
+    }
+
+    /// Recursively remap all ObjectId references in an Object
+    pub(crate) fn remap_object(obj: &Object, id_map: &BTreeMap<ObjectId, ObjectId>) -> Object {
+        match obj {
+            Object::Reference(old_id) => {
+                if let Some(&new_id) = id_map.get(old_id) {
+                    Object::Reference(new_id)
+                } else {
+                    // The target was not copied; an unmapped id would point at
+                    // an unrelated object of the merged document
+                    Object::Null
+                }
+            }
+            Object::Array(arr) => {
+                let new_arr: Vec<Object> = arr
+                    .iter()
+                    .map(|item| Self::remap_object(item, id_map))
+                    .collect();
+                Object::Array(new_arr)
+            }
+            Object::Dictionary(dict) => {
+                let mut new_dict = Dictionary::new();
+                for (key, value) in dict.iter() {
+                    new_dict.set(key.clone(), Self::remap_object(value, id_map));
+                }
+                Object::Dictionary(new_dict)
+            }
+            Object::Stream(stream) => {
+                let mut new_dict = Dictionary::new();
+                for (key, value) in stream.dict.iter() {
+                    new_dict.set(key.clone(), Self::remap_object(value, id_map));
+                }
+                Object::Stream(lopdf::Stream::new(new_dict, stream.content.clone()))
+            }
+            // All other types (Boolean, Integer, Real, String, Name, Null) have no references
+            _ => obj.clone(),
+        }
+    }
+}
+
+/// An outline item collected from a source document, already pointing at
+/// pages of the merged document.
+pub(crate) struct OutlineNode {
+    title: Object,
+    dest: Option<Vec<Object>>,
+    action: Option<Object>,
+    open: bool,
+    /// Presentation entries carried over as-is (`/C`, `/F`)
+    extra: Dictionary,
+    children: Vec<OutlineNode>,
+}
+
+/// The label of one page: numbering style (`/S`), prefix (`/P`) and number
+#[derive(Debug, Clone, PartialEq)]
+struct PageLabel {
+    style: Option<Vec<u8>>,
+    prefix: Vec<u8>,
+    number: i64,
+}
+
+impl PageLabel {
+    fn decimal(number: i64) -> Self {
+        Self {
+            style: Some(b"D".to_vec()),
+            prefix: Vec::new(),
+            number,
+        }
+    }
+}
+
+/// What the link fix-up pass needs to know about one merged input
+struct LinkSource {
+    /// Canonical path, used to recognise `GoToR` links between inputs
+    path: Option<PathBuf>,
+    /// All pages of the source document, in order
+    page_ids: Vec<ObjectId>,
+    /// Selected source pages and their copies in the merged document
+    page_map: BTreeMap<ObjectId, ObjectId>,
+    /// Source destination names and the names they got in the merged tree
+    dest_names: HashMap<Vec<u8>, Vec<u8>>,
+    merged_pages: Vec<ObjectId>,
+}
+
+/// Where a `GoToR` link points relative to the merge
+enum RemoteTarget {
+    /// A file that is not part of this merge; the link stays as is
+    External,
+    /// A file of this merge, with the destination mapped into the merged
+    /// document or `None` if the page was left out
+    Merged(Option<Object>),
+}
+
+/// The combined interactive form of all merged documents
+#[derive(Default)]
+struct MergedAcroForm {
+    fields: Vec<Object>,
+    field_names: HashSet<String>,
+    /// `/DR` entries by resource category (`Font`, `XObject`, ...)
+    resources: BTreeMap<Vec<u8>, Dictionary>,
+    default_appearance: Option<Object>,
+    need_appearances: bool,
+    sig_flags: i64,
+    calculation_order: Vec<Object>,
+}
+
+impl MergedAcroForm {
+    fn into_dictionary(self) -> Dictionary {
+        let mut acroform = Dictionary::new();
+        acroform.set("Fields", Object::Array(self.fields));
+        if !self.resources.is_empty() {
+            let mut dr = Dictionary::new();
+            for (category, entries) in self.resources {
+                dr.set(category, Object::Dictionary(entries));
+            }
+            acroform.set("DR", Object::Dictionary(dr));
+        }
+        if let Some(da) = self.default_appearance {
+            acroform.set("DA", da);
+        }
+        if self.need_appearances {
+            acroform.set("NeedAppearances", Object::Boolean(true));
+        }
+        if self.sig_flags != 0 {
+            acroform.set("SigFlags", Object::Integer(self.sig_flags));
+        }
+        if !self.calculation_order.is_empty() {
+            acroform.set("CO", Object::Array(self.calculation_order));
+        }
+        acroform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pdf::{OutputSecurity, PageSelection, PdfFileEntry};
+    use crate::services::test_support::*;
+
+    fn entry(path: &str, selected_pages: Option<Vec<usize>>) -> PdfFileEntry {
+        PdfFileEntry { path: path.to_string(), selected_pages, ..Default::default() }
+    }
+
+    #[test]
+    fn test_merge_config_validation() {
+        let config = MergeConfig {
+            files: vec![],
+            output_path: "out.pdf".to_string(),
+            keep_bookmarks: true,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        let result = PdfMergerService::merge(&config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_merge_missing_file() {
+        let config = MergeConfig {
+            files: vec![
+                PdfFileEntry { path: "/nonexistent/a.pdf".to_string(), ..Default::default() },
+                PdfFileEntry { path: "/nonexistent/b.pdf".to_string(), ..Default::default() },
+            ],
+            output_path: "out.pdf".to_string(),
+            keep_bookmarks: true,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        let result = PdfMergerService::merge(&config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_remap_object_reference() {
+        let mut id_map = BTreeMap::new();
+        id_map.insert((1, 0), (100, 0));
+        id_map.insert((2, 0), (200, 0));
+
+        // Test reference remapping
+        let obj = Object::Reference((1, 0));
+        let remapped = PdfMergerService::remap_object(&obj, &id_map);
+        assert_eq!(remapped, Object::Reference((100, 0)));
+
+        // Test array with references
+        let arr = Object::Array(vec![
+            Object::Reference((1, 0)),
+            Object::Reference((2, 0)),
+            Object::Integer(42),
+        ]);
+        let remapped_arr = PdfMergerService::remap_object(&arr, &id_map);
+        match remapped_arr {
+            Object::Array(items) => {
+                assert_eq!(items[0], Object::Reference((100, 0)));
+                assert_eq!(items[1], Object::Reference((200, 0)));
+                assert_eq!(items[2], Object::Integer(42));
+            }
+            _ => panic!("Expected array"),
+        }
+    }
+
+    #[test]
+    fn test_merge_combines_outlines() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut first = build_test_document(3);
+        add_test_outline(&mut first, &[("Intro", 1), ("Body", 2), ("Appendix", 3)]);
+        let mut second = build_test_document(2);
+        add_test_outline(&mut second, &[("Only", 1)]);
+        let first_path = save_test_document(&mut first, dir.path(), "first.pdf");
+        let second_path = save_test_document(&mut second, dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first_path, Some(vec![1, 3])), entry(&second_path, None)],
+            output_path: output.clone(),
+            keep_bookmarks: true,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let pages: Vec<ObjectId> = merged.page_iter().collect();
+        assert_eq!(pages.len(), 4);
+
+        let outlines = merged.get_dict_in_dict(merged.catalog().unwrap(), b"Outlines").unwrap();
+        assert_eq!(outline_titles(&merged, outlines), vec!["first", "second"]);
+
+        let file_item = merged.get_dict_in_dict(outlines, b"First").unwrap();
+        assert_eq!(outline_titles(&merged, file_item), vec!["Intro", "Appendix"]);
+
+        let appendix = merged.get_dict_in_dict(file_item, b"Last").unwrap();
+        let dest = appendix.get(b"Dest").unwrap().as_array().unwrap();
+        assert_eq!(dest[0], Object::Reference(pages[1]));
+    }
+
+    #[test]
+    fn test_merge_writes_document_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut first = build_test_document(1);
+        let info = Dictionary::from_iter(vec![
+            ("Title", lopdf::text_string("Quarterly report")),
+            ("Author", lopdf::text_string("Finance")),
+            ("Creator", lopdf::text_string("Writer")),
+        ]);
+        let info_id = first.add_object(info);
+        first.trailer.set("Info", Object::Reference(info_id));
+        let first_path = save_test_document(&mut first, dir.path(), "first.pdf");
+        let second_path = save_test_document(&mut build_test_document(1), dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first_path, None), entry(&second_path, None)],
+            output_path: output.clone(),
+            info: DocumentInfo {
+                author: Some("Legal & Finance".to_string()),
+                subject: Some("Q3 números".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let info = PdfParserService::document_info(&merged);
+        assert_eq!(info.title.as_deref(), Some("Quarterly report"));
+        assert_eq!(info.author.as_deref(), Some("Legal & Finance"));
+        assert_eq!(info.subject.as_deref(), Some("Q3 números"));
+        assert_eq!(info.creator.as_deref(), Some("Writer"));
+        assert_eq!(info.keywords, None);
+        assert!(PdfParserService::extract_info_field(&merged, b"ModDate").unwrap().starts_with("D:"));
+
+        let catalog = merged.catalog().unwrap();
+        let preferences = merged.get_dict_in_dict(catalog, b"ViewerPreferences").unwrap();
+        assert!(preferences.get(b"DisplayDocTitle").unwrap().as_bool().unwrap());
+        let metadata_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let xmp = merged.get_object(metadata_id).unwrap().as_stream().unwrap().get_plain_content().unwrap();
+        let xmp = String::from_utf8(xmp).unwrap();
+        assert!(xmp.contains("<rdf:li xml:lang=\"x-default\">Quarterly report</rdf:li>"));
+        assert!(xmp.contains("<rdf:li>Legal &amp; Finance</rdf:li>"));
+        assert!(xmp.contains("<xmp:CreatorTool>Writer</xmp:CreatorTool>"));
+    }
+
+    #[test]
+    fn test_merge_normalizes_page_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut receipt = build_test_document(1);
+        let receipt_page = receipt.page_iter().next().unwrap();
+        let annot_id = receipt.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"Square".to_vec())),
+            ("Rect", Object::Array(vec![0.into(), 0.into(), 100.into(), 100.into()])),
+        ]));
+        let page = receipt.get_dictionary_mut(receipt_page).unwrap();
+        page.set("MediaBox", Object::Array(vec![0.into(), 0.into(), 200.into(), 400.into()]));
+        page.set("Annots", Object::Array(vec![Object::Reference(annot_id)]));
+
+        let mut rotated = build_test_document(1);
+        let rotated_page = rotated.page_iter().next().unwrap();
+        let page = rotated.get_dictionary_mut(rotated_page).unwrap();
+        page.set("MediaBox", Object::Array(vec![0.into(), 0.into(), 792.into(), 612.into()]));
+        page.set("Rotate", Object::Integer(90));
+
+        let receipt_path = save_test_document(&mut receipt, dir.path(), "receipt.pdf");
+        let rotated_path = save_test_document(&mut rotated, dir.path(), "rotated.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&receipt_path, None), entry(&rotated_path, None)],
+            output_path: output.clone(),
+            keep_bookmarks: false,
+            page_size: "a4".to_string(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let pages: Vec<ObjectId> = merged.page_iter().collect();
+        let media_box = |id| PdfMergerService::rect_of(&merged, merged.get_dictionary(id).unwrap().get(b"MediaBox").unwrap()).unwrap();
+        assert_eq!(media_box(pages[0]), [0.0, 0.0, A4_SIZE.0, A4_SIZE.1]);
+        assert_eq!(media_box(pages[1]), [0.0, 0.0, A4_SIZE.1, A4_SIZE.0]);
+        assert_eq!(merged.get_dictionary(pages[1]).unwrap().get(b"Rotate").unwrap(), &Object::Integer(90));
+
+        // The 200x400 receipt is scaled by 841.89 / 400 and centered horizontally
+        let scale = A4_SIZE.1 / 400.0;
+        let offset = (A4_SIZE.0 - 200.0 * scale) / 2.0;
+        let annot = merged.get_dictionary(PdfMergerService::page_annotation_ids(&merged, pages[0])[0]).unwrap();
+        let rect = PdfMergerService::rect_of(&merged, annot.get(b"Rect").unwrap()).unwrap();
+        assert!((rect[0] - offset).abs() < 0.01);
+        assert!((rect[2] - (offset + 100.0 * scale)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_merge_moves_destinations_with_fitted_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(2);
+        add_test_outline(&mut doc, &[("Detail", 2)]);
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        doc.get_dictionary_mut(pages[1])
+            .unwrap()
+            .set("MediaBox", Object::Array(vec![0.into(), 0.into(), 200.into(), 400.into()]));
+        let xyz = |left: i64, top: i64| {
+            let kind = Object::Name(b"XYZ".to_vec());
+            Object::Array(vec![Object::Reference(pages[1]), kind, left.into(), top.into(), Object::Null])
+        };
+        let outlines = doc.get_dict_in_dict(doc.catalog().unwrap(), b"Outlines").unwrap();
+        let outline_item = outlines.get(b"First").and_then(Object::as_reference).unwrap();
+        doc.get_dictionary_mut(outline_item).unwrap().set("Dest", xyz(50, 300));
+        add_test_link(&mut doc, 1, ("A", PdfMergerService::goto_action(xyz(100, 100))));
+        let input = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&input, None)],
+            output_path: output.clone(),
+            keep_bookmarks: true,
+            page_size: "a4".to_string(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        // The 200x400 page is scaled by 841.89 / 400 and centered horizontally
+        let scale = A4_SIZE.1 / 400.0;
+        let offset = (A4_SIZE.0 - 200.0 * scale) / 2.0;
+        let assert_position = |dest: &[Object], (left, top): (f32, f32)| {
+            assert_eq!(dest[1].as_name().unwrap(), b"XYZ");
+            assert!((dest[2].as_float().unwrap() - (offset + left * scale)).abs() < 0.01);
+            assert!((dest[3].as_float().unwrap() - top * scale).abs() < 0.01);
+            assert_eq!(dest[4], Object::Null);
+        };
+
+        let merged = Document::load(&output).unwrap();
+        let outlines = merged.get_dict_in_dict(merged.catalog().unwrap(), b"Outlines").unwrap();
+        let item = merged.get_dict_in_dict(outlines, b"First").unwrap();
+        assert_position(item.get(b"Dest").unwrap().as_array().unwrap(), (50.0, 300.0));
+
+        let first_page = merged.page_iter().next().unwrap();
+        let link = merged.get_dictionary(PdfMergerService::page_annotation_ids(&merged, first_page)[0]).unwrap();
+        let action = link.get(b"A").unwrap().as_dict().unwrap();
+        assert_position(action.get(b"D").unwrap().as_array().unwrap(), (100.0, 100.0));
+    }
+
+    #[test]
+    fn test_merge_copies_only_reachable_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut first = build_test_document(50);
+        add_test_outline(&mut first, &[("Start", 1), ("End", 50)]);
+        let mut second = build_test_document(50);
+        let first_path = save_test_document(&mut first, dir.path(), "first.pdf");
+        let second_path = save_test_document(&mut second, dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first_path, Some(vec![2])), entry(&second_path, Some(vec![50]))],
+            output_path: output.clone(),
+            keep_bookmarks: false,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        // Two pages with one content stream each, plus the new Pages, Catalog,
+        // Info and XMP metadata
+        let merged = Document::load(&output).unwrap();
+        assert_eq!(merged.get_pages().len(), 2);
+        let objects = merged.objects.values().filter(|obj| obj.type_name().ok() != Some("XRef"));
+        assert_eq!(objects.count(), 8);
+    }
+
+    /// Build a two-level Pages tree where the root carries the Resources and
+    /// MediaBox, and an intermediate node carries the Rotate and CropBox
+    fn build_inheriting_document() -> Document {
+        let mut doc = Document::with_version("1.5");
+        let root_id = doc.new_object_id();
+        let middle_id = doc.new_object_id();
+
+        let font_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+        ]));
+        let fonts = Dictionary::from_iter(vec![("F1", Object::Reference(font_id))]);
+        let resources_id = doc.add_object(Dictionary::from_iter(vec![("Font", Object::Dictionary(fonts))]));
+
+        let mut kids = Vec::new();
+        for _ in 0..2 {
+            let content_id = doc.add_object(lopdf::Stream::new(
+                Dictionary::new(),
+                b"BT /F1 12 Tf 72 700 Td (Hello) Tj ET".to_vec(),
+            ));
+            kids.push(Object::Reference(doc.add_object(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Page".to_vec())),
+                ("Parent", Object::Reference(middle_id)),
+                ("Contents", Object::Reference(content_id)),
+            ]))));
+        }
+
+        doc.objects.insert(middle_id, Object::Dictionary(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Parent", Object::Reference(root_id)),
+            ("Kids", Object::Array(kids)),
+            ("Count", Object::Integer(2)),
+            ("Rotate", Object::Integer(180)),
+            ("CropBox", Object::Array(vec![10.into(), 10.into(), 400.into(), 500.into()])),
+        ])));
+        doc.objects.insert(root_id, Object::Dictionary(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Kids", Object::Array(vec![Object::Reference(middle_id)])),
+            ("Count", Object::Integer(2)),
+            ("Resources", Object::Reference(resources_id)),
+            ("MediaBox", Object::Array(vec![0.into(), 0.into(), 420.into(), 595.into()])),
+        ])));
+        let catalog_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(root_id)),
+        ]));
+        doc.trailer.set("Root", Object::Reference(catalog_id));
+        doc
+    }
+
+    #[test]
+    fn test_resolve_inherited_attributes() {
+        let mut doc = build_inheriting_document();
+        PdfMergerService::resolve_inherited_attributes(&mut doc);
+
+        for page_id in doc.page_iter() {
+            let page = doc.get_dictionary(page_id).unwrap();
+            assert!(page.get(b"Resources").unwrap().as_reference().is_ok());
+            assert_eq!(page.get(b"Rotate").unwrap(), &Object::Integer(180));
+            assert_eq!(PdfMergerService::rect_of(&doc, page.get(b"MediaBox").unwrap()), Some([0.0, 0.0, 420.0, 595.0]));
+            assert_eq!(PdfMergerService::rect_of(&doc, page.get(b"CropBox").unwrap()), Some([10.0, 10.0, 400.0, 500.0]));
+        }
+    }
+
+    #[test]
+    fn test_resolve_inherited_attributes_keeps_page_values() {
+        let mut doc = build_inheriting_document();
+        let page_id = doc.page_iter().next().unwrap();
+        doc.get_dictionary_mut(page_id).unwrap().set("Rotate", Object::Integer(90));
+        PdfMergerService::resolve_inherited_attributes(&mut doc);
+
+        let rotations: Vec<Object> = doc
+            .page_iter()
+            .map(|id| doc.get_dictionary(id).unwrap().get(b"Rotate").unwrap().clone())
+            .collect();
+        assert_eq!(rotations, vec![Object::Integer(90), Object::Integer(180)]);
+    }
+
+    #[test]
+    fn test_merge_keeps_inherited_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        let first_path = save_test_document(&mut build_inheriting_document(), dir.path(), "first.pdf");
+        let second_path = save_test_document(&mut build_inheriting_document(), dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first_path, None), entry(&second_path, Some(vec![2]))],
+            output_path: output.clone(),
+            keep_bookmarks: false,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let pages: Vec<ObjectId> = merged.page_iter().collect();
+        assert_eq!(pages.len(), 3);
+        for page_id in pages {
+            let fonts = merged.get_page_fonts(page_id).unwrap();
+            assert_eq!(fonts.get(b"F1".as_slice()).unwrap().get(b"BaseFont").unwrap().as_name().unwrap(), b"Helvetica");
+            let page = merged.get_dictionary(page_id).unwrap();
+            assert_eq!(page.get(b"Rotate").unwrap(), &Object::Integer(180));
+            assert_eq!(PdfMergerService::rect_of(&merged, page.get(b"MediaBox").unwrap()), Some([0.0, 0.0, 420.0, 595.0]));
+        }
+    }
+
+    #[test]
+    fn test_merge_deduplicates_shared_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut docs = Vec::new();
+        for name in ["first.pdf", "second.pdf", "third.pdf"] {
+            let mut doc = build_inheriting_document();
+            let font_file = doc.add_object(lopdf::Stream::new(Dictionary::new(), vec![7u8; 4096]));
+            let descriptor = doc.add_object(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"FontDescriptor".to_vec())),
+                ("FontName", Object::Name(b"Lecture".to_vec())),
+                ("FontFile2", Object::Reference(font_file)),
+            ]));
+            let font = doc.add_object(Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Font".to_vec())),
+                ("Subtype", Object::Name(b"TrueType".to_vec())),
+                ("BaseFont", Object::Name(b"Lecture".to_vec())),
+                ("FontDescriptor", Object::Reference(descriptor)),
+            ]));
+            let root = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+            let resources = doc.get_dictionary(root).unwrap().get(b"Resources").unwrap().as_reference().unwrap();
+            let resources = doc.get_dictionary_mut(resources).unwrap();
+            resources.set("Font", Dictionary::from_iter(vec![("F1", Object::Reference(font))]));
+            docs.push(entry(&save_test_document(&mut doc, dir.path(), name), None));
+        }
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: docs,
+            output_path: output.clone(),
+            keep_bookmarks: false,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        let result = PdfMergerService::merge(&config).unwrap();
+        // Two font files plus the identical page contents of the later inputs
+        assert!(result.bytes_saved > 2 * 4096);
+
+        let merged = Document::load(&output).unwrap();
+        let fonts: HashSet<ObjectId> = merged
+            .page_iter()
+            .map(|page| {
+                let (_, resource_ids) = merged.get_page_resources(page).unwrap();
+                let resources = merged.get_dictionary(resource_ids[0]).unwrap();
+                let fonts = resources.get(b"Font").unwrap().as_dict().unwrap();
+                fonts.get(b"F1").unwrap().as_reference().unwrap()
+            })
+            .collect();
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(merged.get_pages().len(), 6);
+    }
+
+    /// Add a one-field AcroForm whose default font `/F1` is `base_font`
+    fn add_test_form(doc: &mut Document, field_name: &str, base_font: &str) {
+        let page_id = doc.page_iter().next().unwrap();
+        let widget_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"Widget".to_vec())),
+            ("FT", Object::Name(b"Tx".to_vec())),
+            ("T", lopdf::text_string(field_name)),
+            ("DA", Object::string_literal("/F1 10 Tf 0 g")),
+            ("Rect", Object::Array(vec![72.into(), 700.into(), 272.into(), 720.into()])),
+            ("P", Object::Reference(page_id)),
+        ]));
+        doc.get_dictionary_mut(page_id).unwrap().set("Annots", Object::Array(vec![Object::Reference(widget_id)]));
+
+        let font = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(base_font.as_bytes().to_vec())),
+        ]);
+        let dr = Dictionary::from_iter(vec![("Font", Object::Dictionary(Dictionary::from_iter(vec![("F1", Object::Dictionary(font))])))]);
+        let acroform = Dictionary::from_iter(vec![
+            ("Fields", Object::Array(vec![Object::Reference(widget_id)])),
+            ("DR", Object::Dictionary(dr)),
+            ("NeedAppearances", Object::Boolean(true)),
+        ]);
+        doc.catalog_mut().unwrap().set("AcroForm", Object::Dictionary(acroform));
+    }
+
+    #[test]
+    fn test_merge_combines_acroforms() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut first = build_test_document(1);
+        add_test_form(&mut first, "name", "Helvetica");
+        let mut second = build_test_document(1);
+        add_test_form(&mut second, "name", "Courier");
+        let first_path = save_test_document(&mut first, dir.path(), "first.pdf");
+        let second_path = save_test_document(&mut second, dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first_path, None), entry(&second_path, None)],
+            output_path: output.clone(),
+            keep_bookmarks: false,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let acroform = merged.get_dict_in_dict(merged.catalog().unwrap(), b"AcroForm").unwrap();
+        assert_eq!(acroform.get(b"NeedAppearances").unwrap(), &Object::Boolean(true));
+
+        let fields: Vec<&Dictionary> = acroform
+            .get(b"Fields")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| merged.get_dictionary(f.as_reference().unwrap()).unwrap())
+            .collect();
+        let names: Vec<String> = fields.iter().map(|f| lopdf::decode_text_string(f.get(b"T").unwrap()).unwrap()).collect();
+        assert_eq!(names, vec!["name", "second_name"]);
+        assert_eq!(fields[1].get(b"DA").unwrap().as_str().unwrap(), b"/F1_second 10 Tf 0 g");
+
+        let fonts = acroform.get(b"DR").unwrap().as_dict().unwrap().get(b"Font").unwrap().as_dict().unwrap();
+        assert_eq!(fonts.len(), 2);
+        let courier = fonts.get(b"F1_second").unwrap().as_dict().unwrap();
+        assert_eq!(courier.get(b"BaseFont").unwrap().as_name().unwrap(), b"Courier");
+    }
+
+    fn add_test_link(doc: &mut Document, page: usize, link: (&str, Object)) {
+        let page_id: ObjectId = doc.page_iter().nth(page - 1).unwrap();
+        let annot_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"Link".to_vec())),
+            ("Rect", Object::Array(vec![0.into(), 0.into(), 10.into(), 10.into()])),
+            link,
+        ]));
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        let mut annots = page.get(b"Annots").and_then(Object::as_array).cloned().unwrap_or_default();
+        annots.push(Object::Reference(annot_id));
+        page.set("Annots", Object::Array(annots));
+    }
+
+    fn add_test_named_dests(doc: &mut Document, dests: &[(&str, usize)]) {
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        let names: Vec<Object> = dests
+            .iter()
+            .flat_map(|(name, page)| {
+                [
+                    Object::string_literal(*name),
+                    Object::Array(vec![Object::Reference(pages[page - 1]), Object::Name(b"Fit".to_vec())]),
+                ]
+            })
+            .collect();
+        let tree = doc.add_object(Dictionary::from_iter(vec![("Names", Object::Array(names))]));
+        let names = Dictionary::from_iter(vec![("Dests", Object::Reference(tree))]);
+        doc.catalog_mut().unwrap().set("Names", Object::Dictionary(names));
+    }
+
+    #[test]
+    fn test_merge_keeps_links_and_named_destinations() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut first = build_test_document(3);
+        let first_pages: Vec<ObjectId> = first.page_iter().collect();
+        add_test_named_dests(&mut first, &[("chapter2", 2), ("appendix", 3)]);
+        add_test_link(&mut first, 1, ("Dest", Object::string_literal("chapter2")));
+        add_test_link(&mut first, 1, ("Dest", Object::string_literal("appendix")));
+        add_test_link(&mut first, 2, ("A", PdfMergerService::goto_action(Object::Array(vec![
+            Object::Reference(first_pages[2]),
+            Object::Name(b"Fit".to_vec()),
+        ]))));
+
+        let mut second = build_test_document(1);
+        add_test_named_dests(&mut second, &[("chapter2", 1)]);
+        add_test_link(&mut second, 1, ("A", Object::Dictionary(Dictionary::from_iter(vec![
+            ("S", Object::Name(b"GoToR".to_vec())),
+            ("F", Object::string_literal("first.pdf")),
+            ("D", Object::string_literal("chapter2")),
+        ]))));
+
+        let first_path = save_test_document(&mut first, dir.path(), "first.pdf");
+        let second_path = save_test_document(&mut second, dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first_path, Some(vec![1, 2])), entry(&second_path, None)],
+            output_path: output.clone(),
+            keep_bookmarks: false,
+            page_size: "original".to_string(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let pages: Vec<ObjectId> = merged.page_iter().collect();
+        let names = merged.get_dict_in_dict(merged.catalog().unwrap(), b"Names").unwrap();
+        let tree = merged.get_dict_in_dict(names, b"Dests").unwrap();
+        let keys: Vec<&[u8]> = tree
+            .get(b"Names")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| pair[0].as_str().unwrap())
+            .collect();
+        assert_eq!(keys, vec![&b"chapter2"[..], b"second_chapter2"]);
+
+        // The link to the left-out appendix is gone, the other one still works
+        let links = PdfMergerService::page_annotation_ids(&merged, pages[0]);
+        assert_eq!(links.len(), 1);
+        let link = merged.get_dictionary(links[0]).unwrap();
+        assert_eq!(link.get(b"Dest").unwrap().as_str().unwrap(), b"chapter2");
+        assert!(PdfMergerService::page_annotation_ids(&merged, pages[1]).is_empty());
+
+        // The cross-file link became an internal one
+        let link = merged.get_dictionary(PdfMergerService::page_annotation_ids(&merged, pages[2])[0]).unwrap();
+        let action = link.get(b"A").unwrap().as_dict().unwrap();
+        assert_eq!(action.get(b"S").unwrap().as_name().unwrap(), b"GoTo");
+        assert_eq!(action.get(b"D").unwrap().as_str().unwrap(), b"chapter2");
+    }
+
+    fn set_test_page_labels(doc: &mut Document, ranges: Vec<(i64, Dictionary)>) {
+        let nums = ranges.into_iter().flat_map(|(start, range)| [Object::Integer(start), Object::Dictionary(range)]).collect();
+        let labels = Dictionary::from_iter(vec![("Nums", Object::Array(nums))]);
+        doc.catalog_mut().unwrap().set("PageLabels", Object::Dictionary(labels));
+    }
+
+    fn merged_page_labels(path: &str) -> Vec<(i64, Dictionary)> {
+        let merged = Document::load(path).unwrap();
+        let tree = merged.catalog().unwrap().get(b"PageLabels").unwrap().clone();
+        let mut ranges = Vec::new();
+        PdfMergerService::number_tree_entries(&merged, &tree, &mut ranges, 0);
+        ranges
+    }
+
+    #[test]
+    fn test_merge_keeps_page_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut book = build_test_document(5);
+        set_test_page_labels(&mut book, vec![
+            (0, Dictionary::from_iter(vec![("S", Object::Name(b"r".to_vec()))])),
+            (2, Dictionary::from_iter(vec![("S", Object::Name(b"D".to_vec()))])),
+        ]);
+        let mut plain = build_test_document(2);
+        let book_path = save_test_document(&mut book, dir.path(), "book.pdf");
+        let plain_path = save_test_document(&mut plain, dir.path(), "plain.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&book_path, Some(vec![1, 2, 4, 5])), entry(&plain_path, None)],
+            output_path: output.clone(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        // i, ii | 2, 3 | then the plain file continues at its output position
+        let ranges = merged_page_labels(&output);
+        let starts: Vec<i64> = ranges.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, vec![0, 2, 4]);
+        assert_eq!(ranges[0].1.get(b"S").unwrap().as_name().unwrap(), b"r");
+        assert_eq!(ranges[1].1.get(b"St").unwrap(), &Object::Integer(2));
+        assert_eq!(ranges[2].1.get(b"St").unwrap(), &Object::Integer(5));
+    }
+
+    #[test]
+    fn test_merge_restarts_page_labels_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let first_path = save_test_document(&mut build_test_document(3), dir.path(), "first.pdf");
+        let second_path = save_test_document(&mut build_test_document(2), dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first_path, None), entry(&second_path, None)],
+            output_path: output.clone(),
+            restart_page_labels: true,
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let ranges = merged_page_labels(&output);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[1].0, 3);
+        assert_eq!(ranges[0].1.get(b"P").unwrap().as_str().unwrap(), b"A-");
+        assert_eq!(ranges[1].1.get(b"P").unwrap().as_str().unwrap(), b"B-");
+        assert!(!ranges[1].1.has(b"St"));
+    }
+
+    #[test]
+    fn test_merge_interleaves_duplex_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let fronts = save_test_document(&mut build_test_document(3), dir.path(), "fronts.pdf");
+        let backs = save_test_document(&mut build_test_document(3), dir.path(), "backs.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![
+                entry(&fronts, None),
+                PdfFileEntry { path: backs.clone(), reverse: true, ..Default::default() },
+            ],
+            output_path: output.clone(),
+            merge_mode: MergeMode::Interleave,
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        // Fronts 0, 1, 2 interleaved with backs scanned as 2, 1, 0
+        assert_eq!(page_markers(&output), vec![0, 2, 1, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_merge_single_file_respects_selected_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = save_test_document(&mut build_test_document(4), dir.path(), "input.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&input, Some(vec![4, 2]))],
+            output_path: output.clone(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        assert_eq!(page_markers(&output), vec![3, 1]);
+    }
+
+    #[test]
+    fn test_order_pages_with_uneven_inputs() {
+        let pages = vec![vec![1, 2, 3], vec![10], vec![20, 21]];
+        assert_eq!(PdfMergerService::order_pages(pages.clone(), MergeMode::Interleave), vec![1, 10, 20, 2, 21, 3]);
+        assert_eq!(PdfMergerService::order_pages(pages, MergeMode::Concatenate), vec![1, 2, 3, 10, 20, 21]);
+    }
+
+    #[test]
+    fn test_merge_orders_duplicates_rotates_and_pads() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = save_test_document(&mut build_test_document(4), dir.path(), "first.pdf");
+        let second = save_test_document(&mut build_test_document(3), dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![
+                PdfFileEntry {
+                    path: first,
+                    pages: Some(vec![
+                        PageSelection { page: 3, ..Default::default() },
+                        PageSelection { page: 1, rotation: 90, blank_after: true },
+                        PageSelection { page: 3, rotation: 270, ..Default::default() },
+                    ]),
+                    ..Default::default()
+                },
+                PdfFileEntry {
+                    path: second,
+                    page_range: Some("3-".to_string()),
+                    pad_to_even: true,
+                    ..Default::default()
+                },
+            ],
+            output_path: output.clone(),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let pages: Vec<ObjectId> = merged.page_iter().collect();
+        assert_eq!(pages.len(), 6);
+        let rotations: Vec<i64> = pages
+            .iter()
+            .map(|&id| merged.get_dictionary(id).unwrap().get(b"Rotate").and_then(Object::as_i64).unwrap_or(0))
+            .collect();
+        assert_eq!(rotations, vec![0, 90, 0, 270, 0, 0]);
+        // Blank pages have no content
+        assert!(merged.get_page_contents(pages[2]).is_empty());
+        assert!(merged.get_page_contents(pages[5]).is_empty());
+        let content = |id| Content::decode(&merged.get_page_content(id).unwrap()).unwrap().operations[1].operands[0].as_i64().unwrap();
+        assert_eq!([content(pages[0]), content(pages[1]), content(pages[3]), content(pages[4])], [2, 0, 2, 2]);
+    }
+
+    #[test]
+    fn test_page_selections_reject_bad_rotation() {
+        let entry = PdfFileEntry {
+            path: "a.pdf".to_string(),
+            pages: Some(vec![PageSelection { page: 1, rotation: 45, ..Default::default() }]),
+            ..Default::default()
+        };
+        assert!(PdfMergerService::page_selections(&entry, 2).is_err());
+    }
+
+    #[test]
+    fn test_merge_encrypts_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = save_test_document(&mut build_test_document(2), dir.path(), "first.pdf");
+        let second = save_test_document(&mut build_test_document(3), dir.path(), "second.pdf");
+        let output = dir.path().join("protected.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![entry(&first, None), entry(&second, None)],
+            output_path: output.clone(),
+            security: Some(OutputSecurity {
+                user_password: "open sesame".to_string(),
+                owner_password: "owner".to_string(),
+                allow_print: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        // Content is unreadable without decryption
+        let raw = Document::load(&output).unwrap();
+        assert!(raw.trailer.has(b"Encrypt"));
+        assert!(matches!(PdfSecurityService::load(&output, None), Err(AppError::EncryptedDocument(_))));
+
+        for password in ["open sesame", "owner"] {
+            let (doc, permissions) = PdfSecurityService::load_with_permissions(&output, Some(password)).unwrap();
+            assert_eq!(doc.get_pages().len(), 5);
+            let permissions = permissions.unwrap();
+            assert!(permissions.print && permissions.print_high_quality);
+            assert!(!permissions.copy && !permissions.modify && !permissions.annotate);
+
+            // Outline titles are strings inside dictionaries and must round-trip too
+            let outlines_id = doc.catalog().unwrap().get(b"Outlines").and_then(Object::as_reference).unwrap();
+            let titles = outline_titles(&doc, doc.get_dictionary(outlines_id).unwrap());
+            assert_eq!(titles, vec!["first", "second"]);
+        }
+    }
+
+    #[test]
+    fn test_merge_keeps_encryption_of_a_single_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut locked = build_test_document(3);
+        let security = OutputSecurity { user_password: "secret".to_string(), ..Default::default() };
+        PdfSecurityService::encrypt(&mut locked, &security).unwrap();
+        let locked = save_test_document(&mut locked, dir.path(), "locked.pdf");
+        let plain = save_test_document(&mut build_test_document(1), dir.path(), "plain.pdf");
+        let locked_entry = PdfFileEntry { password: Some("secret".to_string()), ..entry(&locked, Some(vec![1, 3])) };
+
+        let output = dir.path().join("single.pdf").to_string_lossy().to_string();
+        let config = MergeConfig {
+            files: vec![locked_entry.clone()],
+            output_path: output.clone(),
+            ..Default::default()
+        };
+        assert!(PdfMergerService::merge(&config).unwrap().warnings.is_empty());
+        assert!(matches!(PdfSecurityService::load(&output, None), Err(AppError::EncryptedDocument(_))));
+        assert_eq!(PdfSecurityService::load(&output, Some("secret")).unwrap().get_pages().len(), 2);
+
+        // Several inputs have no single encryption to keep, which the result reports
+        let output = dir.path().join("combined.pdf").to_string_lossy().to_string();
+        let config = MergeConfig {
+            files: vec![locked_entry, entry(&plain, None)],
+            output_path: output.clone(),
+            ..Default::default()
+        };
+        let result = PdfMergerService::merge(&config).unwrap();
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("locked.pdf"));
+        assert_eq!(PdfSecurityService::load(&output, None).unwrap().get_pages().len(), 3);
+    }
 }
@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::models::pdf::{OptimizeConfig, OptimizeOptions, OptimizeResult};
+use crate::services::pdf_merger::PdfMergerService;
+use crate::services::pdf_renderer::{PageMark, PdfRendererService};
+use crate::services::pdf_security::PdfSecurityService;
+use crate::utils::error::AppError;
+use crate::utils::pdf_writer::{self, write_indirect_object, write_xref_stream, XrefEntry};
+
+/// Images with less stream data than this are not worth re-encoding
+const MIN_IMAGE_BYTES: usize = 16 * 1024;
+/// Objects packed into one object stream
+const OBJECTS_PER_STREAM: usize = 100;
+
+pub struct PdfOptimizerService;
+
+/// What `optimize_document` did to a document
+#[derive(Debug, Default)]
+pub struct Optimization {
+    pub images_recompressed: usize,
+    pub objects_removed: usize,
+    /// Object number -> (object stream number, index within that stream)
+    packed: BTreeMap<u32, (u32, usize)>,
+}
+
+impl PdfOptimizerService {
+    /// Optimize one PDF and report the file size before and after
+    pub fn optimize(config: &OptimizeConfig) -> Result<OptimizeResult, AppError> {
+        let original_size = fs::metadata(&config.input_path)?.len();
+        let (mut doc, cipher) = PdfSecurityService::load_with_cipher(&config.input_path, config.password.as_deref())?;
+        let optimization = Self::optimize_document(&mut doc, &config.options)?;
+        if let Some(cipher) = &cipher {
+            PdfSecurityService::reencrypt(&mut doc, cipher)?;
+        }
+        Self::save(&mut doc, &config.output_path, &optimization)?;
+        let optimized_size = fs::metadata(&config.output_path)?.len();
+
+        log::info!(
+            "Optimized {} from {} to {} bytes ({} images recompressed, {} objects removed)",
+            config.input_path,
+            original_size,
+            optimized_size,
+            optimization.images_recompressed,
+            optimization.objects_removed
+        );
+        Ok(OptimizeResult {
+            output_path: config.output_path.clone(),
+            original_size,
+            optimized_size,
+            images_recompressed: optimization.images_recompressed,
+            objects_removed: optimization.objects_removed,
+        })
+    }
+
+    /// Shrink a document in place. Packed objects only reach the file through
+    /// `save`; encryption has to be applied in between, so each object stream
+    /// is encrypted as a whole.
+    pub fn optimize_document(doc: &mut Document, options: &OptimizeOptions) -> Result<Optimization, AppError> {
+        if !(1..=100).contains(&options.jpeg_quality) {
+            return Err(AppError::ConfigError(format!(
+                "JPEG quality must be between 1 and 100, got {}",
+                options.jpeg_quality
+            )));
+        }
+        if options.recompress_images && options.image_dpi <= 0.0 {
+            return Err(AppError::ConfigError("Image resolution must be positive".to_string()));
+        }
+
+        let mut optimization = Optimization::default();
+        Self::strip_private_data(doc);
+        // Containers read from the source file; their objects are loaded already
+        doc.objects.retain(|_, obj| match obj {
+            Object::Stream(stream) => !stream.dict.type_is(b"ObjStm") && !stream.dict.type_is(b"XRef"),
+            _ => true,
+        });
+        optimization.objects_removed += doc.prune_objects().len();
+        optimization.objects_removed += PdfMergerService::deduplicate_objects(doc).0;
+        if options.recompress_images {
+            optimization.images_recompressed = Self::recompress_images(doc, options);
+        }
+        if options.object_streams {
+            optimization.packed = Self::pack_object_streams(doc);
+        }
+        doc.compress();
+        Ok(optimization)
+    }
+
+    /// Save a document prepared by `optimize_document`. Packed objects are
+    /// listed in a cross-reference stream.
+    pub fn save(doc: &mut Document, path: &str, optimization: &Optimization) -> Result<(), AppError> {
+        if optimization.packed.is_empty() {
+            doc.save(path)
+                .map_err(|e| AppError::PdfError(format!("Failed to save PDF: {}", e)))?;
+            return Ok(());
+        }
+        if doc.version.as_str() < "1.5" {
+            doc.version = "1.5".to_string();
+        }
+
+        let mut out = Vec::new();
+        writeln!(out, "%PDF-{}", doc.version)?;
+        out.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+        let mut entries: BTreeMap<u32, XrefEntry> = BTreeMap::new();
+        for (&id, obj) in &doc.objects {
+            entries.insert(id.0, write_indirect_object(&mut out, id, obj)?);
+        }
+        for (&id, &(container, index)) in &optimization.packed {
+            entries.insert(id, XrefEntry::Compressed(container, index));
+        }
+        let xref_id = doc.max_id.max(entries.keys().last().copied().unwrap_or(0)) + 1;
+        for id in 0..xref_id {
+            entries.entry(id).or_insert(XrefEntry::Free);
+        }
+
+        let mut trailer = Dictionary::new();
+        for key in [&b"Root"[..], b"Info", b"ID", b"Encrypt"] {
+            if let Ok(value) = doc.trailer.get(key) {
+                trailer.set(key, value.clone());
+            }
+        }
+        write_xref_stream(&mut out, xref_id, &entries, trailer)?;
+        fs::write(path, out)?;
+        Ok(())
+    }
+
+    /// Drop page thumbnails and the private data applications keep in
+    /// `/PieceInfo`
+    fn strip_private_data(doc: &mut Document) {
+        for obj in doc.objects.values_mut() {
+            let dict = match obj {
+                Object::Dictionary(dict) => dict,
+                Object::Stream(stream) => &mut stream.dict,
+                _ => continue,
+            };
+            dict.remove(b"PieceInfo");
+            if dict.type_is(b"Page") {
+                dict.remove(b"Thumb");
+            }
+        }
+    }
+
+    /// Re-encode large images as JPEG, downsampled to the target resolution
+    /// where the pages show them at a higher one. Returns how many images
+    /// got smaller.
+    fn recompress_images(doc: &mut Document, options: &OptimizeOptions) -> usize {
+        let resolutions = Self::image_resolutions(doc);
+        // Soft masks carry transparency; JPEG artifacts there show as halos
+        let masks: HashSet<ObjectId> = doc
+            .objects
+            .values()
+            .filter_map(|obj| match obj {
+                Object::Stream(stream) => stream.dict.get(b"SMask").and_then(Object::as_reference).ok(),
+                _ => None,
+            })
+            .collect();
+        let images: Vec<ObjectId> = doc
+            .objects
+            .iter()
+            .filter(|(id, obj)| {
+                !masks.contains(id)
+                    && matches!(obj, Object::Stream(stream)
+                        if stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Image"[..]))
+            })
+            .map(|(&id, _)| id)
+            .collect();
+
+        let mut recompressed = 0;
+        for id in images {
+            let scale = resolutions.get(&id).map_or(1.0, |&dpi| (options.image_dpi / dpi).min(1.0));
+            let Some(Object::Stream(stream)) = doc.objects.get(&id) else {
+                continue;
+            };
+            if let Some(stream) = Self::recompress_image(doc, stream, scale, options.jpeg_quality) {
+                doc.objects.insert(id, Object::Stream(stream));
+                recompressed += 1;
+            }
+        }
+        recompressed
+    }
+
+    /// Lowest resolution, in pixels per inch, at which the pages show each
+    /// image XObject. Images only used elsewhere (patterns, annotations) are
+    /// missing and keep their size.
+    fn image_resolutions(doc: &Document) -> HashMap<ObjectId, f32> {
+        let mut resolutions = HashMap::new();
+        for page_id in doc.page_iter() {
+            for mark in PdfRendererService::page_marks(doc, page_id) {
+                let PageMark::Image { id, ctm } = mark else {
+                    continue;
+                };
+                let Ok(Object::Stream(image)) = doc.get_object(id) else {
+                    continue;
+                };
+                let pixels = |key: &[u8]| image.dict.get(key).and_then(Object::as_i64).unwrap_or(0) as f32;
+                let shown_width = ctm[0].hypot(ctm[1]) / 72.0;
+                let shown_height = ctm[2].hypot(ctm[3]) / 72.0;
+                if shown_width > 0.0 && shown_height > 0.0 {
+                    let dpi = (pixels(b"Width") / shown_width).min(pixels(b"Height") / shown_height);
+                    let lowest = resolutions.entry(id).or_insert(f32::INFINITY);
+                    *lowest = lowest.min(dpi);
+                }
+            }
+        }
+        resolutions
+    }
+
+    /// Re-encode one image as JPEG, scaled by `scale`. Returns `None` for
+    /// images that can't be re-encoded faithfully or wouldn't get smaller.
+    fn recompress_image(doc: &Document, stream: &Stream, scale: f32, quality: u8) -> Option<Stream> {
+        let dict = &stream.dict;
+        if stream.content.len() < MIN_IMAGE_BYTES
+            || dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false)
+            || dict.has(b"Decode")
+            || dict.has(b"SMaskInData")
+            // A color key mask would no longer match the altered colors
+            || matches!(dict.get(b"Mask"), Ok(Object::Array(_)))
+            || dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8)
+        {
+            return None;
+        }
+        let components = PdfRendererService::color_components(doc, dict.get(b"ColorSpace").ok()?)?;
+        let filters = stream.filters().ok().unwrap_or_default();
+        if components == 4
+            || !matches!(
+                filters.iter().map(String::as_str).collect::<Vec<_>>().as_slice(),
+                [] | ["FlateDecode"] | ["DCTDecode"]
+            )
+        {
+            return None;
+        }
+        let image = PdfRendererService::decode_image(doc, stream)?;
+
+        let new_width = ((image.width() as f32 * scale).round() as u32).max(1);
+        let new_height = ((image.height() as f32 * scale).round() as u32).max(1);
+        let image = if new_width < image.width() {
+            image.resize_exact(new_width, new_height, FilterType::Lanczos3)
+        } else {
+            image
+        };
+        let image = match components {
+            1 => DynamicImage::ImageLuma8(image.to_luma8()),
+            _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+        };
+        let mut jpeg = Vec::new();
+        image.write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, quality)).ok()?;
+        if jpeg.len() >= stream.content.len() {
+            return None;
+        }
+
+        let mut dict = dict.clone();
+        dict.remove(b"DecodeParms");
+        dict.set("Filter", Object::Name(b"DCTDecode".to_vec()));
+        dict.set("Width", Object::Integer(image.width() as i64));
+        dict.set("Height", Object::Integer(image.height() as i64));
+        Some(Stream::new(dict, jpeg).with_compression(false))
+    }
+
+    /// Move every object that is not a stream into new object streams. The
+    /// catalog stays outside, where later steps such as encryption still
+    /// find it.
+    fn pack_object_streams(doc: &mut Document) -> BTreeMap<u32, (u32, usize)> {
+        let root = doc.trailer.get(b"Root").and_then(Object::as_reference).ok();
+        let ids: Vec<ObjectId> = doc
+            .objects
+            .iter()
+            .filter(|(&id, obj)| id.1 == 0 && Some(id) != root && !matches!(obj, Object::Stream(_)))
+            .map(|(&id, _)| id)
+            .collect();
+
+        let mut packed = BTreeMap::new();
+        for chunk in ids.chunks(OBJECTS_PER_STREAM) {
+            let mut header = Vec::new();
+            let mut body = Vec::new();
+            for id in chunk {
+                if let Some(obj) = doc.objects.remove(id) {
+                    header.push(format!("{} {}", id.0, body.len()));
+                    // Writing to a Vec can't fail
+                    let _ = pdf_writer::write_object(&mut body, &obj);
+                    body.push(b'\n');
+                }
+            }
+            let header = header.join(" ") + "\n";
+            let dict = Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"ObjStm".to_vec())),
+                ("N", Object::Integer(chunk.len() as i64)),
+                ("First", Object::Integer(header.len() as i64)),
+            ]);
+            let stream_id = doc.add_object(Stream::new(dict, [header.into_bytes(), body].concat()));
+            for (index, id) in chunk.iter().enumerate() {
+                packed.insert(id.0, (stream_id.0, index));
+            }
+        }
+        packed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::Content;
+    use crate::models::pdf::{MergeConfig, OutputSecurity, PdfFileEntry};
+    use crate::services::test_support::*;
+
+    /// A 2-page document whose first page shows a 600x400 pixel photo-like
+    /// image on a 200x100 point area (216 dpi across, 288 dpi down), plus a
+    /// thumbnail and private application data
+    fn build_bloated_document() -> Document {
+        let mut doc = build_test_document(2);
+        let pixels: Vec<u8> = (0..600u32 * 400)
+            .flat_map(|i| {
+                let (x, y) = (i % 600, i / 600);
+                let noise = (i.wrapping_mul(2_654_435_761) >> 24) as u8 % 16;
+                [(x / 3) as u8 + noise, (y / 2) as u8 + noise, 128 + noise]
+            })
+            .collect();
+        let image = Stream::new(
+            Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(600)),
+                ("Height", Object::Integer(400)),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+            ]),
+            pixels,
+        );
+        let image_id = doc.add_object(image);
+        let thumb_id = doc.add_object(Stream::new(Dictionary::new(), vec![0; 64]));
+        let drawing = doc.add_object(Stream::new(Dictionary::new(), b"q 200 0 0 100 50 50 cm /Im1 Do Q".to_vec()));
+        // Never referenced
+        doc.add_object(Stream::new(Dictionary::new(), b"orphan".to_vec()));
+
+        let first = doc.page_iter().next().unwrap();
+        let page = doc.get_dictionary_mut(first).unwrap();
+        let content = page.get(b"Contents").unwrap().clone();
+        page.set("Contents", Object::Array(vec![content, Object::Reference(drawing)]));
+        let xobjects = Dictionary::from_iter(vec![("Im1", Object::Reference(image_id))]);
+        page.set("Resources", Dictionary::from_iter(vec![("XObject", Object::Dictionary(xobjects))]));
+        page.set("Thumb", Object::Reference(thumb_id));
+        let piece_info = Dictionary::from_iter(vec![("Private", Object::string_literal("data"))]);
+        doc.catalog_mut().unwrap().set("PieceInfo", Object::Dictionary(piece_info));
+        doc
+    }
+
+    fn first_image(doc: &Document) -> &Stream {
+        doc.objects
+            .values()
+            .find_map(|obj| match obj {
+                Object::Stream(stream) if stream.dict.has(b"Width") => Some(stream),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_optimize_shrinks_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = save_test_document(&mut build_bloated_document(), dir.path(), "input.pdf");
+        let output = dir.path().join("optimized.pdf").to_string_lossy().to_string();
+
+        let config = OptimizeConfig {
+            input_path: input,
+            password: None,
+            output_path: output.clone(),
+            options: OptimizeOptions { image_dpi: 72.0, ..Default::default() },
+        };
+        let result = PdfOptimizerService::optimize(&config).unwrap();
+        assert_eq!(result.images_recompressed, 1);
+        // The orphan and the thumbnail
+        assert_eq!(result.objects_removed, 2);
+        assert!(result.optimized_size * 10 < result.original_size);
+        assert_eq!(result.optimized_size, fs::metadata(&output).unwrap().len());
+        assert_eq!(page_markers(&output), vec![0, 1]);
+
+        let raw = fs::read(&output).unwrap();
+        assert!(raw.windows(13).any(|w| w == b"/Type /ObjStm"));
+        assert!(raw.windows(11).any(|w| w == b"/Type /XRef"));
+        let optimized = Document::load(&output).unwrap();
+        let image = first_image(&optimized);
+        // Scaled by 72 / 216, keeping the aspect ratio
+        assert_eq!(image.dict.get(b"Width").unwrap().as_i64().unwrap(), 200);
+        assert_eq!(image.dict.get(b"Height").unwrap().as_i64().unwrap(), 133);
+        assert_eq!(image.dict.get(b"Filter").unwrap().as_name().unwrap(), b"DCTDecode");
+        assert!(image::load_from_memory(&image.content).is_ok());
+        let first = optimized.page_iter().next().unwrap();
+        assert!(!optimized.get_dictionary(first).unwrap().has(b"Thumb"));
+        assert!(!optimized.catalog().unwrap().has(b"PieceInfo"));
+    }
+
+    #[test]
+    fn test_optimize_keeps_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_bloated_document();
+        let security = OutputSecurity { user_password: "secret".to_string(), ..Default::default() };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let input = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let output = dir.path().join("optimized.pdf").to_string_lossy().to_string();
+
+        let config = OptimizeConfig {
+            input_path: input,
+            password: Some("secret".to_string()),
+            output_path: output.clone(),
+            options: OptimizeOptions::default(),
+        };
+        PdfOptimizerService::optimize(&config).unwrap();
+        assert!(matches!(PdfSecurityService::load(&output, None), Err(AppError::EncryptedDocument(_))));
+        let optimized = PdfSecurityService::load(&output, Some("secret")).unwrap();
+        let page_id = optimized.page_iter().nth(1).unwrap();
+        let content = Content::decode(&optimized.get_page_content(page_id).unwrap()).unwrap();
+        assert_eq!(content.operations[1].operands[0].as_i64().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_optimize_rejects_invalid_options() {
+        let mut doc = build_test_document(1);
+        let options = OptimizeOptions { jpeg_quality: 0, ..Default::default() };
+        assert!(PdfOptimizerService::optimize_document(&mut doc, &options).is_err());
+        let options = OptimizeOptions { image_dpi: 0.0, ..Default::default() };
+        assert!(PdfOptimizerService::optimize_document(&mut doc, &options).is_err());
+    }
+
+    #[test]
+    fn test_merge_optimized_and_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = save_test_document(&mut build_bloated_document(), dir.path(), "first.pdf");
+        let second = save_test_document(&mut build_test_document(3), dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![
+                PdfFileEntry { path: first, ..Default::default() },
+                PdfFileEntry { path: second, ..Default::default() },
+            ],
+            output_path: output.clone(),
+            security: Some(OutputSecurity { user_password: "secret".to_string(), ..Default::default() }),
+            optimize: Some(OptimizeOptions::default()),
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = PdfSecurityService::load(&output, Some("secret")).unwrap();
+        let markers: Vec<i64> = merged
+            .page_iter()
+            .map(|page_id| {
+                let content = Content::decode(&merged.get_page_content(page_id).unwrap()).unwrap();
+                content.operations[1].operands[0].as_i64().unwrap()
+            })
+            .collect();
+        assert_eq!(markers, vec![0, 1, 0, 1, 2]);
+        // Scaled by 150 / 216
+        let image = first_image(&merged);
+        assert_eq!(image.dict.get(b"Width").unwrap().as_i64().unwrap(), 417);
+    }
+}
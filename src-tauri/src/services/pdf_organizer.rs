@@ -0,0 +1,198 @@
+use crate::models::pdf::{
+    MergeConfig, OrganizeConfig, OrganizeResult, PageOperation, PageSelection, PdfFileEntry,
+};
+use crate::services::pdf_merger::PdfMergerService;
+use crate::services::pdf_security::PdfSecurityService;
+use crate::utils::error::AppError;
+use crate::utils::page_range::parse_page_range;
+
+pub struct PdfOrganizerService;
+
+impl PdfOrganizerService {
+    /// Apply a list of page operations to one PDF and save the result to
+    /// `config.output_path`
+    pub fn organize(config: &OrganizeConfig) -> Result<OrganizeResult, AppError> {
+        let (doc, cipher) = PdfSecurityService::load_with_cipher(&config.input_path, config.password.as_deref())?;
+        let pages = Self::apply_operations(doc.get_pages().len(), &config.operations)?;
+        let result_pages = pages.len();
+
+        // The merge pipeline already copies pages in any order, with
+        // duplicates and rotation, and keeps outlines, links and forms working
+        let merge_config = MergeConfig {
+            files: vec![PdfFileEntry {
+                path: config.input_path.clone(),
+                password: config.password.clone(),
+                pages: Some(pages),
+                ..Default::default()
+            }],
+            output_path: config.output_path.clone(),
+            ..Default::default()
+        };
+        PdfMergerService::merge_documents(&merge_config, vec![doc], cipher.as_ref())?;
+
+        log::info!(
+            "Applied {} page operations to {} ({} pages)",
+            config.operations.len(),
+            config.input_path,
+            result_pages
+        );
+        Ok(OrganizeResult {
+            output_path: config.output_path.clone(),
+            page_count: result_pages,
+        })
+    }
+
+    /// Resolve the operations into the final list of source pages
+    fn apply_operations(page_count: usize, operations: &[PageOperation]) -> Result<Vec<PageSelection>, AppError> {
+        let mut pages: Vec<PageSelection> = (1..=page_count)
+            .map(|page| PageSelection { page, ..Default::default() })
+            .collect();
+
+        for operation in operations {
+            match operation {
+                PageOperation::Delete { pages: range } => {
+                    let deleted = parse_page_range(range, pages.len())?;
+                    let mut position = 0;
+                    pages.retain(|_| {
+                        position += 1;
+                        !deleted.contains(&position)
+                    });
+                }
+                PageOperation::Reorder { order } => {
+                    let order = parse_page_range(order, pages.len())?;
+                    let mut sorted = order.clone();
+                    sorted.sort_unstable();
+                    if !sorted.iter().copied().eq(1..=pages.len()) {
+                        return Err(AppError::ConfigError(format!(
+                            "A new page order has to list each of the {} pages exactly once",
+                            pages.len()
+                        )));
+                    }
+                    pages = order.into_iter().map(|position| pages[position - 1].clone()).collect();
+                }
+                PageOperation::Duplicate { pages: range, copies } => {
+                    let duplicated = parse_page_range(range, pages.len())?;
+                    pages = pages
+                        .into_iter()
+                        .enumerate()
+                        .flat_map(|(i, page)| {
+                            let count = if duplicated.contains(&(i + 1)) { copies + 1 } else { 1 };
+                            std::iter::repeat(page).take(count)
+                        })
+                        .collect();
+                }
+                PageOperation::Rotate { pages: range, degrees } => {
+                    if degrees % 90 != 0 {
+                        return Err(AppError::ConfigError(format!(
+                            "Rotation must be a multiple of 90 degrees, got {}",
+                            degrees
+                        )));
+                    }
+                    for position in parse_page_range(range, pages.len())? {
+                        let page = &mut pages[position - 1];
+                        page.rotation = (page.rotation + degrees).rem_euclid(360);
+                    }
+                }
+                PageOperation::Extract { pages: range } => {
+                    pages = parse_page_range(range, pages.len())?
+                        .into_iter()
+                        .map(|position| pages[position - 1].clone())
+                        .collect();
+                }
+            }
+
+            if pages.is_empty() {
+                return Err(AppError::ConfigError("The result would have no pages".to_string()));
+            }
+        }
+        Ok(pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{Document, Object, ObjectId};
+    use crate::models::pdf::OutputSecurity;
+    use crate::services::test_support::*;
+
+    fn numbers(pages: &[PageSelection]) -> Vec<(usize, i64)> {
+        pages.iter().map(|page| (page.page, page.rotation)).collect()
+    }
+
+    #[test]
+    fn test_apply_operations() {
+        let operations = vec![
+            PageOperation::Delete { pages: "2".to_string() },
+            PageOperation::Duplicate { pages: "1".to_string(), copies: 1 },
+            PageOperation::Rotate { pages: "2,3".to_string(), degrees: -90 },
+            PageOperation::Reorder { order: "5,4,3,2,1".to_string() },
+            PageOperation::Extract { pages: "1-3".to_string() },
+        ];
+        let pages = PdfOrganizerService::apply_operations(5, &operations).unwrap();
+        // 1 2 3 4 5 -> 1 3 4 5 -> 1 1 3 4 5 -> 1 1' 3' 4 5 -> 5 4 3' 1' 1 -> 5 4 3'
+        assert_eq!(numbers(&pages), vec![(5, 0), (4, 0), (3, 270)]);
+    }
+
+    #[test]
+    fn test_apply_operations_rejects_invalid_edits() {
+        let reorder = PageOperation::Reorder { order: "1,1,2".to_string() };
+        assert!(PdfOrganizerService::apply_operations(3, &[reorder]).is_err());
+        let delete_all = PageOperation::Delete { pages: "1-".to_string() };
+        assert!(PdfOrganizerService::apply_operations(3, &[delete_all]).is_err());
+        let rotate = PageOperation::Rotate { pages: "1".to_string(), degrees: 45 };
+        assert!(PdfOrganizerService::apply_operations(3, &[rotate]).is_err());
+    }
+
+    #[test]
+    fn test_organize_keeps_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(3);
+        let security = OutputSecurity { user_password: "secret".to_string(), ..Default::default() };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let input = save_test_document(&mut doc, dir.path(), "locked.pdf");
+        let output = dir.path().join("organized.pdf").to_string_lossy().to_string();
+
+        let config = OrganizeConfig {
+            input_path: input,
+            password: Some("secret".to_string()),
+            output_path: output.clone(),
+            operations: vec![PageOperation::Delete { pages: "2".to_string() }],
+        };
+        assert_eq!(PdfOrganizerService::organize(&config).unwrap().page_count, 2);
+        assert!(matches!(PdfSecurityService::load(&output, None), Err(AppError::EncryptedDocument(_))));
+        assert_eq!(PdfSecurityService::load(&output, Some("secret")).unwrap().get_pages().len(), 2);
+    }
+
+    #[test]
+    fn test_organize_saves_edited_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(4);
+        add_test_outline(&mut doc, &[("Start", 1), ("Removed", 2), ("End", 4)]);
+        let input = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let output = dir.path().join("organized.pdf").to_string_lossy().to_string();
+
+        let config = OrganizeConfig {
+            input_path: input,
+            password: None,
+            output_path: output.clone(),
+            operations: vec![
+                PageOperation::Delete { pages: "2".to_string() },
+                PageOperation::Duplicate { pages: "3".to_string(), copies: 2 },
+                PageOperation::Rotate { pages: "1".to_string(), degrees: 180 },
+            ],
+        };
+        let result = PdfOrganizerService::organize(&config).unwrap();
+        assert_eq!(result.page_count, 5);
+        assert_eq!(page_markers(&output), vec![0, 2, 3, 3, 3]);
+
+        let organized = Document::load(&output).unwrap();
+        let first: ObjectId = organized.page_iter().next().unwrap();
+        let rotate = organized.get_dictionary(first).unwrap().get(b"Rotate").and_then(Object::as_i64);
+        assert_eq!(rotate.unwrap(), 180);
+        // The outline is kept as is, minus items for deleted pages
+        let outlines_id = organized.catalog().unwrap().get(b"Outlines").and_then(Object::as_reference).unwrap();
+        let titles = outline_titles(&organized, organized.get_dictionary(outlines_id).unwrap());
+        assert_eq!(titles, vec!["Start", "End"]);
+    }
+}
@@ -1,14 +1,47 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
-use lopdf::Document;
-use crate::models::pdf::PdfInfo;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use crate::models::pdf::{
+    DocumentInfo, MetadataUpdate, PageLayout, PageMode, PageText, PdfInfo, PdfMetadata, TextQuality, TextRun,
+    ViewerPreferences,
+};
+use crate::services::document_cache::DocumentCacheService;
+use crate::services::pdf_merger::PdfMergerService;
+use crate::services::pdf_renderer::{multiply, PageMark, PdfRendererService};
+use crate::services::pdf_security::PdfSecurityService;
+use crate::utils::date::{pdf_date_to_iso, DateTime};
 use crate::utils::error::AppError;
+use crate::utils::pdf_font::FontCache;
+use crate::utils::pdf_writer;
+
+/// `/PageLayout` names of the catalog
+const PAGE_LAYOUTS: [(PageLayout, &str); 6] = [
+    (PageLayout::SinglePage, "SinglePage"),
+    (PageLayout::OneColumn, "OneColumn"),
+    (PageLayout::TwoColumnLeft, "TwoColumnLeft"),
+    (PageLayout::TwoColumnRight, "TwoColumnRight"),
+    (PageLayout::TwoPageLeft, "TwoPageLeft"),
+    (PageLayout::TwoPageRight, "TwoPageRight"),
+];
+
+/// `/PageMode` names of the catalog
+const PAGE_MODES: [(PageMode, &str); 6] = [
+    (PageMode::UseNone, "UseNone"),
+    (PageMode::UseOutlines, "UseOutlines"),
+    (PageMode::UseThumbs, "UseThumbs"),
+    (PageMode::FullScreen, "FullScreen"),
+    (PageMode::UseOc, "UseOC"),
+    (PageMode::UseAttachments, "UseAttachments"),
+];
 
 pub struct PdfParserService;
 
 impl PdfParserService {
-    /// Get PDF file information
-    pub fn get_info(path: &str) -> Result<PdfInfo, AppError> {
+    /// Get PDF file information with a thumbnail of the first page whose
+    /// longer side is `thumbnail_size` pixels
+    pub fn get_info(path: &str, password: Option<&str>, thumbnail_size: u32) -> Result<PdfInfo, AppError> {
         if !Path::new(path).exists() {
             return Err(AppError::FileNotFound(path.to_string()));
         }
@@ -16,8 +49,7 @@ impl PdfParserService {
         let metadata = fs::metadata(path)?;
         let file_size = metadata.len();
 
-        let doc = Document::load(path)
-            .map_err(|e| AppError::PdfError(format!("Failed to load PDF: {}", e)))?;
+        let (doc, permissions) = DocumentCacheService::load_with_permissions(path, password)?;
 
         let page_count = doc.get_pages().len();
 
@@ -25,8 +57,11 @@ impl PdfParserService {
         let title = Self::extract_info_field(&doc, b"Title");
         let author = Self::extract_info_field(&doc, b"Author");
 
-        // Generate a simple placeholder thumbnail (base64 encoded)
-        let thumbnail = Self::generate_placeholder_thumbnail(page_count);
+        // A missing thumbnail shouldn't keep the file from being listed
+        let thumbnail = PdfRendererService::first_page_thumbnail(&doc, path, thumbnail_size).unwrap_or_else(|e| {
+            log::warn!("No thumbnail for {}: {}", path, e);
+            String::new()
+        });
 
         Ok(PdfInfo {
             page_count,
@@ -34,96 +69,798 @@ impl PdfParserService {
             title,
             author,
             thumbnail,
+            encrypted: permissions.is_some(),
+            permissions,
         })
     }
 
     /// Extract text content from a specific page
-    pub fn extract_page_text(path: &str, page_num: usize) -> Result<String, AppError> {
-        let doc = Document::load(path)
-            .map_err(|e| AppError::PdfError(format!("Failed to load PDF: {}", e)))?;
+    pub fn extract_page_text(path: &str, page_num: usize, password: Option<&str>) -> Result<String, AppError> {
+        Ok(Self::plain_text(&Self::extract_page_runs(path, page_num, password)?))
+    }
+
+    /// Extract all text from a PDF
+    pub fn extract_all_text(path: &str, password: Option<&str>) -> Result<Vec<String>, AppError> {
+        Ok(Self::extract_all_pages(path, password)?.iter().map(Self::plain_text).collect())
+    }
+
+    /// Positioned text of every page, with how well it could be decoded
+    pub fn extract_all_pages(path: &str, password: Option<&str>) -> Result<Vec<PageText>, AppError> {
+        let doc = DocumentCacheService::load(path, password)?;
+        let mut fonts = FontCache::new();
+        Ok(doc
+            .page_iter()
+            .enumerate()
+            .map(|(index, page_id)| Self::page_text(&doc, page_id, index + 1, &mut fonts))
+            .collect())
+    }
 
-        let pages: Vec<lopdf::ObjectId> = doc.page_iter().collect();
-        if page_num == 0 || page_num > pages.len() {
-            return Err(AppError::PdfError(format!(
-                "Page {} out of range (1-{})", page_num, pages.len()
-            )));
+    /// Check if a PDF is likely a scanned document (no extractable text).
+    /// Hidden OCR text counts as extractable; characters that can't be
+    /// decoded don't.
+    pub fn is_scanned_pdf(path: &str, password: Option<&str>) -> Result<bool, AppError> {
+        let doc = DocumentCacheService::load(path, password)?;
+        let mut fonts = FontCache::new();
+
+        let total_chars: usize = doc
+            .page_iter()
+            .take(5)
+            .enumerate()
+            .map(|(index, page_id)| {
+                let text = Self::plain_text(&Self::page_text(&doc, page_id, index + 1, &mut fonts));
+                text.replace(char::REPLACEMENT_CHARACTER, "").trim().len()
+            })
+            .sum();
+
+        // If very little text is extractable in the first 5 pages, likely a scanned doc
+        Ok(total_chars < 50)
+    }
+
+    /// Text of a page as positioned runs with their fonts and colors
+    pub fn extract_page_runs(path: &str, page_num: usize, password: Option<&str>) -> Result<PageText, AppError> {
+        let doc = DocumentCacheService::load(path, password)?;
+        let page_count = doc.get_pages().len();
+        let page_id = doc.page_iter().nth(page_num.wrapping_sub(1)).ok_or_else(|| {
+            AppError::PdfError(format!("Page {} out of range (1-{})", page_num, page_count))
+        })?;
+        Ok(Self::page_text(&doc, page_id, page_num, &mut FontCache::new()))
+    }
+
+    /// The runs of a page as lines of plain text, in the order the page
+    /// draws them
+    pub fn plain_text(page: &PageText) -> String {
+        let mut text = String::new();
+        let mut last: Option<&TextRun> = None;
+        for run in &page.runs {
+            if let Some(last) = last {
+                let size = last.font_size;
+                let gap = run.x - (last.x + last.width);
+                if (run.y - last.y).abs() > size * 0.5 || gap < -size {
+                    text.push('\n');
+                } else if needs_space(&last.text, &run.text, gap, size) {
+                    text.push(' ');
+                }
+            }
+            text.push_str(&run.text);
+            last = Some(run);
         }
+        text
+    }
 
-        let page_id = pages[page_num - 1];
-        let text = doc.extract_text(&[page_id.0 as u32])
-            .unwrap_or_default();
+    /// Run the content of a page and join the strings it shows into runs.
+    /// Strings continue a run when they share its style and baseline and
+    /// start close to where it ends; a gap of more than a fifth of the font
+    /// size becomes a space, except between CJK characters.
+    pub fn page_text(doc: &Document, page_id: ObjectId, page_num: usize, fonts: &mut FontCache) -> PageText {
+        let (_, to_display, (width, height)) = PdfMergerService::visible_area(doc, page_id);
+        let mut runs: Vec<TextRun> = Vec::new();
+        let mut quality = TextQuality::default();
+        // Unit vector along the baseline of the last run
+        let mut direction = (1.0, 0.0);
 
-        Ok(text)
+        for mark in PdfRendererService::page_marks_with_fonts(doc, page_id, fonts) {
+            let PageMark::Text(mark) = mark else {
+                continue;
+            };
+            // Hidden text, such as the layer OCR puts over a scan, says
+            // nothing about how well the page's own text decodes
+            let invisible = matches!(mark.render_mode, 3 | 7);
+            if !invisible {
+                let unmapped = mark.text.chars().filter(|&c| c == char::REPLACEMENT_CHARACTER).count();
+                quality.characters += mark.text.chars().filter(|c| !c.is_whitespace()).count();
+                quality.unmapped += unmapped;
+                if let Some(font) = mark.font.as_deref().filter(|_| unmapped > 0) {
+                    if !quality.fonts.contains(&font.name) {
+                        quality.fonts.push(font.name.clone());
+                    }
+                }
+            }
+
+            let m = multiply(&mark.matrix, &to_display);
+            let (x_scale, y_scale) = (m[0].hypot(m[1]), m[2].hypot(m[3]));
+            if x_scale == 0.0 || y_scale == 0.0 {
+                continue;
+            }
+            // Text sheared away from upright is drawn oblique
+            let shear = (m[0] * m[2] + m[1] * m[3]) / (x_scale * y_scale);
+            let font = mark.font.as_deref();
+            let run = TextRun {
+                text: mark.text,
+                x: m[4],
+                y: height - m[5],
+                width: mark.width * x_scale,
+                font_size: mark.font_size * y_scale,
+                font_name: font.map(|font| font.name.clone()).unwrap_or_default(),
+                bold: font.is_some_and(|font| font.bold) || mark.render_mode == 2,
+                italic: font.is_some_and(|font| font.italic) || shear.abs() > 0.1,
+                color: format!("#{:02x}{:02x}{:02x}", mark.color[0], mark.color[1], mark.color[2]),
+                angle: m[1].atan2(m[0]).to_degrees(),
+                invisible,
+            };
+
+            if let Some(last) = runs.last_mut() {
+                let same_style = last.font_name == run.font_name
+                    && (last.font_size - run.font_size).abs() <= last.font_size * 0.01
+                    && (last.bold, last.italic, last.invisible) == (run.bold, run.italic, run.invisible)
+                    && last.color == run.color
+                    && (last.angle - run.angle).abs() < 1.0;
+                // Offset of the new string from the end of the run, along and
+                // across its baseline, with y pointing up as in PDF space
+                let (ux, uy) = direction;
+                let (dx, dy) = (run.x - (last.x + ux * last.width), (height - run.y) - (height - last.y + uy * last.width));
+                let (along, across) = (dx * ux + dy * uy, dy * ux - dx * uy);
+                let size = last.font_size;
+                if same_style && across.abs() <= size * 0.2 && (-size * 0.5..=size * 1.5).contains(&along) {
+                    if needs_space(&last.text, &run.text, along, size) {
+                        last.text.push(' ');
+                    }
+                    last.text.push_str(&run.text);
+                    last.width = (last.width + along + run.width).max(last.width);
+                    continue;
+                }
+            }
+            let angle = run.angle.to_radians();
+            direction = (angle.cos(), angle.sin());
+            runs.push(run);
+        }
+        runs.retain(|run| !run.text.trim().is_empty());
+        quality.score = if quality.characters == 0 {
+            1.0
+        } else {
+            1.0 - quality.unmapped as f32 / quality.characters as f32
+        };
+
+        PageText {
+            page: page_num,
+            width,
+            height,
+            runs,
+            quality,
+        }
     }
 
-    /// Extract all text from a PDF
-    pub fn extract_all_text(path: &str) -> Result<Vec<String>, AppError> {
-        let doc = Document::load(path)
-            .map_err(|e| AppError::PdfError(format!("Failed to load PDF: {}", e)))?;
+    /// Read a text field of the document Info dictionary
+    pub fn extract_info_field(doc: &Document, key: &[u8]) -> Option<String> {
+        let info = doc
+            .trailer
+            .get(b"Info")
+            .and_then(|info| doc.dereference(info))
+            .and_then(|(_, info)| info.as_dict())
+            .ok()?;
+        Self::text_value(doc, info.get(key).ok()?)
+    }
 
-        let pages: Vec<lopdf::ObjectId> = doc.page_iter().collect();
-        let mut texts = Vec::new();
+    /// Decode a text string, following a reference if needed
+    fn text_value(doc: &Document, value: &Object) -> Option<String> {
+        let (_, value) = doc.dereference(value).ok()?;
+        let bytes = value.as_str().ok()?;
+        // Some producers write UTF-8 without the BOM that PDF 2.0 asks for;
+        // anything else is PDFDocEncoding or UTF-16
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Some(text.trim_start_matches('\u{feff}').to_string()),
+            Err(_) => lopdf::decode_text_string(value).ok(),
+        }
+    }
 
-        for (i, _page_id) in pages.iter().enumerate() {
-            let page_num = (i + 1) as u32;
-            let text = doc.extract_text(&[page_num]).unwrap_or_default();
-            texts.push(text);
+    /// The document properties of `doc`
+    pub fn document_info(doc: &Document) -> DocumentInfo {
+        DocumentInfo {
+            title: Self::extract_info_field(doc, b"Title"),
+            author: Self::extract_info_field(doc, b"Author"),
+            subject: Self::extract_info_field(doc, b"Subject"),
+            keywords: Self::extract_info_field(doc, b"Keywords"),
+            creator: Self::extract_info_field(doc, b"Creator"),
+            producer: Self::extract_info_field(doc, b"Producer"),
         }
+    }
+
+    /// Give `doc` a new Info dictionary holding `info`, created and modified
+    /// now, and an XMP metadata stream on the catalog with the same values.
+    /// Empty fields are left out.
+    pub fn write_metadata(doc: &mut Document, info: &DocumentInfo) -> Result<(), AppError> {
+        let now = DateTime::now();
+        let mut dict = Dictionary::new();
+        Self::set_info_fields(&mut dict, info);
+        dict.set("CreationDate", Object::string_literal(now.to_pdf()));
+        dict.set("ModDate", Object::string_literal(now.to_pdf()));
+        let info_id = doc.add_object(dict);
+        doc.trailer.set("Info", Object::Reference(info_id));
 
-        Ok(texts)
+        let xmp = Self::xmp_packet(info, Some(&now.to_xmp()), &now.to_xmp());
+        let metadata_id = doc.add_object(Self::metadata_stream(xmp));
+        doc.catalog_mut()?.set("Metadata", Object::Reference(metadata_id));
+        Ok(())
+    }
+
+    /// Document-level metadata of a PDF file
+    pub fn get_metadata(path: &str, password: Option<&str>) -> Result<PdfMetadata, AppError> {
+        let (doc, permissions) = DocumentCacheService::load_with_permissions(path, password)?;
+        let file_size = fs::metadata(path)?.len();
+
+        let catalog = doc.catalog().ok();
+        let catalog_entry = |key: &[u8]| {
+            let value = catalog?.get(key).ok()?;
+            doc.dereference(value).ok().map(|(_, value)| value)
+        };
+        let catalog_name = |key: &[u8]| catalog_entry(key).and_then(|value| value.as_name().ok());
+        let date = |key: &[u8]| Self::extract_info_field(&doc, key).map(|raw| pdf_date_to_iso(&raw).unwrap_or(raw));
+
+        let xmp = catalog_entry(b"Metadata")
+            .and_then(|metadata| metadata.as_stream().ok())
+            .and_then(|stream| stream.get_plain_content().ok())
+            .map(|content| String::from_utf8_lossy(&content).into_owned());
+        let viewer_preferences = catalog_entry(b"ViewerPreferences")
+            .and_then(|preferences| preferences.as_dict().ok())
+            .map(Self::viewer_preferences)
+            .unwrap_or_default();
+        let signatures = doc
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .filter(|dict| dict.has(b"ByteRange") && dict.has(b"Contents"))
+            .count();
+
+        Ok(PdfMetadata {
+            info: Self::document_info(&doc),
+            creation_date: date(b"CreationDate"),
+            modification_date: date(b"ModDate"),
+            xmp,
+            language: catalog.and_then(|catalog| Self::text_value(&doc, catalog.get(b"Lang").ok()?)),
+            page_layout: catalog_name(b"PageLayout").and_then(|name| {
+                PAGE_LAYOUTS.iter().find(|(_, known)| known.as_bytes() == name).map(|(layout, _)| *layout)
+            }),
+            page_mode: catalog_name(b"PageMode")
+                .and_then(|name| PAGE_MODES.iter().find(|(_, known)| known.as_bytes() == name).map(|(mode, _)| *mode)),
+            viewer_preferences,
+            pdf_version: doc.version.clone(),
+            page_count: doc.get_pages().len(),
+            file_size,
+            encrypted: permissions.is_some(),
+            signatures,
+        })
     }
 
-    /// Check if a PDF is likely a scanned document (no extractable text)
-    pub fn is_scanned_pdf(path: &str) -> Result<bool, AppError> {
-        let doc = Document::load(path)
-            .map_err(|e| AppError::PdfError(format!("Failed to load PDF: {}", e)))?;
+    /// Replace the metadata of a PDF with an incremental update: the changed
+    /// Info dictionary, catalog and XMP stream are appended to the unchanged
+    /// original bytes, so existing signatures stay valid. Info keys this
+    /// editor doesn't know and the creation date are kept.
+    pub fn update_metadata(update: &MetadataUpdate) -> Result<PdfMetadata, AppError> {
+        let password = update.password.as_deref();
+        let (doc, cipher) = PdfSecurityService::load_with_cipher(&update.input_path, password)?;
+        let original = fs::read(&update.input_path)?;
+        let now = DateTime::now();
 
-        let pages: Vec<lopdf::ObjectId> = doc.page_iter().collect();
-        let pages_to_check = std::cmp::min(5, pages.len());
-        let mut total_chars = 0;
+        let size = doc.trailer.get(b"Size").and_then(Object::as_i64).unwrap_or(0);
+        let mut max_id = doc.max_id.max(size.saturating_sub(1) as u32);
+        let mut existing_or_new = |id: Option<ObjectId>| {
+            id.unwrap_or_else(|| {
+                max_id += 1;
+                (max_id, 0)
+            })
+        };
 
-        for i in 0..pages_to_check {
-            let page_num = (i + 1) as u32;
-            let text = doc.extract_text(&[page_num]).unwrap_or_default();
-            total_chars += text.trim().len();
+        let (info_id, mut info) = match doc.trailer.get(b"Info").and_then(|info| doc.dereference(info)) {
+            Ok((id, Object::Dictionary(info))) => (existing_or_new(id), info.clone()),
+            _ => (existing_or_new(None), Dictionary::new()),
+        };
+        for key in ["Title", "Author", "Subject", "Keywords", "Creator", "Producer"] {
+            info.remove(key.as_bytes());
         }
-        
-        // If very little text is extractable in the first 5 pages, likely a scanned doc
-        Ok(total_chars < 50)
+        Self::set_info_fields(&mut info, &update.info);
+        info.set("ModDate", Object::string_literal(now.to_pdf()));
+
+        let root_id = doc.trailer.get(b"Root").and_then(Object::as_reference)?;
+        let mut catalog = doc.get_dictionary(root_id)?.clone();
+        match update.language.as_deref().filter(|language| !language.is_empty()) {
+            Some(language) => catalog.set("Lang", lopdf::text_string(language)),
+            None => {
+                catalog.remove(b"Lang");
+            }
+        }
+        let layout = update.page_layout.and_then(|layout| PAGE_LAYOUTS.iter().find(|(known, _)| *known == layout));
+        match layout {
+            Some((_, name)) => catalog.set("PageLayout", Object::Name(name.as_bytes().to_vec())),
+            None => {
+                catalog.remove(b"PageLayout");
+            }
+        }
+        match update.page_mode.and_then(|mode| PAGE_MODES.iter().find(|(known, _)| *known == mode)) {
+            Some((_, name)) => catalog.set("PageMode", Object::Name(name.as_bytes().to_vec())),
+            None => {
+                catalog.remove(b"PageMode");
+            }
+        }
+        let mut preferences = catalog
+            .get(b"ViewerPreferences")
+            .and_then(|preferences| doc.dereference(preferences))
+            .and_then(|(_, preferences)| preferences.as_dict())
+            .cloned()
+            .unwrap_or_default();
+        Self::set_viewer_preferences(&mut preferences, &update.viewer_preferences);
+        if preferences.is_empty() {
+            catalog.remove(b"ViewerPreferences");
+        } else {
+            catalog.set("ViewerPreferences", Object::Dictionary(preferences));
+        }
+
+        let created = info
+            .get(b"CreationDate")
+            .ok()
+            .and_then(|date| Self::text_value(&doc, date))
+            .and_then(|date| pdf_date_to_iso(&date));
+        let xmp = match &update.xmp {
+            Some(xmp) => xmp.clone(),
+            None => Self::xmp_packet(&update.info, created.as_deref(), &now.to_xmp()),
+        };
+        let metadata_id = existing_or_new(catalog.get(b"Metadata").and_then(Object::as_reference).ok());
+        catalog.set("Metadata", Object::Reference(metadata_id));
+
+        let mut objects = BTreeMap::from([
+            (info_id, Object::Dictionary(info)),
+            (root_id, Object::Dictionary(catalog)),
+            (metadata_id, Object::Stream(Self::metadata_stream(xmp))),
+        ]);
+        let mut trailer = Dictionary::from_iter(vec![
+            ("Size", Object::Integer(max_id as i64 + 1)),
+            ("Root", Object::Reference(root_id)),
+            ("Info", Object::Reference(info_id)),
+        ]);
+        if let Ok(id) = doc.trailer.get(b"ID") {
+            trailer.set("ID", id.clone());
+        }
+        if let Some(cipher) = &cipher {
+            for (&id, object) in objects.iter_mut() {
+                cipher.encrypt_object(id, object)?;
+            }
+            trailer.set("Encrypt", cipher.encrypt.clone());
+        }
+
+        let output_path = update.output_path.as_deref().unwrap_or(&update.input_path);
+        write_replacing(output_path, &pdf_writer::append_incremental_update(&original, &objects, &trailer)?)?;
+        DocumentCacheService::evict(output_path);
+        Self::get_metadata(output_path, password)
     }
 
-    fn extract_info_field(doc: &Document, key: &[u8]) -> Option<String> {
-        if let Ok(info_ref) = doc.trailer.get(b"Info") {
-            if let Ok(info_id) = info_ref.as_reference() {
-                if let Ok(lopdf::Object::Dictionary(ref info_dict)) = doc.get_object(info_id) {
-                    if let Ok(val) = info_dict.get(key) {
-                        return match val {
-                            lopdf::Object::String(bytes, _) => {
-                                Some(String::from_utf8_lossy(bytes).to_string())
-                            }
-                            _ => None,
-                        };
-                    }
-                }
+    fn viewer_preferences(dict: &Dictionary) -> ViewerPreferences {
+        let flag = |key: &[u8]| dict.get(key).and_then(Object::as_bool).unwrap_or(false);
+        ViewerPreferences {
+            hide_toolbar: flag(b"HideToolbar"),
+            hide_menubar: flag(b"HideMenubar"),
+            hide_window_ui: flag(b"HideWindowUI"),
+            fit_window: flag(b"FitWindow"),
+            center_window: flag(b"CenterWindow"),
+            display_doc_title: flag(b"DisplayDocTitle"),
+            right_to_left: dict.get(b"Direction").and_then(Object::as_name).is_ok_and(|name| name == b"R2L"),
+        }
+    }
+
+    /// Write `preferences` into a `/ViewerPreferences` dictionary, leaving
+    /// its other entries alone. Flags that are off are removed, as that is
+    /// their default.
+    fn set_viewer_preferences(dict: &mut Dictionary, preferences: &ViewerPreferences) {
+        let flags = [
+            ("HideToolbar", preferences.hide_toolbar),
+            ("HideMenubar", preferences.hide_menubar),
+            ("HideWindowUI", preferences.hide_window_ui),
+            ("FitWindow", preferences.fit_window),
+            ("CenterWindow", preferences.center_window),
+            ("DisplayDocTitle", preferences.display_doc_title),
+        ];
+        for (key, value) in flags {
+            if value {
+                dict.set(key, Object::Boolean(true));
+            } else {
+                dict.remove(key.as_bytes());
             }
         }
-        None
+        if preferences.right_to_left {
+            dict.set("Direction", Object::Name(b"R2L".to_vec()));
+        } else {
+            dict.remove(b"Direction");
+        }
+    }
+
+    fn set_info_fields(dict: &mut Dictionary, info: &DocumentInfo) {
+        for (key, value) in Self::info_fields(info) {
+            dict.set(key, lopdf::text_string(value));
+        }
     }
 
-    fn generate_placeholder_thumbnail(_page_count: usize) -> String {
-        // Return a minimal 1x1 transparent PNG as base64 placeholder
-        // In production, use pdfium-render for real thumbnails
-        "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==".to_string()
+    fn metadata_stream(xmp: String) -> Stream {
+        let metadata = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Metadata".to_vec())),
+            ("Subtype", Object::Name(b"XML".to_vec())),
+        ]);
+        // Left uncompressed so tools that scan files for XMP still find it
+        Stream::new(metadata, xmp.into_bytes()).with_compression(false)
+    }
+
+    /// Non-empty fields of `info` with their Info dictionary keys
+    fn info_fields(info: &DocumentInfo) -> Vec<(&'static str, &str)> {
+        [
+            ("Title", &info.title),
+            ("Author", &info.author),
+            ("Subject", &info.subject),
+            ("Keywords", &info.keywords),
+            ("Creator", &info.creator),
+            ("Producer", &info.producer),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.as_deref().filter(|value| !value.is_empty())?)))
+        .collect()
+    }
+
+    /// XMP packet mirroring the Info dictionary, using the property mapping
+    /// of ISO 32000-1 14.3.2. Dates are ISO 8601.
+    fn xmp_packet(info: &DocumentInfo, created: Option<&str>, modified: &str) -> String {
+        let mut properties = vec!["<dc:format>application/pdf</dc:format>".to_string()];
+        for (key, value) in Self::info_fields(info) {
+            let value = xml_escape(value);
+            properties.push(match key {
+                "Title" => format!("<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>", value),
+                "Author" => format!("<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>", value),
+                "Subject" => format!(
+                    "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>",
+                    value
+                ),
+                "Keywords" => format!("<pdf:Keywords>{}</pdf:Keywords>", value),
+                "Creator" => format!("<xmp:CreatorTool>{}</xmp:CreatorTool>", value),
+                _ => format!("<pdf:Producer>{}</pdf:Producer>", value),
+            });
+        }
+        if let Some(created) = created {
+            properties.push(format!("<xmp:CreateDate>{}</xmp:CreateDate>", xml_escape(created)));
+        }
+        for key in ["ModifyDate", "MetadataDate"] {
+            properties.push(format!("<xmp:{0}>{1}</xmp:{0}>", key, xml_escape(modified)));
+        }
+
+        format!(
+            concat!(
+                "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+                "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+                "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+                "<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" ",
+                "xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n",
+                "{}\n",
+                "</rdf:Description>\n",
+                "</rdf:RDF>\n",
+                "</x:xmpmeta>\n",
+                "<?xpacket end=\"w\"?>"
+            ),
+            properties.join("\n")
+        )
     }
 }
 
+/// Write a file next to `path` and move it into place, so that `path`, which
+/// may be the input itself, is never left half written
+fn write_replacing(path: &str, data: &[u8]) -> Result<(), AppError> {
+    let path = Path::new(path);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(data)?;
+    file.as_file().sync_all()?;
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(file.path(), metadata.permissions())?;
+    }
+    file.persist(path).map_err(|e| AppError::IoError(e.error))?;
+    Ok(())
+}
+
+/// Whether text that continues `before` after a gap of `gap` points, in
+/// a font of `size` points, starts a new word
+fn needs_space(before: &str, after: &str, gap: f32, size: f32) -> bool {
+    let between_cjk = before.chars().last().is_some_and(is_cjk) && after.chars().next().is_some_and(is_cjk);
+    gap > size * 0.2 && !between_cjk && !before.ends_with(char::is_whitespace) && !after.starts_with(char::is_whitespace)
+}
+
+/// Han, kana and Hangul characters and the punctuation set between them,
+/// which is written without spaces
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x303F | 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF
+        | 0xF900..=0xFAFF | 0xFF00..=0xFFEF | 0x20000..=0x2FA1F)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::pdf::{OptimizeOptions, OutputSecurity};
+    use crate::services::pdf_optimizer::PdfOptimizerService;
+    use crate::services::test_support::*;
+
+    fn metadata_update(input_path: &str, output_path: &str) -> MetadataUpdate {
+        MetadataUpdate {
+            input_path: input_path.to_string(),
+            password: None,
+            output_path: Some(output_path.to_string()),
+            info: DocumentInfo {
+                title: Some("Jahresbericht – 2024".to_string()),
+                author: Some("Finance".to_string()),
+                ..Default::default()
+            },
+            xmp: None,
+            language: Some("de-DE".to_string()),
+            page_layout: Some(PageLayout::TwoPageRight),
+            page_mode: Some(PageMode::UseOutlines),
+            viewer_preferences: ViewerPreferences {
+                display_doc_title: true,
+                ..Default::default()
+            },
+        }
+    }
 
     #[test]
     fn test_get_info_missing_file() {
-        let result = PdfParserService::get_info("/nonexistent/test.pdf");
+        let result = PdfParserService::get_info("/nonexistent/test.pdf", None, 64);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_extract_info_field_decodes_text_strings() {
+        let mut doc = Document::with_version("1.7");
+        let info = Dictionary::from_iter(vec![
+            ("Title", lopdf::text_string("Überblick")),
+            ("Author", Object::string_literal("Zoë")),
+            ("Subject", Object::string_literal(b"caf\xE9".to_vec())),
+        ]);
+        let info_id = doc.add_object(info);
+        doc.trailer.set("Info", Object::Reference(info_id));
+
+        assert_eq!(PdfParserService::extract_info_field(&doc, b"Title").unwrap(), "Überblick");
+        assert_eq!(PdfParserService::extract_info_field(&doc, b"Author").unwrap(), "Zoë");
+        assert_eq!(PdfParserService::extract_info_field(&doc, b"Subject").unwrap(), "café");
+        assert_eq!(PdfParserService::extract_info_field(&doc, b"Keywords"), None);
+    }
+
+    #[test]
+    fn test_extract_page_runs_positions_and_styles() {
+        let mut doc = build_test_document(1);
+        let helvetica = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+        ]));
+        let to_unicode = doc.add_object(Stream::new(
+            Dictionary::new(),
+            b"1 beginbfrange <0001> <0002> <4F60> endbfrange 1 beginbfchar <0002> <597D> endbfchar".to_vec(),
+        ));
+        let descendant = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"CIDFontType2".to_vec())),
+            ("BaseFont", Object::Name(b"SimHei".to_vec())),
+        ]));
+        let cjk = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type0".to_vec())),
+            ("BaseFont", Object::Name(b"BCDEFG+SimHei".to_vec())),
+            ("Encoding", Object::Name(b"Identity-H".to_vec())),
+            ("DescendantFonts", Object::Array(vec![Object::Reference(descendant)])),
+            ("ToUnicode", Object::Reference(to_unicode)),
+        ]));
+        let content = b"BT /F1 12 Tf 0 0 1 rg 72 700 Td [(Hel) -20 (lo) -300 (World)] TJ ET\n\
+            BT /F2 10 Tf 1 0 0 1 72 650 Tm <00010002> Tj 25 0 Td <0001> Tj 3 Tr <0002> Tj ET\n\
+            BT /F1 12 Tf 2 Tr 1 0 0.3 1 72 600 Tm (Bold) Tj ET\n\
+            BT /F2 10 Tf 72 550 Td <0009> Tj ET"
+            .to_vec();
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+        let page_id = doc.page_iter().next().unwrap();
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.set("Contents", Object::Reference(content_id));
+        page.set(
+            "Resources",
+            Dictionary::from_iter(vec![(
+                "Font",
+                Object::Dictionary(Dictionary::from_iter(vec![
+                    ("F1", Object::Reference(helvetica)),
+                    ("F2", Object::Reference(cjk)),
+                ])),
+            )]),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = save_test_document(&mut doc, dir.path(), "runs.pdf");
+
+        let text = PdfParserService::extract_page_runs(&path, 1, None).unwrap();
+        assert_eq!((text.page, text.width, text.height), (1, 612.0, 792.0));
+        let runs: Vec<(&str, f32, f32, f32, &str)> =
+            text.runs.iter().map(|run| (run.text.as_str(), run.x, run.y, (run.width * 100.0).round() / 100.0, run.font_name.as_str())).collect();
+        // The -300 adjustment opens a gap wide enough for a space; CJK text
+        // is joined without one. Hidden text makes a run of its own.
+        assert_eq!(
+            runs,
+            vec![
+                ("Hello World", 72.0, 92.0, 63.84, "Helvetica"),
+                ("你好你", 72.0, 142.0, 35.0, "SimHei"),
+                ("好", 107.0, 142.0, 10.0, "SimHei"),
+                ("Bold", 72.0, 192.0, 24.0, "Helvetica"),
+                ("\u{FFFD}", 72.0, 242.0, 10.0, "SimHei"),
+            ]
+        );
+        assert_eq!(text.runs[0].color, "#0000ff");
+        assert_eq!(text.runs[1].font_size, 10.0);
+        assert!(text.runs[2].invisible);
+        let bold = &text.runs[3];
+        assert!(bold.bold && bold.italic && !bold.invisible);
+        assert_eq!(bold.angle, 0.0);
+
+        // Code 9 has no ToUnicode entry and the font names no character
+        // collection, so it can't be decoded
+        // The hidden 好 isn't counted
+        assert_eq!((text.quality.characters, text.quality.unmapped), (18, 1));
+        assert_eq!(text.quality.score, 1.0 - 1.0 / 18.0);
+        assert_eq!(text.quality.fonts, vec!["SimHei".to_string()]);
+        assert_eq!(PdfParserService::plain_text(&text), "Hello World\n你好你好\nBold\n\u{FFFD}");
+        assert_eq!(PdfParserService::extract_page_text(&path, 1, None).unwrap(), PdfParserService::plain_text(&text));
+
+        assert!(PdfParserService::extract_page_runs(&path, 2, None).is_err());
+    }
+
+    #[test]
+    fn test_hidden_text_is_left_out_of_quality() {
+        let mut doc = build_test_document(1);
+        let helvetica = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+        ]));
+        // No ToUnicode and no character collection, so no code decodes
+        let descendant = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"CIDFontType2".to_vec())),
+        ]));
+        let undecodable = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type0".to_vec())),
+            ("BaseFont", Object::Name(b"Scanned".to_vec())),
+            ("Encoding", Object::Name(b"Identity-H".to_vec())),
+            ("DescendantFonts", Object::Array(vec![Object::Reference(descendant)])),
+        ]));
+        // An OCR layer over a scan: recognized text and glyphs it couldn't map
+        let content = b"BT 3 Tr /F1 12 Tf 72 700 Td (The quick brown fox jumps over the lazy dog, twice over.) Tj \
+            /F2 12 Tf 0 -20 Td <00090009> Tj ET"
+            .to_vec();
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+        let page_id = doc.page_iter().next().unwrap();
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.set("Contents", Object::Reference(content_id));
+        page.set(
+            "Resources",
+            Dictionary::from_iter(vec![(
+                "Font",
+                Object::Dictionary(Dictionary::from_iter(vec![
+                    ("F1", Object::Reference(helvetica)),
+                    ("F2", Object::Reference(undecodable)),
+                ])),
+            )]),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = save_test_document(&mut doc, dir.path(), "ocr.pdf");
+
+        let text = PdfParserService::extract_page_runs(&path, 1, None).unwrap();
+        assert_eq!(text.runs.len(), 2);
+        assert!(text.runs.iter().all(|run| run.invisible));
+        assert_eq!(text.quality, TextQuality { score: 1.0, ..Default::default() });
+        // The recognized text can be extracted, so the scan isn't reported
+        assert!(!PdfParserService::is_scanned_pdf(&path, None).unwrap());
+
+        // Once visible, the undecodable glyphs count against the page and
+        // aren't extractable text
+        let content = b"BT /F2 12 Tf 72 700 Td <0009000900090009> Tj ET".to_vec();
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+        doc.get_dictionary_mut(page_id).unwrap().set("Contents", Object::Reference(content_id));
+        let path = save_test_document(&mut doc, dir.path(), "visible.pdf");
+        let text = PdfParserService::extract_page_runs(&path, 1, None).unwrap();
+        assert_eq!((text.quality.characters, text.quality.unmapped, text.quality.score), (4, 4, 0.0));
+        assert_eq!(text.quality.fonts, vec!["Scanned".to_string()]);
+        assert!(PdfParserService::is_scanned_pdf(&path, None).unwrap());
+    }
+
+    #[test]
+    fn test_update_metadata_appends_incremental_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(2);
+        let info = Dictionary::from_iter(vec![
+            ("Title", Object::string_literal("Draft")),
+            ("Subject", Object::string_literal("Removed")),
+            ("CreationDate", Object::string_literal("D:20200102030405+01'00'")),
+            ("Department", Object::string_literal("Kept")),
+        ]);
+        let info_id = doc.add_object(info);
+        doc.trailer.set("Info", Object::Reference(info_id));
+        let input = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let output = dir.path().join("output.pdf").to_string_lossy().to_string();
+
+        let metadata = PdfParserService::update_metadata(&metadata_update(&input, &output)).unwrap();
+        let original = fs::read(&input).unwrap();
+        assert!(fs::read(&output).unwrap().starts_with(&original));
+
+        assert_eq!(metadata.info.title.as_deref(), Some("Jahresbericht – 2024"));
+        assert_eq!(metadata.info.author.as_deref(), Some("Finance"));
+        assert_eq!(metadata.info.subject, None);
+        assert_eq!(metadata.creation_date.as_deref(), Some("2020-01-02T03:04:05+01:00"));
+        assert!(metadata.modification_date.is_some());
+        assert_eq!(metadata.language.as_deref(), Some("de-DE"));
+        assert_eq!(metadata.page_layout, Some(PageLayout::TwoPageRight));
+        assert_eq!(metadata.page_mode, Some(PageMode::UseOutlines));
+        assert!(metadata.viewer_preferences.display_doc_title);
+        assert!(!metadata.viewer_preferences.right_to_left);
+        assert_eq!(metadata.page_count, 2);
+        let xmp = metadata.xmp.unwrap();
+        assert!(xmp.contains("Jahresbericht – 2024"));
+        assert!(xmp.contains("<xmp:CreateDate>2020-01-02T03:04:05+01:00</xmp:CreateDate>"));
+
+        let updated = Document::load(&output).unwrap();
+        assert_eq!(PdfParserService::extract_info_field(&updated, b"Department").unwrap(), "Kept");
+        assert!(updated.trailer.has(b"Prev"));
+    }
+
+    #[test]
+    fn test_update_metadata_keeps_cross_reference_streams() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(1);
+        let input = dir.path().join("input.pdf").to_string_lossy().to_string();
+        let optimization = PdfOptimizerService::optimize_document(&mut doc, &OptimizeOptions::default()).unwrap();
+        PdfOptimizerService::save(&mut doc, &input, &optimization).unwrap();
+        assert!(!String::from_utf8_lossy(&fs::read(&input).unwrap()).contains("\nxref"));
+
+        let mut update = metadata_update(&input, "");
+        update.output_path = None;
+        update.viewer_preferences.right_to_left = true;
+        let metadata = PdfParserService::update_metadata(&update).unwrap();
+        assert_eq!(metadata.info.title.as_deref(), Some("Jahresbericht – 2024"));
+        assert!(metadata.viewer_preferences.right_to_left);
+        assert!(!String::from_utf8_lossy(&fs::read(&input).unwrap()).contains("\nxref"));
+        // The update replaced the file rather than leaving a copy next to it
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_update_metadata_of_encrypted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(1);
+        let security = OutputSecurity {
+            user_password: "secret".to_string(),
+            ..Default::default()
+        };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let input = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let output = dir.path().join("output.pdf").to_string_lossy().to_string();
+
+        let mut update = metadata_update(&input, &output);
+        assert!(PdfParserService::update_metadata(&update).is_err());
+        update.password = Some("secret".to_string());
+        let metadata = PdfParserService::update_metadata(&update).unwrap();
+        assert!(metadata.encrypted);
+        assert_eq!(metadata.info.title.as_deref(), Some("Jahresbericht – 2024"));
+        assert_eq!(metadata.language.as_deref(), Some("de-DE"));
+        assert!(!String::from_utf8_lossy(&fs::read(&output).unwrap()).contains("Jahresbericht"));
+    }
 }
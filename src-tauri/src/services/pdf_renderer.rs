@@ -0,0 +1,1280 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, ImageFormat, Rgb, RgbImage};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use sha2::{Digest, Sha256};
+
+use crate::models::pdf::{PageThumbnail, PageThumbnailRequest, PageThumbnailsResult};
+use crate::services::document_cache::DocumentCacheService;
+use crate::services::pdf_merger::PdfMergerService;
+use crate::utils::error::AppError;
+use crate::utils::page_range::parse_page_range;
+use crate::utils::path::Path as VectorPath;
+use crate::utils::pdf_font::{FontCache, Glyph, PdfFont};
+
+/// Longest side of a thumbnail when the caller doesn't ask for a size
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 160;
+/// Bounds of a requested thumbnail size
+pub const MIN_THUMBNAIL_SIZE: u32 = 16;
+pub const MAX_THUMBNAIL_SIZE: u32 = 2048;
+pub const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+/// Nesting limit when following form XObjects
+const MAX_FORM_DEPTH: usize = 16;
+/// Images with more pixels than this are not decoded
+const MAX_IMAGE_PIXELS: u64 = 64 * 1024 * 1024;
+const MAX_CACHED_THUMBNAILS: usize = 256;
+/// Size of the page thumbnail directory above which the oldest files go
+const MAX_DISK_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+/// Text in fonts that aren't embedded is drawn as gray bars from the
+/// baseline to about the x-height
+const TEXT_COLOR: Rgb<u8> = Rgb([160, 160, 160]);
+const TEXT_HEIGHT: f32 = 0.5;
+/// Rows sampled per pixel when filling paths
+const FILL_SAMPLES: usize = 4;
+
+pub struct PdfRendererService;
+
+/// Something drawn on a page, in content stream order
+#[derive(Debug, Clone, PartialEq)]
+pub enum PageMark {
+    /// An image XObject; `ctm` maps the unit square onto the page
+    Image { id: ObjectId, ctm: [f32; 6] },
+    Text(TextMark),
+    Path(PathMark),
+}
+
+/// A filled or stroked path
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMark {
+    /// The path in page space
+    pub path: VectorPath,
+    /// Fill color, and whether the even-odd rule decides what is inside
+    pub fill: Option<(Rgb<u8>, bool)>,
+    /// Stroke color and line width in page space
+    pub stroke: Option<(Rgb<u8>, f32)>,
+}
+
+/// A string shown by one text operator
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMark {
+    /// Maps text space, with the pen at the origin of the baseline, onto the page
+    pub matrix: [f32; 6],
+    /// Advance of the pen in text space
+    pub width: f32,
+    pub font_size: f32,
+    /// Unicode text, with U+FFFD for codes the font doesn't map
+    pub text: String,
+    /// Codes of the shown glyphs, each with the pen position in text space
+    pub glyphs: Vec<(u32, f32)>,
+    pub horizontal_scaling: f32,
+    pub font: Option<Rc<PdfFont>>,
+    pub color: Rgb<u8>,
+    pub stroke_color: Rgb<u8>,
+    /// `Tr` mode: 2 also strokes the outlines, as fake bold does, and 3 and 7
+    /// are invisible, like the text layer OCR puts over scans
+    pub render_mode: i64,
+}
+
+/// The parts of the graphics state that decide where things are drawn
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    ctm: [f32; 6],
+    font: Option<Rc<PdfFont>>,
+    font_size: f32,
+    fill: Paint,
+    stroke: Paint,
+    line_width: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scaling: f32,
+    leading: f32,
+    rise: f32,
+    render_mode: i64,
+}
+
+impl GraphicsState {
+    fn new(ctm: [f32; 6]) -> Self {
+        Self {
+            ctm,
+            font: None,
+            font_size: 0.0,
+            fill: Paint::default(),
+            stroke: Paint::default(),
+            line_width: 1.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            render_mode: 0,
+        }
+    }
+
+    /// The stroke color for operators in capitals, such as `RG`, else the
+    /// fill color
+    fn paint_mut(&mut self, operator: &str) -> &mut Paint {
+        match operator.starts_with(|c: char| c.is_ascii_uppercase()) {
+            true => &mut self.stroke,
+            false => &mut self.fill,
+        }
+    }
+}
+
+/// A fill or stroke color
+#[derive(Debug, Clone)]
+struct Paint {
+    color: Rgb<u8>,
+    /// Components of the color space when it is gray, RGB or CMYK; paths
+    /// painted in other color spaces, such as patterns, are left out
+    components: Option<usize>,
+}
+
+impl Default for Paint {
+    fn default() -> Self {
+        Self {
+            color: Rgb([0, 0, 0]),
+            components: Some(1),
+        }
+    }
+}
+
+/// Rendered first pages by file path, modification time and thumbnail size,
+/// with the time each was last used
+#[derive(Default)]
+struct ThumbnailCache {
+    entries: HashMap<(PathBuf, SystemTime, u32), (u64, String)>,
+    clock: u64,
+}
+
+static THUMBNAIL_CACHE: OnceLock<Mutex<ThumbnailCache>> = OnceLock::new();
+
+impl PdfRendererService {
+    /// Thumbnail of the first page of the file at `path` as a PNG data URL,
+    /// taken from the cache while the file is unchanged
+    pub fn first_page_thumbnail(doc: &Document, path: &str, max_size: u32) -> Result<String, AppError> {
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let key = modified.map(|modified| (PathBuf::from(path), modified, max_size));
+        let cache = THUMBNAIL_CACHE.get_or_init(Mutex::default);
+        if let Some(key) = &key {
+            let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+            cache.clock += 1;
+            let clock = cache.clock;
+            if let Some((used, thumbnail)) = cache.entries.get_mut(key) {
+                *used = clock;
+                return Ok(thumbnail.clone());
+            }
+        }
+
+        let page_id = doc
+            .page_iter()
+            .next()
+            .ok_or_else(|| AppError::PdfError(format!("{} has no pages", path)))?;
+        let thumbnail = Self::thumbnail(doc, page_id, max_size)?;
+
+        if let Some(key) = key {
+            let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+            if cache.entries.len() >= MAX_CACHED_THUMBNAILS {
+                let oldest = cache.entries.iter().min_by_key(|(_, (used, _))| *used).map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    cache.entries.remove(&oldest);
+                }
+            }
+            let clock = cache.clock;
+            cache.entries.insert(key, (clock, thumbnail.clone()));
+        }
+        Ok(thumbnail)
+    }
+
+    /// Render a page as a PNG data URL whose longer side is `max_size` pixels
+    pub fn thumbnail(doc: &Document, page_id: ObjectId, max_size: u32) -> Result<String, AppError> {
+        Ok(data_url(&Self::render_png(doc, page_id, max_size)?))
+    }
+
+    /// Thumbnails of the pages of `request`, rendered from one loaded
+    /// document and handed to `on_thumbnail` one at a time in page order.
+    /// The PNGs are kept in `cache_dir` and reused while the file is
+    /// unchanged, except for password-protected files, whose pages are never
+    /// written to disk. Stops with `AppError::Cancelled` once `cancelled` is set.
+    pub fn page_thumbnails(
+        request: &PageThumbnailRequest,
+        cache_dir: &Path,
+        cancelled: &AtomicBool,
+        mut on_thumbnail: impl FnMut(PageThumbnail),
+    ) -> Result<PageThumbnailsResult, AppError> {
+        let doc = DocumentCacheService::load(&request.path, request.password.as_deref())?;
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        let numbers = match &request.pages {
+            Some(spec) => parse_page_range(spec, pages.len())?,
+            None => (1..=pages.len()).collect(),
+        };
+        let size = request.size.clamp(MIN_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE);
+        let file_key = match request.password {
+            Some(_) => None,
+            None => {
+                fs::create_dir_all(cache_dir)?;
+                Some(Self::file_key(&request.path)?)
+            }
+        };
+
+        let mut result = PageThumbnailsResult {
+            task_id: request.task_id.clone(),
+            rendered: 0,
+            cached: 0,
+        };
+        for number in numbers {
+            if cancelled.load(Ordering::SeqCst) {
+                return Err(AppError::Cancelled);
+            }
+            let cache_path = file_key.as_ref().map(|file_key| {
+                let digest = Sha256::digest(format!("{}\n{}\n{}", file_key, number, size));
+                let name: String = digest[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+                cache_dir.join(format!("{}.png", name))
+            });
+
+            let png = match cache_path.as_ref().and_then(|cache_path| fs::read(cache_path).ok()) {
+                Some(png) => {
+                    result.cached += 1;
+                    png
+                }
+                None => {
+                    let png = Self::render_png(&doc, pages[number - 1], size)?;
+                    // Written under another name first so a reader never sees
+                    // half a file; a failed write only costs a render later
+                    if let Some(cache_path) = &cache_path {
+                        let partial = cache_path.with_extension("part");
+                        if let Err(e) = fs::write(&partial, &png).and_then(|_| fs::rename(&partial, cache_path)) {
+                            log::warn!("Could not cache thumbnail {}: {}", cache_path.display(), e);
+                        }
+                    }
+                    result.rendered += 1;
+                    png
+                }
+            };
+            on_thumbnail(PageThumbnail {
+                task_id: request.task_id.clone(),
+                page: number,
+                thumbnail: data_url(&png),
+            });
+        }
+
+        if file_key.is_some() {
+            Self::prune_disk_cache(cache_dir, MAX_DISK_CACHE_BYTES);
+        }
+        Ok(result)
+    }
+
+    fn render_png(doc: &Document, page_id: ObjectId, max_size: u32) -> Result<Vec<u8>, AppError> {
+        let image = Self::render_page(doc, page_id, max_size);
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| AppError::PdfError(format!("Failed to encode thumbnail: {}", e)))?;
+        Ok(png)
+    }
+
+    /// Identifies a file's current contents: its canonical path,
+    /// modification time and size
+    fn file_key(path: &str) -> Result<String, AppError> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_nanos());
+        let canonical = fs::canonicalize(path)?;
+        Ok(format!("{}\n{}\n{}", canonical.display(), modified, metadata.len()))
+    }
+
+    /// Delete the oldest thumbnails until the directory holds at most
+    /// `max_bytes`
+    fn prune_disk_cache(cache_dir: &Path, max_bytes: u64) {
+        let Ok(entries) = fs::read_dir(cache_dir) else {
+            return;
+        };
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok()?;
+                let path = entry.path();
+                let is_png = path.extension().is_some_and(|extension| extension == "png");
+                (metadata.is_file() && is_png).then(|| (metadata.modified().unwrap_or(UNIX_EPOCH), metadata.len(), path))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        files.sort();
+        for (_, len, path) in files {
+            if total <= max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+
+    /// Draw a page onto a white canvas whose longer side is `max_size`
+    /// pixels. Images, filled and stroked paths and the glyphs of embedded
+    /// fonts are drawn as they are placed on the page; text in other fonts is
+    /// shown as gray bars where it runs. Clipping paths, dashes, shadings and
+    /// colors outside the device color spaces are left out.
+    pub fn render_page(doc: &Document, page_id: ObjectId, max_size: u32) -> RgbImage {
+        let (_, to_display, (width, height)) = PdfMergerService::visible_area(doc, page_id);
+        let scale = max_size.max(1) as f32 / width.max(height);
+        let canvas_width = ((width * scale).round() as u32).max(1);
+        let canvas_height = ((height * scale).round() as u32).max(1);
+        let mut canvas = RgbImage::from_pixel(canvas_width, canvas_height, Rgb([255, 255, 255]));
+        // Displayed page space to pixels, which count from the top
+        let to_canvas = multiply(&to_display, &[scale, 0.0, 0.0, -scale, 0.0, canvas_height as f32]);
+
+        for mark in Self::page_marks(doc, page_id) {
+            match mark {
+                PageMark::Image { id, ctm } => {
+                    let Ok(Object::Stream(stream)) = doc.get_object(id) else {
+                        continue;
+                    };
+                    let placement = multiply(&ctm, &to_canvas);
+                    // Scale large images down first so sampling doesn't alias
+                    let shown_width = (placement[0].hypot(placement[1]).ceil() as u32).max(1);
+                    let shown_height = (placement[2].hypot(placement[3]).ceil() as u32).max(1);
+                    if let Some(image) = Self::page_image(doc, stream, shown_width, shown_height) {
+                        let (image_width, image_height) = image.dimensions();
+                        fill(&mut canvas, &placement, |u, v| {
+                            let x = ((u * image_width as f32) as u32).min(image_width - 1);
+                            let y = (((1.0 - v) * image_height as f32) as u32).min(image_height - 1);
+                            *image.get_pixel(x, y)
+                        });
+                    }
+                }
+                PageMark::Text(mark) if !matches!(mark.render_mode, 3 | 7) => {
+                    let Some(font) = mark.font.as_ref().filter(|font| font.has_outlines()) else {
+                        let bar = [mark.width, 0.0, 0.0, mark.font_size * TEXT_HEIGHT, 0.0, 0.0];
+                        let placement = multiply(&multiply(&bar, &mark.matrix), &to_canvas);
+                        fill(&mut canvas, &placement, |_, _| TEXT_COLOR);
+                        continue;
+                    };
+                    for &(code, offset) in &mark.glyphs {
+                        let Some(outline) = font.glyph_outline(code) else {
+                            continue;
+                        };
+                        let glyph = [mark.font_size * mark.horizontal_scaling, 0.0, 0.0, mark.font_size, offset, 0.0];
+                        let outline = outline.transform(&multiply(&multiply(&glyph, &mark.matrix), &to_canvas));
+                        // Modes 1 and 5 only stroke the outlines, 2 and 6 do both
+                        if !matches!(mark.render_mode, 1 | 5) {
+                            fill_path(&mut canvas, &outline, false, mark.color);
+                        }
+                        if matches!(mark.render_mode, 1 | 2 | 5 | 6) {
+                            fill_path(&mut canvas, &outline.stroke(1.0), false, mark.stroke_color);
+                        }
+                    }
+                }
+                PageMark::Text(_) => {}
+                PageMark::Path(mark) => {
+                    let path = mark.path.transform(&to_canvas);
+                    if let Some((color, even_odd)) = mark.fill {
+                        fill_path(&mut canvas, &path, even_odd, color);
+                    }
+                    if let Some((color, width)) = mark.stroke {
+                        // Lines never get thinner than a pixel, as in viewers
+                        fill_path(&mut canvas, &path.stroke((width * scale).max(1.0)), false, color);
+                    }
+                }
+            }
+        }
+        canvas
+    }
+
+    /// Images, text runs and paths of a page, in the order they are drawn
+    pub fn page_marks(doc: &Document, page_id: ObjectId) -> Vec<PageMark> {
+        Self::page_marks_with_fonts(doc, page_id, &mut FontCache::new())
+    }
+
+    /// [`Self::page_marks`], reusing the fonts read for earlier pages
+    pub fn page_marks_with_fonts(doc: &Document, page_id: ObjectId, fonts: &mut FontCache) -> Vec<PageMark> {
+        let mut marks = Vec::new();
+        if let Ok(content) = doc.get_page_content(page_id) {
+            let resources = PdfMergerService::inherited_attribute(doc, page_id, b"Resources")
+                .and_then(|resources| Some(doc.dereference(&resources).ok()?.1.as_dict().ok()?.clone()))
+                .unwrap_or_default();
+            Self::scan_content(doc, &content, &resources, GraphicsState::new(IDENTITY), fonts, &mut marks, 0);
+        }
+        marks
+    }
+
+    /// Decode an image XObject with 1 to 8 bits per component in a gray, RGB,
+    /// CMYK or indexed color space. JPEG, Flate and LZW data is supported;
+    /// JPEG 2000, CCITT and JBIG2 images give `None`.
+    pub fn decode_image(doc: &Document, stream: &Stream) -> Option<DynamicImage> {
+        let dict = &stream.dict;
+        if dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false) {
+            return None;
+        }
+        let width = u32::try_from(dict.get(b"Width").and_then(Object::as_i64).ok()?).ok()?;
+        let height = u32::try_from(dict.get(b"Height").and_then(Object::as_i64).ok()?).ok()?;
+        if width == 0 || height == 0 || u64::from(width) * u64::from(height) > MAX_IMAGE_PIXELS {
+            return None;
+        }
+
+        let filters = stream.filters().ok().unwrap_or_default();
+        if filters.last().is_some_and(|filter| filter == "DCTDecode") {
+            return match filters.len() {
+                1 => image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg).ok(),
+                _ => None,
+            };
+        }
+        if !filters
+            .iter()
+            .all(|filter| matches!(filter.as_str(), "FlateDecode" | "LZWDecode" | "ASCII85Decode" | "ASCIIHexDecode"))
+        {
+            return None;
+        }
+        // lopdf refuses to decode streams marked as images
+        let mut encoded = Dictionary::new();
+        for key in [&b"Filter"[..], b"DecodeParms"] {
+            if let Ok(value) = dict.get(key) {
+                encoded.set(key, value.clone());
+            }
+        }
+        let data = Stream::new(encoded, stream.content.clone()).get_plain_content().ok()?;
+
+        let bits = dict.get(b"BitsPerComponent").and_then(Object::as_i64).unwrap_or(8);
+        if !matches!(bits, 1 | 2 | 4 | 8) {
+            return None;
+        }
+        let bits = bits as usize;
+        let color_space = doc.dereference(dict.get(b"ColorSpace").ok()?).ok()?.1;
+        let (components, palette) = match Self::color_components(doc, color_space) {
+            Some(components) => (components, None),
+            None => {
+                let (base, palette) = Self::indexed_palette(doc, color_space)?;
+                (1, Some((base, palette)))
+            }
+        };
+        // A Decode array of [1 0] inverts the samples, as in many scans
+        let inverted = palette.is_none()
+            && dict
+                .get(b"Decode")
+                .and_then(Object::as_array)
+                .ok()
+                .and_then(|decode| decode.first()?.as_float().ok())
+                .is_some_and(|first| first > 0.5);
+
+        let row_bytes = (width as usize * components * bits).div_ceil(8);
+        if data.len() < row_bytes * height as usize {
+            return None;
+        }
+        let max = ((1u32 << bits) - 1) as f32;
+        let sample = |row: &[u8], index: usize| -> u8 {
+            let bit = index * bits;
+            (row[bit / 8] >> (8 - bits - bit % 8)) & ((1u16 << bits) - 1) as u8
+        };
+        let scaled = |value: u8| -> u8 {
+            let value = (value as f32 * 255.0 / max).round() as u8;
+            if inverted {
+                255 - value
+            } else {
+                value
+            }
+        };
+
+        let rows = data.chunks(row_bytes).take(height as usize);
+        if components == 1 && palette.is_none() {
+            let pixels = rows.flat_map(|row| (0..width as usize).map(move |x| scaled(sample(row, x)))).collect();
+            return GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8);
+        }
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 3);
+        for row in rows {
+            for x in 0..width as usize {
+                let pixel = match &palette {
+                    Some((base, palette)) => {
+                        let start = sample(row, x) as usize * base;
+                        to_rgb(palette.get(start..start + base).unwrap_or(&[0, 0, 0, 0][..*base]))
+                    }
+                    None => {
+                        let values: Vec<u8> = (0..components).map(|c| scaled(sample(row, x * components + c))).collect();
+                        to_rgb(&values)
+                    }
+                };
+                pixels.extend_from_slice(&pixel.0);
+            }
+        }
+        RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+    }
+
+    /// Number of components of a gray, RGB or CMYK color space; `None` for
+    /// the others
+    pub fn color_components(doc: &Document, color_space: &Object) -> Option<usize> {
+        match doc.dereference(color_space).ok()?.1 {
+            Object::Name(name) => match name.as_slice() {
+                b"DeviceGray" | b"CalGray" | b"G" => Some(1),
+                b"DeviceRGB" | b"CalRGB" | b"RGB" => Some(3),
+                b"DeviceCMYK" | b"CMYK" => Some(4),
+                _ => None,
+            },
+            Object::Array(items) => match items.first().and_then(|v| v.as_name().ok())? {
+                b"CalGray" => Some(1),
+                b"CalRGB" | b"Lab" => Some(3),
+                b"ICCBased" => {
+                    let profile = doc.dereference(items.get(1)?).ok()?.1.as_stream().ok()?;
+                    match profile.dict.get(b"N").and_then(Object::as_i64).ok()? {
+                        n @ (1 | 3 | 4) => Some(n as usize),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Components of the base color space and the color table of an
+    /// `/Indexed` color space
+    fn indexed_palette(doc: &Document, color_space: &Object) -> Option<(usize, Vec<u8>)> {
+        let items = color_space.as_array().ok()?;
+        if !matches!(items.first()?.as_name().ok()?, b"Indexed" | b"I") {
+            return None;
+        }
+        let base = Self::color_components(doc, items.get(1)?)?;
+        let palette = match doc.dereference(items.get(3)?).ok()?.1 {
+            Object::String(bytes, _) => bytes.clone(),
+            Object::Stream(stream) => stream.get_plain_content().ok()?,
+            _ => return None,
+        };
+        Some((base, palette))
+    }
+
+    /// Decode an image for drawing at about `width` x `height` pixels, with
+    /// its soft mask blended onto white
+    fn page_image(doc: &Document, stream: &Stream, width: u32, height: u32) -> Option<RgbImage> {
+        let image = Self::decode_image(doc, stream)?;
+        let image = if width < image.width() || height < image.height() {
+            image.resize_exact(width.min(image.width()), height.min(image.height()), FilterType::Triangle)
+        } else {
+            image
+        };
+        let mut rgb = image.to_rgb8();
+
+        let mask = stream
+            .dict
+            .get(b"SMask")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_object(id))
+            .and_then(Object::as_stream)
+            .ok()
+            .and_then(|mask| Self::decode_image(doc, mask));
+        if let Some(mask) = mask {
+            let mask: GrayImage = mask.resize_exact(rgb.width(), rgb.height(), FilterType::Triangle).to_luma8();
+            for (pixel, alpha) in rgb.pixels_mut().zip(mask.pixels()) {
+                let alpha = alpha[0] as u16;
+                for channel in pixel.0.iter_mut() {
+                    *channel = ((*channel as u16 * alpha + 255 * (255 - alpha)) / 255) as u8;
+                }
+            }
+        }
+        Some(rgb)
+    }
+
+    fn scan_content(
+        doc: &Document,
+        content: &[u8],
+        resources: &Dictionary,
+        base: GraphicsState,
+        fonts: &mut FontCache,
+        marks: &mut Vec<PageMark>,
+        depth: usize,
+    ) {
+        let Ok(content) = Content::decode(content) else {
+            return;
+        };
+        let mut state = base.clone();
+        let mut saved = Vec::new();
+        let (mut text_matrix, mut line_matrix) = (IDENTITY, IDENTITY);
+        // In user space until it is painted
+        let mut path = VectorPath::default();
+
+        for operation in &content.operations {
+            let operands = &operation.operands;
+            let number = |index: usize| operands.get(index).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
+            match operation.operator.as_str() {
+                "q" => saved.push(state.clone()),
+                "Q" => state = saved.pop().unwrap_or_else(|| base.clone()),
+                "cm" => {
+                    if let Some(matrix) = matrix(operands) {
+                        state.ctm = multiply(&matrix, &state.ctm);
+                    }
+                }
+                "BT" => (text_matrix, line_matrix) = (IDENTITY, IDENTITY),
+                "g" | "rg" | "k" | "G" | "RG" | "K" => {
+                    let values: Vec<f32> = operands.iter().filter_map(|v| v.as_float().ok()).collect();
+                    let paint = state.paint_mut(&operation.operator);
+                    paint.components = Some(values.len());
+                    paint.color = color(&values);
+                }
+                "cs" | "CS" => {
+                    let components = operands.first().and_then(|name| match name.as_name().ok()? {
+                        b"DeviceGray" => Some(1),
+                        b"DeviceRGB" => Some(3),
+                        b"DeviceCMYK" => Some(4),
+                        name => Self::color_components(doc, Self::resource(doc, resources, b"ColorSpace", name)?),
+                    });
+                    *state.paint_mut(&operation.operator) = Paint {
+                        color: Rgb([0, 0, 0]),
+                        components,
+                    };
+                }
+                "sc" | "scn" | "SC" | "SCN" => {
+                    let values: Vec<f32> = operands.iter().filter_map(|v| v.as_float().ok()).collect();
+                    let paint = state.paint_mut(&operation.operator);
+                    if paint.components == Some(values.len()) {
+                        paint.color = color(&values);
+                    }
+                }
+                "w" => state.line_width = number(0),
+                "m" => path.move_to(number(0), number(1)),
+                "l" => path.line_to(number(0), number(1)),
+                "c" => path.curve_to(number(0), number(1), number(2), number(3), number(4), number(5)),
+                "v" => {
+                    let (x, y) = path.current_point().copied().unwrap_or_default();
+                    path.curve_to(x, y, number(0), number(1), number(2), number(3));
+                }
+                "y" => path.curve_to(number(0), number(1), number(2), number(3), number(2), number(3)),
+                "h" => path.close(),
+                "re" => path.rect(number(0), number(1), number(2), number(3)),
+                "f" | "F" | "f*" | "S" | "s" | "B" | "B*" | "b" | "b*" | "n" => {
+                    let operator = operation.operator.as_str();
+                    if matches!(operator, "s" | "b" | "b*") {
+                        path.close();
+                    }
+                    let painted = std::mem::take(&mut path);
+                    let fill = match operator {
+                        "S" | "s" | "n" => None,
+                        _ => state.fill.components.map(|_| (state.fill.color, operator.ends_with('*'))),
+                    };
+                    let stroke = match operator {
+                        "S" | "s" | "B" | "B*" | "b" | "b*" => state.stroke.components.map(|_| {
+                            let ctm = &state.ctm;
+                            let scale = (ctm[0] * ctm[3] - ctm[1] * ctm[2]).abs().sqrt();
+                            (state.stroke.color, state.line_width * scale)
+                        }),
+                        _ => None,
+                    };
+                    if (fill.is_some() || stroke.is_some()) && !painted.is_empty() {
+                        marks.push(PageMark::Path(PathMark {
+                            path: painted.transform(&state.ctm),
+                            fill,
+                            stroke,
+                        }));
+                    }
+                }
+                "Tf" => {
+                    state.font = operands.first().and_then(|name| Self::font(doc, resources, name, fonts));
+                    state.font_size = number(1);
+                }
+                "Tc" => state.char_spacing = number(0),
+                "Tw" => state.word_spacing = number(0),
+                "Tz" => state.horizontal_scaling = number(0) / 100.0,
+                "TL" => state.leading = number(0),
+                "Ts" => state.rise = number(0),
+                "Tr" => state.render_mode = operands.first().and_then(|v| v.as_i64().ok()).unwrap_or(0),
+                "Td" | "TD" => {
+                    if operation.operator == "TD" {
+                        state.leading = -number(1);
+                    }
+                    line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, number(0), number(1)], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tm" => {
+                    if let Some(matrix) = matrix(operands) {
+                        (text_matrix, line_matrix) = (matrix, matrix);
+                    }
+                }
+                "T*" | "'" | "\"" | "Tj" | "TJ" => {
+                    if operation.operator == "\"" {
+                        state.word_spacing = number(0);
+                        state.char_spacing = number(1);
+                    }
+                    if matches!(operation.operator.as_str(), "T*" | "'" | "\"") {
+                        line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                    let items = match operands.last() {
+                        Some(Object::Array(items)) if operation.operator == "TJ" => items.as_slice(),
+                        Some(last) if operation.operator != "T*" => std::slice::from_ref(last),
+                        _ => &[],
+                    };
+                    for item in items {
+                        match item {
+                            Object::String(bytes, _) => Self::show_text(&state, &mut text_matrix, bytes, marks),
+                            adjustment => {
+                                let shift = -adjustment.as_float().unwrap_or(0.0) / 1000.0
+                                    * state.font_size
+                                    * state.horizontal_scaling;
+                                text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, shift, 0.0], &text_matrix);
+                            }
+                        }
+                    }
+                }
+                "Do" => {
+                    let Some(id) = operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| Self::resource(doc, resources, b"XObject", name)?.as_reference().ok())
+                    else {
+                        continue;
+                    };
+                    let Ok(Object::Stream(xobject)) = doc.get_object(id) else {
+                        continue;
+                    };
+                    match xobject.dict.get(b"Subtype").and_then(Object::as_name).ok() {
+                        Some(b"Image") => marks.push(PageMark::Image { id, ctm: state.ctm }),
+                        Some(b"Form") if depth < MAX_FORM_DEPTH => {
+                            let form_matrix = xobject
+                                .dict
+                                .get(b"Matrix")
+                                .and_then(Object::as_array)
+                                .ok()
+                                .and_then(|form_matrix| matrix(form_matrix))
+                                .unwrap_or(IDENTITY);
+                            let form_resources = xobject
+                                .dict
+                                .get(b"Resources")
+                                .ok()
+                                .and_then(|form_resources| doc.dereference(form_resources).ok())
+                                .and_then(|(_, form_resources)| form_resources.as_dict().ok())
+                                .unwrap_or(resources);
+                            if let Ok(form_content) = xobject.get_plain_content() {
+                                let mut form_state = state.clone();
+                                form_state.ctm = multiply(&form_matrix, &state.ctm);
+                                Self::scan_content(doc, &form_content, form_resources, form_state, fonts, marks, depth + 1);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Record a shown string and move the text matrix past it
+    fn show_text(state: &GraphicsState, text_matrix: &mut [f32; 6], bytes: &[u8], marks: &mut Vec<PageMark>) {
+        let glyphs = match &state.font {
+            Some(font) => font.glyphs(bytes),
+            None => bytes
+                .iter()
+                .map(|&byte| Glyph {
+                    code: u32::from(byte),
+                    width: 500.0,
+                    text: Some(char::from(byte).to_string()),
+                    is_space: byte == b' ',
+                })
+                .collect(),
+        };
+        let mut advance = 0.0;
+        let mut text = String::new();
+        let mut positions = Vec::with_capacity(glyphs.len());
+        for glyph in &glyphs {
+            positions.push((glyph.code, advance));
+            let word_spacing = if glyph.is_space { state.word_spacing } else { 0.0 };
+            advance += (glyph.width / 1000.0 * state.font_size + state.char_spacing + word_spacing)
+                * state.horizontal_scaling;
+            text.push_str(glyph.text.as_deref().unwrap_or("\u{FFFD}"));
+        }
+
+        if advance != 0.0 && state.font_size != 0.0 {
+            marks.push(PageMark::Text(TextMark {
+                matrix: multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, state.rise], &multiply(text_matrix, &state.ctm)),
+                width: advance,
+                font_size: state.font_size,
+                text,
+                glyphs: positions,
+                horizontal_scaling: state.horizontal_scaling,
+                font: state.font.clone(),
+                color: state.fill.color,
+                stroke_color: state.stroke.color,
+                render_mode: state.render_mode,
+            }));
+        }
+        *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, advance, 0.0], text_matrix);
+    }
+
+    /// The font resource `name`, read once per font object
+    fn font(doc: &Document, resources: &Dictionary, name: &Object, fonts: &mut FontCache) -> Option<Rc<PdfFont>> {
+        let font = Self::resource(doc, resources, b"Font", name.as_name().ok()?)?;
+        let Ok(id) = font.as_reference() else {
+            return Some(Rc::new(PdfFont::load(doc, font.as_dict().ok()?)));
+        };
+        if let Some(cached) = fonts.get(&id) {
+            return Some(cached.clone());
+        }
+        let loaded = Rc::new(PdfFont::load(doc, doc.get_dictionary(id).ok()?));
+        fonts.insert(id, loaded.clone());
+        Some(loaded)
+    }
+
+    /// Entry `name` of the resource category `kind`, such as `/Font`
+    fn resource<'a>(doc: &'a Document, resources: &'a Dictionary, kind: &[u8], name: &[u8]) -> Option<&'a Object> {
+        let (_, category) = doc.dereference(resources.get(kind).ok()?).ok()?;
+        category.as_dict().ok()?.get(name).ok()
+    }
+}
+
+/// `a` applied after `b`, as the `cm` operator does
+pub fn multiply(a: &[f32; 6], b: &[f32; 6]) -> [f32; 6] {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+pub fn matrix(operands: &[Object]) -> Option<[f32; 6]> {
+    let values: Vec<f32> = operands.iter().filter_map(|v| v.as_float().ok()).collect();
+    values.try_into().ok()
+}
+
+fn invert(m: &[f32; 6]) -> Option<[f32; 6]> {
+    let determinant = m[0] * m[3] - m[1] * m[2];
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let (a, b, c, d) = (m[3] / determinant, -m[1] / determinant, -m[2] / determinant, m[0] / determinant);
+    Some([a, b, c, d, -(m[4] * a + m[5] * c), -(m[4] * b + m[5] * d)])
+}
+
+/// Paint the pixels of `canvas` covered by the unit square as placed by
+/// `placement`, coloring each by its position `(u, v)` in the square
+fn fill(canvas: &mut RgbImage, placement: &[f32; 6], color: impl Fn(f32, f32) -> Rgb<u8>) {
+    let Some(inverse) = invert(placement) else {
+        return;
+    };
+    let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(u, v)| {
+        (
+            placement[0] * u + placement[2] * v + placement[4],
+            placement[1] * u + placement[3] * v + placement[5],
+        )
+    });
+    let bound = |values: [f32; 4], limit: u32| {
+        let low = values.iter().fold(f32::INFINITY, |low, &v| low.min(v)).floor().max(0.0) as u32;
+        let high = values.iter().fold(f32::NEG_INFINITY, |high, &v| high.max(v)).ceil().min(limit as f32).max(0.0) as u32;
+        low..high
+    };
+    let columns = bound(corners.map(|(x, _)| x), canvas.width());
+    let rows = bound(corners.map(|(_, y)| y), canvas.height());
+    for y in rows {
+        for x in columns.clone() {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let u = inverse[0] * px + inverse[2] * py + inverse[4];
+            let v = inverse[1] * px + inverse[3] * py + inverse[5];
+            if (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v) {
+                canvas.put_pixel(x, y, color(u, v));
+            }
+        }
+    }
+}
+
+/// Paint the pixels of `canvas` inside `path`, given in pixels, by the
+/// nonzero or the even-odd rule, blending partly covered pixels at the edges
+fn fill_path(canvas: &mut RgbImage, path: &VectorPath, even_odd: bool, color: Rgb<u8>) {
+    // Edges as (top, bottom, x at top, x per row, winding), ordered by top
+    let mut edges: Vec<(f32, f32, f32, f32, i32)> = path
+        .edges()
+        .filter(|((_, y0), (_, y1))| y0 != y1)
+        .map(|((x0, y0), (x1, y1))| {
+            let slope = (x1 - x0) / (y1 - y0);
+            match y0 < y1 {
+                true => (y0, y1, x0, slope, 1),
+                false => (y1, y0, x1, slope, -1),
+            }
+        })
+        .collect();
+    if edges.is_empty() {
+        return;
+    }
+    edges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+    let top = edges[0].0.floor().max(0.0);
+    let bottom = edges.iter().fold(f32::NEG_INFINITY, |bottom, edge| bottom.max(edge.1)).ceil().min(height);
+    let left = path.edges().fold(f32::INFINITY, |left, ((x, _), _)| left.min(x)).floor().max(0.0);
+    let right = path.edges().fold(f32::NEG_INFINITY, |right, ((x, _), _)| right.max(x)).ceil().min(width);
+    if top >= bottom || left >= right {
+        return;
+    }
+
+    let (left, columns) = (left as usize, (right - left) as usize);
+    let mut coverage = vec![0.0f32; columns];
+    let (mut next, mut active, mut crossings) = (0, Vec::new(), Vec::new());
+    for y in top as u32..bottom as u32 {
+        coverage.fill(0.0);
+        for sample in 0..FILL_SAMPLES {
+            let row = y as f32 + (sample as f32 + 0.5) / FILL_SAMPLES as f32;
+            while next < edges.len() && edges[next].0 <= row {
+                active.push(next);
+                next += 1;
+            }
+            active.retain(|&index| edges[index].1 > row);
+            crossings.clear();
+            crossings.extend(active.iter().map(|&index| {
+                let (top, _, x, slope, winding) = edges[index];
+                (x + (row - top) * slope, winding)
+            }));
+            crossings.sort_by(|a: &(f32, i32), b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = if even_odd { winding % 2 != 0 } else { winding != 0 };
+                if !inside {
+                    continue;
+                }
+                // Add the part of each pixel the span covers
+                let start = (pair[0].0 - left as f32).clamp(0.0, columns as f32);
+                let end = (pair[1].0 - left as f32).clamp(0.0, columns as f32);
+                for (column, share) in coverage.iter_mut().enumerate().take(end.ceil() as usize).skip(start as usize) {
+                    let column = column as f32;
+                    *share += (end.min(column + 1.0) - start.max(column)).max(0.0);
+                }
+            }
+        }
+        for (column, share) in coverage.iter().enumerate() {
+            let alpha = (share / FILL_SAMPLES as f32).min(1.0);
+            if alpha <= 0.0 {
+                continue;
+            }
+            let pixel = canvas.get_pixel_mut((left + column) as u32, y);
+            for (channel, target) in pixel.0.iter_mut().zip(color.0) {
+                *channel = (*channel as f32 + (target as f32 - *channel as f32) * alpha).round() as u8;
+            }
+        }
+    }
+}
+
+fn data_url(png: &[u8]) -> String {
+    format!("data:image/png;base64,{}", STANDARD.encode(png))
+}
+
+/// A color given as 1, 3 or 4 components from 0 to 1, as RGB
+fn color(values: &[f32]) -> Rgb<u8> {
+    let bytes: Vec<u8> = values.iter().map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8).collect();
+    to_rgb(&bytes)
+}
+
+/// One color of a gray, RGB or CMYK color space as RGB
+fn to_rgb(values: &[u8]) -> Rgb<u8> {
+    match *values {
+        [gray] => Rgb([gray; 3]),
+        [r, g, b] => Rgb([r, g, b]),
+        [c, m, y, k] => {
+            let channel = |value: u8| ((255 - value as u16) * (255 - k as u16) / 255) as u8;
+            Rgb([channel(c), channel(m), channel(y)])
+        }
+        _ => Rgb([0, 0, 0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pdf::OutputSecurity;
+    use crate::services::pdf_security::PdfSecurityService;
+    use crate::services::test_support::*;
+
+    fn add_image(doc: &mut Document, page_number: usize, width: u32, height: u32, pixels: Vec<u8>, placement: &str) {
+        let image = Stream::new(
+            Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(width as i64)),
+                ("Height", Object::Integer(height as i64)),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+            ]),
+            pixels,
+        );
+        let image_id = doc.add_object(image);
+        let page_id = doc.page_iter().nth(page_number - 1).unwrap();
+        let content = format!("q {} cm /Im1 Do Q", placement).into_bytes();
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+        let resources = Dictionary::from_iter(vec![(
+            "XObject",
+            Object::Dictionary(Dictionary::from_iter(vec![("Im1", Object::Reference(image_id))])),
+        )]);
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.set("Contents", Object::Reference(content_id));
+        page.set("Resources", Object::Dictionary(resources));
+    }
+
+    #[test]
+    fn test_render_page_places_images() {
+        let mut doc = build_test_document(1);
+        // A red image filling the lower half of the Letter page
+        add_image(&mut doc, 1, 2, 2, [255, 0, 0].repeat(4), "612 0 0 396 0 0");
+        let page_id = doc.page_iter().next().unwrap();
+
+        let image = PdfRendererService::render_page(&doc, page_id, 200);
+        assert_eq!(image.dimensions(), (155, 200));
+        assert_eq!(*image.get_pixel(77, 50), Rgb([255, 255, 255]));
+        assert_eq!(*image.get_pixel(77, 150), Rgb([255, 0, 0]));
+
+        doc.get_dictionary_mut(page_id).unwrap().set("Rotate", Object::Integer(90));
+        let rotated = PdfRendererService::render_page(&doc, page_id, 200);
+        assert_eq!(rotated.dimensions(), (200, 155));
+        // The bottom of the page is on the left once turned clockwise
+        assert_eq!(*rotated.get_pixel(50, 77), Rgb([255, 0, 0]));
+        assert_eq!(*rotated.get_pixel(150, 77), Rgb([255, 255, 255]));
+    }
+
+    fn set_page_content(doc: &mut Document, content: &[u8], resources: Dictionary) -> ObjectId {
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content.to_vec()));
+        let page_id = doc.page_iter().next().unwrap();
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.set("Contents", Object::Reference(content_id));
+        page.set("Resources", Object::Dictionary(resources));
+        page_id
+    }
+
+    #[test]
+    fn test_render_page_paints_paths() {
+        let mut doc = build_test_document(1);
+        // A red square, a blue even-odd ring, a thick green line, and a
+        // pattern fill that is left out
+        let content = b"1 0 0 rg 100 100 100 100 re f \
+            0 0 1 rg 300 100 m 500 100 l 500 300 l 300 300 l h 350 150 100 100 re f* \
+            0 1 0 RG 10 w 100 600 m 500 600 l S \
+            /Pattern cs /P0 scn 0 0 612 792 re f";
+        let page_id = set_page_content(&mut doc, content, Dictionary::new());
+
+        let image = PdfRendererService::render_page(&doc, page_id, 792);
+        assert_eq!(image.dimensions(), (612, 792));
+        assert_eq!(*image.get_pixel(150, 792 - 150), Rgb([255, 0, 0]));
+        assert_eq!(*image.get_pixel(320, 792 - 120), Rgb([0, 0, 255]));
+        assert_eq!(*image.get_pixel(400, 792 - 200), Rgb([255, 255, 255]));
+        assert_eq!(*image.get_pixel(300, 792 - 603), Rgb([0, 255, 0]));
+        assert_eq!(*image.get_pixel(300, 792 - 610), Rgb([255, 255, 255]));
+        assert_eq!(*image.get_pixel(50, 50), Rgb([255, 255, 255]));
+
+        let marks = PdfRendererService::page_marks(&doc, page_id);
+        assert_eq!(marks.len(), 3);
+        let PageMark::Path(line) = &marks[2] else {
+            panic!("expected a path");
+        };
+        assert_eq!((line.fill, line.stroke), (None, Some((Rgb([0, 255, 0]), 10.0))));
+    }
+
+    #[test]
+    fn test_render_page_draws_embedded_glyphs() {
+        let mut doc = build_test_document(1);
+        let program = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/PdfcraftTest.ttf")).unwrap();
+        let program_id = doc.add_object(Stream::new(Dictionary::new(), program));
+        let descriptor_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"FontDescriptor".to_vec())),
+            ("FontFile2", Object::Reference(program_id)),
+        ]));
+        let font = |descriptor: Option<ObjectId>| {
+            let mut font = Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Font".to_vec())),
+                ("Subtype", Object::Name(b"TrueType".to_vec())),
+                ("BaseFont", Object::Name(b"PdfcraftTest-Regular".to_vec())),
+                ("FirstChar", Object::Integer(72)),
+                ("Widths", Object::Array(vec![550.into()])),
+                ("Encoding", Object::Name(b"WinAnsiEncoding".to_vec())),
+            ]);
+            if let Some(descriptor) = descriptor {
+                font.set("FontDescriptor", Object::Reference(descriptor));
+            }
+            Object::Dictionary(font)
+        };
+        let resources = Dictionary::from_iter(vec![(
+            "Font",
+            Object::Dictionary(Dictionary::from_iter(vec![("F1", font(Some(descriptor_id))), ("F2", font(None))])),
+        )]);
+        // The box of H spans 50 to 500 units across and 700 up
+        let content = b"BT 1 0 0 rg /F1 100 Tf 100 100 Td (H) Tj /F2 100 Tf 100 500 Td (H) Tj ET";
+        let page_id = set_page_content(&mut doc, content, resources);
+
+        let image = PdfRendererService::render_page(&doc, page_id, 792);
+        assert_eq!(*image.get_pixel(110, 792 - 165), Rgb([255, 0, 0]));
+        assert_eq!(*image.get_pixel(102, 792 - 150), Rgb([255, 255, 255]));
+        assert_eq!(*image.get_pixel(110, 792 - 175), Rgb([255, 255, 255]));
+        // Without the program the run is a gray bar
+        assert_eq!(*image.get_pixel(250, 792 - 620), TEXT_COLOR);
+    }
+
+    #[test]
+    fn test_page_marks_follow_text_state() {
+        let mut doc = build_test_document(1);
+        let font_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+            ("FirstChar", Object::Integer(65)),
+            ("Widths", Object::Array(vec![600.into(), 400.into()])),
+        ]));
+        let content = b"BT /F1 10 Tf 72 700 Td (AB) Tj 0 -12 Td 3 Tr (A) Tj 0 Tr [(B) -1000 (A)] TJ ET".to_vec();
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+        let page_id = doc.page_iter().next().unwrap();
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.set("Contents", Object::Reference(content_id));
+        page.set(
+            "Resources",
+            Dictionary::from_iter(vec![(
+                "Font",
+                Object::Dictionary(Dictionary::from_iter(vec![("F1", Object::Reference(font_id))])),
+            )]),
+        );
+
+        let marks = PdfRendererService::page_marks(&doc, page_id);
+        let runs: Vec<(f32, f32, f32, &str, i64)> = marks
+            .iter()
+            .map(|mark| match mark {
+                PageMark::Text(mark) => (mark.matrix[4], mark.matrix[5], mark.width, mark.text.as_str(), mark.render_mode),
+                _ => panic!("unexpected mark"),
+            })
+            .collect();
+        // TJ adjustments move the pen by thousandths of the font size
+        assert_eq!(
+            runs,
+            vec![
+                (72.0, 700.0, 10.0, "AB", 0),
+                (72.0, 688.0, 6.0, "A", 3),
+                (78.0, 688.0, 4.0, "B", 0),
+                (92.0, 688.0, 6.0, "A", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_indexed_and_inverted_images() {
+        let doc = Document::with_version("1.5");
+        let indexed = Stream::new(
+            Dictionary::from_iter(vec![
+                ("Width", Object::Integer(4)),
+                ("Height", Object::Integer(1)),
+                ("BitsPerComponent", Object::Integer(2)),
+                (
+                    "ColorSpace",
+                    Object::Array(vec![
+                        Object::Name(b"Indexed".to_vec()),
+                        Object::Name(b"DeviceRGB".to_vec()),
+                        Object::Integer(3),
+                        Object::String(vec![0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255], lopdf::StringFormat::Hexadecimal),
+                    ]),
+                ),
+            ]),
+            vec![0b0001_1011],
+        );
+        let image = PdfRendererService::decode_image(&doc, &indexed).unwrap().to_rgb8();
+        let pixels: Vec<[u8; 3]> = image.pixels().map(|pixel| pixel.0).collect();
+        assert_eq!(pixels, vec![[0, 0, 0], [255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+
+        let scan = Stream::new(
+            Dictionary::from_iter(vec![
+                ("Width", Object::Integer(8)),
+                ("Height", Object::Integer(1)),
+                ("BitsPerComponent", Object::Integer(1)),
+                ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+                ("Decode", Object::Array(vec![1.into(), 0.into()])),
+            ]),
+            vec![0b1000_0001],
+        );
+        let image = PdfRendererService::decode_image(&doc, &scan).unwrap().to_luma8();
+        assert_eq!(image.as_raw(), &vec![0, 255, 255, 255, 255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn test_first_page_thumbnail_is_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(1);
+        let path = save_test_document(&mut doc, dir.path(), "input.pdf");
+
+        let thumbnail = PdfRendererService::first_page_thumbnail(&doc, &path, 64).unwrap();
+        assert!(thumbnail.starts_with("data:image/png;base64,"));
+        // A different document under the same unchanged file comes from the cache
+        let other = build_test_document(0);
+        assert_eq!(PdfRendererService::first_page_thumbnail(&other, &path, 64).unwrap(), thumbnail);
+        assert!(PdfRendererService::first_page_thumbnail(&other, &path, 32).is_err());
+    }
+
+    #[test]
+    fn test_page_thumbnails_uses_disk_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(3);
+        let path = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let cache_dir = dir.path().join("cache");
+        let request = PageThumbnailRequest {
+            task_id: "task".to_string(),
+            path,
+            password: None,
+            pages: Some("3,1".to_string()),
+            size: 48,
+        };
+        let running = AtomicBool::new(false);
+
+        let mut pages = Vec::new();
+        let result = PdfRendererService::page_thumbnails(&request, &cache_dir, &running, |thumbnail| {
+            assert_eq!(thumbnail.task_id, "task");
+            assert!(thumbnail.thumbnail.starts_with("data:image/png;base64,"));
+            pages.push(thumbnail.page);
+        })
+        .unwrap();
+        assert_eq!(pages, vec![3, 1]);
+        assert_eq!((result.rendered, result.cached), (2, 0));
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 2);
+
+        let result = PdfRendererService::page_thumbnails(&request, &cache_dir, &running, |_| {}).unwrap();
+        assert_eq!((result.rendered, result.cached), (0, 2));
+
+        let cancelled = AtomicBool::new(true);
+        let result = PdfRendererService::page_thumbnails(&request, &cache_dir, &cancelled, |_| panic!("cancelled"));
+        assert!(matches!(result, Err(AppError::Cancelled)));
+
+        PdfRendererService::prune_disk_cache(&cache_dir, 0);
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_page_thumbnails_of_protected_files_skip_disk_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(2);
+        let security = OutputSecurity { user_password: "secret".to_string(), ..Default::default() };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let path = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let cache_dir = dir.path().join("cache");
+        let request = PageThumbnailRequest {
+            task_id: "task".to_string(),
+            path,
+            password: Some("secret".to_string()),
+            pages: None,
+            size: 100_000,
+        };
+        let running = AtomicBool::new(false);
+
+        let mut thumbnails = Vec::new();
+        for _ in 0..2 {
+            let result = PdfRendererService::page_thumbnails(&request, &cache_dir, &running, |thumbnail| {
+                thumbnails.push(thumbnail.thumbnail);
+            })
+            .unwrap();
+            assert_eq!((result.rendered, result.cached), (2, 0));
+        }
+        assert!(!cache_dir.exists());
+
+        // The requested size is clamped to the largest thumbnail
+        let png = STANDARD.decode(thumbnails[0].trim_start_matches("data:image/png;base64,")).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        assert_eq!(image.width().max(image.height()), MAX_THUMBNAIL_SIZE);
+    }
+}
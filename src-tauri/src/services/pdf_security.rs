@@ -0,0 +1,954 @@
+use std::path::Path;
+use aes::cipher::block_padding::{NoPadding, Pkcs7};
+use aes::cipher::{BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit};
+use lopdf::{Dictionary, Document, Object, ObjectId, ObjectStream};
+use md5::{Digest, Md5};
+use sha2::{Sha256, Sha384, Sha512};
+use crate::models::pdf::{OutputSecurity, PdfPermissions};
+use crate::utils::error::AppError;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// Padding string of the standard security handler (ISO 32000-1, 7.6.3.3)
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Object streams are renamed to this type while loading so lopdf keeps them
+/// as plain streams; they can only be parsed once they are decrypted
+const DEFERRED_OBJECT_STREAM: &[u8] = b"PdfcraftDeferredObjStm";
+
+pub struct PdfSecurityService;
+
+impl PdfSecurityService {
+    /// Load a PDF, decrypting it first when it is protected by the standard
+    /// security handler
+    pub fn load(path: &str, password: Option<&str>) -> Result<Document, AppError> {
+        Self::load_with_permissions(path, password).map(|(doc, _)| doc)
+    }
+
+    /// Load a PDF like [`Self::load`], also returning the permissions of an
+    /// encrypted document
+    pub fn load_with_permissions(
+        path: &str,
+        password: Option<&str>,
+    ) -> Result<(Document, Option<PdfPermissions>), AppError> {
+        Self::load_with_cipher(path, password)
+            .map(|(doc, cipher)| (doc, cipher.map(|cipher| cipher.permissions)))
+    }
+
+    /// Load a PDF like [`Self::load`], also returning the keys of an
+    /// encrypted document so objects added to it can be encrypted the same way
+    pub fn load_with_cipher(
+        path: &str,
+        password: Option<&str>,
+    ) -> Result<(Document, Option<DocumentCipher>), AppError> {
+        if !Path::new(path).exists() {
+            return Err(AppError::FileNotFound(path.to_string()));
+        }
+        let mut doc = Document::load_filtered(path, Self::defer_object_streams)
+            .map_err(|e| AppError::PdfError(format!("Failed to load {}: {}", path, e)))?;
+
+        let cipher = match Self::encryption_dictionary(&doc) {
+            Some(encrypt) => {
+                let handler = SecurityHandler::new(&doc, &encrypt, password.unwrap_or("").as_bytes())
+                    .map_err(|e| match e {
+                        AppError::EncryptedDocument(_) if password.is_none() => {
+                            AppError::EncryptedDocument(format!("{} requires a password", path))
+                        }
+                        AppError::EncryptedDocument(_) => {
+                            AppError::EncryptedDocument(format!("Incorrect password for {}", path))
+                        }
+                        other => other,
+                    })?;
+                let encrypt_entry = doc.trailer.get(b"Encrypt")?.clone();
+                Self::decrypt_document(&mut doc, &handler);
+                Some(DocumentCipher {
+                    handler,
+                    encrypt: encrypt_entry,
+                    permissions: Self::permissions(&encrypt),
+                    dictionary: encrypt,
+                    id: doc.trailer.get(b"ID").ok().cloned(),
+                })
+            }
+            None => None,
+        };
+
+        Self::expand_object_streams(&mut doc);
+        Ok((doc, cipher))
+    }
+
+    /// Permission flags (`/P`) of an encryption dictionary
+    pub fn permissions(encrypt: &Dictionary) -> PdfPermissions {
+        let p = encrypt.get(b"P").and_then(Object::as_i64).unwrap_or(-1) as i32;
+        let revision = encrypt.get(b"R").and_then(Object::as_i64).unwrap_or(2);
+        let bit = |n: u32| p & (1 << (n - 1)) != 0;
+        // Revision 2 only defines bits 3-6; the later bits follow them
+        let extended = |n: u32, fallback: u32| if revision >= 3 { bit(n) } else { bit(fallback) };
+        PdfPermissions {
+            print: bit(3),
+            modify: bit(4),
+            copy: bit(5),
+            annotate: bit(6),
+            fill_forms: extended(9, 6),
+            extract_for_accessibility: extended(10, 5),
+            assemble: extended(11, 4),
+            print_high_quality: extended(12, 3),
+        }
+    }
+
+    /// Encrypt every string and stream of `doc` with the AES-256 standard
+    /// security handler (revision 6). Call this last, after compression.
+    pub fn encrypt(doc: &mut Document, security: &OutputSecurity) -> Result<(), AppError> {
+        let file_key = random_bytes::<32>()?;
+        let user_password = security.user_password.as_bytes();
+        // Without an owner password anyone could lift the restrictions by
+        // opening the file as owner, so a random one is used instead
+        let owner_password = if security.owner_password.is_empty() {
+            random_bytes::<16>()?.to_vec()
+        } else {
+            security.owner_password.as_bytes().to_vec()
+        };
+        let user_password = &user_password[..user_password.len().min(127)];
+        let owner_password = &owner_password[..owner_password.len().min(127)];
+
+        // Algorithms 8 and 9: 32-byte hash, 8-byte validation salt, 8-byte key salt
+        let user_salts = random_bytes::<16>()?;
+        let user = [&hash_r6(user_password, &user_salts[..8], &[], 6)[..], &user_salts[..]].concat();
+        let user_key = wrap_file_key(&hash_r6(user_password, &user_salts[8..], &[], 6), &file_key);
+        let owner_salts = random_bytes::<16>()?;
+        let owner = [&hash_r6(owner_password, &owner_salts[..8], &user, 6)[..], &owner_salts[..]].concat();
+        let owner_key = wrap_file_key(&hash_r6(owner_password, &owner_salts[8..], &user, 6), &file_key);
+
+        let permissions = Self::permission_flags(security);
+        // Algorithm 10: /Perms lets readers check /P was not tampered with
+        let mut perms = [0u8; 16];
+        perms[..4].copy_from_slice(&permissions.to_le_bytes());
+        perms[4..8].copy_from_slice(&[0xFF; 4]);
+        perms[8..12].copy_from_slice(b"Tadb");
+        perms[12..].copy_from_slice(&random_bytes::<4>()?);
+        aes::Aes256::new(&file_key.into()).encrypt_block((&mut perms).into());
+
+        for (_, object) in doc.objects.iter_mut() {
+            if let Object::Stream(stream) = object {
+                if stream.dict.type_is(b"XRef") {
+                    continue;
+                }
+            }
+            encrypt_object(&file_key, object)?;
+        }
+
+        let hex = |bytes: Vec<u8>| Object::String(bytes, lopdf::StringFormat::Hexadecimal);
+        let crypt_filter = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"CryptFilter".to_vec())),
+            ("CFM", Object::Name(b"AESV3".to_vec())),
+            ("AuthEvent", Object::Name(b"DocOpen".to_vec())),
+            ("Length", Object::Integer(32)),
+        ]);
+        let encrypt = Dictionary::from_iter(vec![
+            ("Filter", Object::Name(b"Standard".to_vec())),
+            ("V", Object::Integer(5)),
+            ("R", Object::Integer(6)),
+            ("Length", Object::Integer(256)),
+            ("CF", Object::Dictionary(Dictionary::from_iter(vec![("StdCF", Object::Dictionary(crypt_filter))]))),
+            ("StmF", Object::Name(b"StdCF".to_vec())),
+            ("StrF", Object::Name(b"StdCF".to_vec())),
+            ("O", hex(owner)),
+            ("U", hex(user)),
+            ("OE", hex(owner_key)),
+            ("UE", hex(user_key)),
+            ("P", Object::Integer(permissions as i64)),
+            ("Perms", hex(perms.to_vec())),
+            ("EncryptMetadata", Object::Boolean(true)),
+        ]);
+        let encrypt_id = doc.add_object(encrypt);
+        doc.trailer.set("Encrypt", Object::Reference(encrypt_id));
+        if !doc.trailer.has(b"ID") {
+            let id = random_bytes::<16>()?.to_vec();
+            doc.trailer.set("ID", Object::Array(vec![hex(id.clone()), hex(id)]));
+        }
+
+        // AES-256 is part of PDF 2.0 and of the Adobe extension level 8 to 1.7
+        if doc.version.as_str() < "1.7" {
+            doc.version = "1.7".to_string();
+        }
+        if doc.version.as_str() < "2.0" {
+            let adbe = Dictionary::from_iter(vec![
+                ("BaseVersion", Object::Name(b"1.7".to_vec())),
+                ("ExtensionLevel", Object::Integer(8)),
+            ]);
+            let extensions = Dictionary::from_iter(vec![("ADBE", Object::Dictionary(adbe))]);
+            doc.catalog_mut()?.set("Extensions", Object::Dictionary(extensions));
+        }
+        Ok(())
+    }
+
+    /// Encrypt every string and stream of `doc` with the keys of the
+    /// document `cipher` was loaded from, so that a copy made from it stays
+    /// protected by the same passwords. Call this last, after compression.
+    pub fn reencrypt(doc: &mut Document, cipher: &DocumentCipher) -> Result<(), AppError> {
+        // The source's encryption dictionary may have come along unencrypted
+        if let Object::Reference(id) = cipher.encrypt {
+            if matches!(doc.objects.get(&id), Some(Object::Dictionary(dict)) if *dict == cipher.dictionary) {
+                doc.objects.remove(&id);
+            }
+        }
+        for (&id, object) in doc.objects.iter_mut() {
+            if let Object::Stream(stream) = object {
+                if stream.dict.type_is(b"XRef") {
+                    continue;
+                }
+            }
+            cipher.encrypt_object(id, object)?;
+        }
+        let encrypt_id = doc.add_object(cipher.dictionary.clone());
+        doc.trailer.set("Encrypt", Object::Reference(encrypt_id));
+        // Older revisions derive the file key from the first file identifier
+        if let Some(id) = &cipher.id {
+            doc.trailer.set("ID", id.clone());
+        }
+        Ok(())
+    }
+
+    /// `/P` value for the allowed operations. Related operations follow the
+    /// four main flags: high quality printing goes with printing, form filling
+    /// with annotating and page assembly with modifying.
+    fn permission_flags(security: &OutputSecurity) -> i32 {
+        // Reserved bits 7-8 and 13-32 must be set
+        let mut flags: u32 = 0xFFFF_F0C0;
+        let mut allow = |allowed: bool, bits: &[u32]| {
+            if allowed {
+                bits.iter().for_each(|bit| flags |= 1 << (bit - 1));
+            }
+        };
+        allow(security.allow_print, &[3, 12]);
+        allow(security.allow_modify, &[4, 11]);
+        allow(security.allow_copy, &[5]);
+        allow(security.allow_annotate, &[6, 9]);
+        // Accessibility tools may always extract content
+        allow(true, &[10]);
+        flags as i32
+    }
+
+    fn encryption_dictionary(doc: &Document) -> Option<Dictionary> {
+        let encrypt = doc.trailer.get(b"Encrypt").ok()?;
+        match doc.dereference(encrypt) {
+            Ok((_, Object::Dictionary(dict))) => Some(dict.clone()),
+            _ => None,
+        }
+    }
+
+    /// Load filter that stops lopdf from parsing (still encrypted) object
+    /// streams. Its return value is only used for objects that come out of
+    /// object streams, which this filter prevents.
+    fn defer_object_streams(id: ObjectId, object: &mut Object) -> Option<(ObjectId, Object)> {
+        if let Object::Stream(stream) = object {
+            if stream.dict.type_is(b"ObjStm") {
+                stream.dict.set("Type", Object::Name(DEFERRED_OBJECT_STREAM.to_vec()));
+            }
+        }
+        Some((id, Object::Null))
+    }
+
+    fn decrypt_document(doc: &mut Document, handler: &SecurityHandler) {
+        let encrypt_id = doc.trailer.get(b"Encrypt").and_then(Object::as_reference).ok();
+        for (&id, object) in doc.objects.iter_mut() {
+            if Some(id) == encrypt_id {
+                continue;
+            }
+            if let Object::Stream(stream) = object {
+                // Cross-reference streams are never encrypted
+                if stream.dict.type_is(b"XRef") {
+                    continue;
+                }
+                if stream.dict.type_is(b"Metadata") && !handler.encrypt_metadata {
+                    continue;
+                }
+            }
+            handler.decrypt_object(id, object);
+        }
+        doc.trailer.remove(b"Encrypt");
+    }
+
+    /// Parse the object streams held back by [`Self::defer_object_streams`]
+    fn expand_object_streams(doc: &mut Document) {
+        let mut expanded = Vec::new();
+        for object in doc.objects.values_mut() {
+            let Object::Stream(stream) = object else {
+                continue;
+            };
+            if !stream.dict.type_is(DEFERRED_OBJECT_STREAM) {
+                continue;
+            }
+            stream.dict.set("Type", Object::Name(b"ObjStm".to_vec()));
+            match ObjectStream::new(stream) {
+                Ok(object_stream) => expanded.extend(object_stream.objects),
+                Err(e) => log::warn!("Skipping unreadable object stream: {}", e),
+            }
+        }
+        // Like lopdf, objects stored directly in the file win over copies in
+        // object streams
+        for (id, object) in expanded {
+            doc.max_id = doc.max_id.max(id.0);
+            doc.objects.entry(id).or_insert(object);
+        }
+    }
+}
+
+/// Keys of an encrypted document that was loaded and decrypted
+pub struct DocumentCipher {
+    handler: SecurityHandler,
+    /// The trailer's `/Encrypt` entry
+    pub encrypt: Object,
+    pub permissions: PdfPermissions,
+    dictionary: Dictionary,
+    /// The trailer's `/ID` entry
+    id: Option<Object>,
+}
+
+impl DocumentCipher {
+    /// Encrypt an object that is about to be written to the document as `id`
+    pub fn encrypt_object(&self, id: ObjectId, object: &mut Object) -> Result<(), AppError> {
+        if let Object::Stream(stream) = object {
+            if stream.dict.type_is(b"Metadata") && !self.handler.encrypt_metadata {
+                return Ok(());
+            }
+        }
+        self.handler.encrypt_object(id, object)
+    }
+}
+
+/// Cipher applied to strings or streams
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CryptMethod {
+    Identity,
+    Rc4,
+    Aes128,
+    Aes256,
+}
+
+/// File key and ciphers of a document protected by the standard security handler
+struct SecurityHandler {
+    key: Vec<u8>,
+    string_method: CryptMethod,
+    stream_method: CryptMethod,
+    encrypt_metadata: bool,
+}
+
+impl SecurityHandler {
+    /// Authenticate `password` as the user or owner password and derive the
+    /// file key
+    fn new(doc: &Document, encrypt: &Dictionary, password: &[u8]) -> Result<Self, AppError> {
+        let filter = encrypt.get(b"Filter").and_then(Object::as_name).unwrap_or(b"Standard");
+        if filter != b"Standard" {
+            return Err(AppError::PdfError(format!(
+                "Unsupported security handler: {}",
+                String::from_utf8_lossy(filter)
+            )));
+        }
+
+        let version = encrypt.get(b"V").and_then(Object::as_i64).unwrap_or(0);
+        let revision = encrypt.get(b"R").and_then(Object::as_i64).unwrap_or(2);
+        let (string_method, stream_method) = match version {
+            1 | 2 => (CryptMethod::Rc4, CryptMethod::Rc4),
+            4 | 5 => (Self::crypt_filter(encrypt, b"StrF"), Self::crypt_filter(encrypt, b"StmF")),
+            _ => {
+                return Err(AppError::PdfError(format!("Unsupported encryption version {}", version)));
+            }
+        };
+        let encrypt_metadata = encrypt.get(b"EncryptMetadata").and_then(Object::as_bool).unwrap_or(true);
+
+        let bytes = |key: &[u8]| encrypt.get(key).and_then(Object::as_str).map(<[u8]>::to_vec).unwrap_or_default();
+        let (owner, user) = (bytes(b"O"), bytes(b"U"));
+
+        let key = if revision >= 5 {
+            Self::aes256_file_key(password, revision, &owner, &user, &bytes(b"OE"), &bytes(b"UE"))
+        } else {
+            let key_len = match version {
+                1 => 5,
+                4 => 16,
+                _ => (encrypt.get(b"Length").and_then(Object::as_i64).unwrap_or(40) / 8).clamp(5, 16) as usize,
+            };
+            let legacy = LegacyParameters {
+                revision,
+                key_len,
+                owner: &owner,
+                user: &user,
+                permissions: encrypt.get(b"P").and_then(Object::as_i64).unwrap_or(-1) as i32,
+                id: Self::file_id(doc),
+                encrypt_metadata,
+            };
+            legacy.file_key(password)
+        };
+
+        let key = key.ok_or_else(|| AppError::EncryptedDocument("incorrect password".to_string()))?;
+        Ok(Self {
+            key,
+            string_method,
+            stream_method,
+            encrypt_metadata,
+        })
+    }
+
+    /// Cipher named by `/StrF` or `/StmF` through the `/CF` dictionary
+    fn crypt_filter(encrypt: &Dictionary, key: &[u8]) -> CryptMethod {
+        let name = encrypt.get(key).and_then(Object::as_name).unwrap_or(b"Identity");
+        if name == b"Identity" {
+            return CryptMethod::Identity;
+        }
+        let method = encrypt
+            .get(b"CF")
+            .and_then(Object::as_dict)
+            .and_then(|filters| filters.get(name))
+            .and_then(Object::as_dict)
+            .and_then(|filter| filter.get(b"CFM"))
+            .and_then(Object::as_name)
+            .unwrap_or(b"None");
+        match method {
+            b"V2" => CryptMethod::Rc4,
+            b"AESV2" => CryptMethod::Aes128,
+            b"AESV3" => CryptMethod::Aes256,
+            _ => CryptMethod::Identity,
+        }
+    }
+
+    fn file_id(doc: &Document) -> Vec<u8> {
+        doc.trailer
+            .get(b"ID")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|ids| ids.first())
+            .and_then(|id| id.as_str().ok())
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default()
+    }
+
+    /// File key of revision 5/6 documents (ISO 32000-2, algorithm 2.A)
+    fn aes256_file_key(
+        password: &[u8],
+        revision: i64,
+        owner: &[u8],
+        user: &[u8],
+        owner_key: &[u8],
+        user_key: &[u8],
+    ) -> Option<Vec<u8>> {
+        if owner.len() < 48 || user.len() < 48 || owner_key.len() < 32 || user_key.len() < 32 {
+            return None;
+        }
+        let password = &password[..password.len().min(127)];
+
+        let (intermediate, encrypted_key) = if hash_r6(password, &user[32..40], &[], revision) == user[..32] {
+            (hash_r6(password, &user[40..48], &[], revision), user_key)
+        } else if hash_r6(password, &owner[32..40], &user[..48], revision) == owner[..32] {
+            (hash_r6(password, &owner[40..48], &user[..48], revision), owner_key)
+        } else {
+            return None;
+        };
+
+        let mut key = encrypted_key[..32].to_vec();
+        Aes256CbcDec::new(&intermediate.into(), &[0u8; 16].into())
+            .decrypt_padded_mut::<NoPadding>(&mut key)
+            .ok()?;
+        Some(key)
+    }
+
+    fn decrypt_object(&self, id: ObjectId, object: &mut Object) {
+        match object {
+            Object::String(bytes, _) => *bytes = self.decrypt_bytes(self.string_method, id, bytes),
+            Object::Array(items) => items.iter_mut().for_each(|item| self.decrypt_object(id, item)),
+            Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, value)| self.decrypt_object(id, value)),
+            Object::Stream(stream) => {
+                stream.dict.iter_mut().for_each(|(_, value)| self.decrypt_object(id, value));
+                let content = self.decrypt_bytes(self.stream_method, id, &stream.content);
+                stream.set_content(content);
+            }
+            _ => {}
+        }
+    }
+
+    fn encrypt_object(&self, id: ObjectId, object: &mut Object) -> Result<(), AppError> {
+        match object {
+            Object::String(bytes, _) => *bytes = self.encrypt_bytes(self.string_method, id, bytes)?,
+            Object::Array(items) => {
+                for item in items {
+                    self.encrypt_object(id, item)?;
+                }
+            }
+            Object::Dictionary(dict) => {
+                for (_, value) in dict.iter_mut() {
+                    self.encrypt_object(id, value)?;
+                }
+            }
+            Object::Stream(stream) => {
+                for (_, value) in stream.dict.iter_mut() {
+                    self.encrypt_object(id, value)?;
+                }
+                let content = self.encrypt_bytes(self.stream_method, id, &stream.content)?;
+                stream.set_content(content);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn encrypt_bytes(&self, method: CryptMethod, id: ObjectId, data: &[u8]) -> Result<Vec<u8>, AppError> {
+        match method {
+            CryptMethod::Identity => Ok(data.to_vec()),
+            CryptMethod::Rc4 => Ok(rc4(&self.object_key(id, false), data)),
+            CryptMethod::Aes128 => aes_cbc_encrypt::<Aes128CbcEnc>(&self.object_key(id, true), data),
+            CryptMethod::Aes256 => aes_cbc_encrypt::<Aes256CbcEnc>(&self.key, data),
+        }
+    }
+
+    fn decrypt_bytes(&self, method: CryptMethod, id: ObjectId, data: &[u8]) -> Vec<u8> {
+        let result = match method {
+            CryptMethod::Identity => return data.to_vec(),
+            CryptMethod::Rc4 => Some(rc4(&self.object_key(id, false), data)),
+            CryptMethod::Aes128 => aes_cbc_decrypt::<Aes128CbcDec>(&self.object_key(id, true), data),
+            CryptMethod::Aes256 => aes_cbc_decrypt::<Aes256CbcDec>(&self.key, data),
+        };
+        result.unwrap_or_else(|| {
+            log::warn!("Failed to decrypt data of object {:?}", id);
+            data.to_vec()
+        })
+    }
+
+    /// Per-object key of RC4 and AES-128 encryption (algorithm 1)
+    fn object_key(&self, (number, generation): ObjectId, aes: bool) -> Vec<u8> {
+        let mut hasher = Md5::new();
+        hasher.update(&self.key);
+        hasher.update(&number.to_le_bytes()[..3]);
+        hasher.update(&generation.to_le_bytes()[..2]);
+        if aes {
+            hasher.update(b"sAlT");
+        }
+        hasher.finalize()[..(self.key.len() + 5).min(16)].to_vec()
+    }
+}
+
+/// Inputs of the MD5-based key derivation used up to revision 4
+struct LegacyParameters<'a> {
+    revision: i64,
+    key_len: usize,
+    owner: &'a [u8],
+    user: &'a [u8],
+    permissions: i32,
+    id: Vec<u8>,
+    encrypt_metadata: bool,
+}
+
+impl LegacyParameters<'_> {
+    /// Try `password` as the user password, then as the owner password
+    fn file_key(&self, password: &[u8]) -> Option<Vec<u8>> {
+        let key = self.user_key(password);
+        if self.is_user_key(&key) {
+            return Some(key);
+        }
+
+        // Algorithm 7: the owner password decrypts /O into the user password
+        let owner_key = self.owner_key(password);
+        let user_password = if self.revision == 2 {
+            rc4(&owner_key, self.owner)
+        } else {
+            (0..=19u8).rev().fold(self.owner.to_vec(), |data, i| rc4(&xor_key(&owner_key, i), &data))
+        };
+        let key = self.user_key(&user_password);
+        self.is_user_key(&key).then_some(key)
+    }
+
+    /// Algorithm 2
+    fn user_key(&self, password: &[u8]) -> Vec<u8> {
+        let mut hasher = Md5::new();
+        hasher.update(pad_password(password));
+        hasher.update(self.owner);
+        hasher.update(self.permissions.to_le_bytes());
+        hasher.update(&self.id);
+        if self.revision >= 4 && !self.encrypt_metadata {
+            hasher.update([0xFF; 4]);
+        }
+        let mut key = hasher.finalize().to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                key = Md5::digest(&key[..self.key_len]).to_vec();
+            }
+        }
+        key.truncate(self.key_len);
+        key
+    }
+
+    /// Key derived from the owner password (algorithm 3, steps a-d)
+    fn owner_key(&self, password: &[u8]) -> Vec<u8> {
+        let mut key = Md5::digest(pad_password(password)).to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                key = Md5::digest(&key).to_vec();
+            }
+        }
+        key.truncate(self.key_len);
+        key
+    }
+
+    /// Algorithms 4 and 5: the key is right when it reproduces `/U`
+    fn is_user_key(&self, key: &[u8]) -> bool {
+        if self.revision == 2 {
+            return rc4(key, &PASSWORD_PADDING) == self.user;
+        }
+        let mut hasher = Md5::new();
+        hasher.update(PASSWORD_PADDING);
+        hasher.update(&self.id);
+        let hash = hasher.finalize().to_vec();
+        let check = (1..=19u8).fold(rc4(key, &hash), |data, i| rc4(&xor_key(key, i), &data));
+        self.user.len() >= 16 && check == self.user[..16]
+    }
+}
+
+fn pad_password(password: &[u8]) -> [u8; 32] {
+    let mut padded = PASSWORD_PADDING;
+    let len = password.len().min(32);
+    padded[..len].copy_from_slice(&password[..len]);
+    padded[len..].copy_from_slice(&PASSWORD_PADDING[..32 - len]);
+    padded
+}
+
+fn xor_key(key: &[u8], value: u8) -> Vec<u8> {
+    key.iter().map(|b| b ^ value).collect()
+}
+
+/// Password hash of revision 5 (SHA-256) and revision 6 (algorithm 2.B)
+fn hash_r6(password: &[u8], salt: &[u8], user: &[u8], revision: i64) -> [u8; 32] {
+    let mut k: Vec<u8> = Sha256::new().chain_update(password).chain_update(salt).chain_update(user).finalize().to_vec();
+    if revision == 5 {
+        return k[..32].try_into().unwrap();
+    }
+
+    let mut round = 0;
+    loop {
+        let block = [password, &k, user].concat();
+        let mut data = block.repeat(64);
+        let key: [u8; 16] = k[..16].try_into().unwrap();
+        let iv: [u8; 16] = k[16..32].try_into().unwrap();
+        let len = data.len();
+        let encrypted = Aes128CbcEnc::new(&key.into(), &iv.into())
+            .encrypt_padded_mut::<NoPadding>(&mut data, len)
+            .expect("input is a multiple of the block size")
+            .to_vec();
+
+        let remainder = encrypted[..16].iter().map(|&b| b as u32).sum::<u32>() % 3;
+        k = match remainder {
+            0 => Sha256::digest(&encrypted).to_vec(),
+            1 => Sha384::digest(&encrypted).to_vec(),
+            _ => Sha512::digest(&encrypted).to_vec(),
+        };
+
+        round += 1;
+        if round >= 64 && (*encrypted.last().unwrap() as u32) <= round - 32 {
+            break;
+        }
+    }
+    k[..32].try_into().unwrap()
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u8> = (0..=255).collect();
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// Encrypt strings and streams of one object with AES-256, as done for
+/// revision 6 documents where every object shares the file key
+fn encrypt_object(key: &[u8; 32], object: &mut Object) -> Result<(), AppError> {
+    match object {
+        Object::String(bytes, _) => *bytes = aes256_cbc_encrypt(key, bytes)?,
+        Object::Array(items) => {
+            for item in items {
+                encrypt_object(key, item)?;
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                encrypt_object(key, value)?;
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                encrypt_object(key, value)?;
+            }
+            let content = aes256_cbc_encrypt(key, &stream.content)?;
+            stream.set_content(content);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// AES-256-CBC with a random IV stored in front of the data
+fn aes256_cbc_encrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, AppError> {
+    aes_cbc_encrypt::<Aes256CbcEnc>(key, data)
+}
+
+/// AES-CBC with a random IV stored in front of the data
+fn aes_cbc_encrypt<C>(key: &[u8], data: &[u8]) -> Result<Vec<u8>, AppError>
+where
+    C: KeyIvInit + BlockEncryptMut,
+{
+    let iv = random_bytes::<16>()?;
+    let mut buffer = [data, &[0u8; 16]].concat();
+    let len = C::new_from_slices(key, &iv)
+        .map_err(|_| AppError::PdfError("Invalid encryption key".to_string()))?
+        .encrypt_padded_mut::<Pkcs7>(&mut buffer, data.len())
+        .map_err(|_| AppError::PdfError("Failed to encrypt data".to_string()))?
+        .len();
+    buffer.truncate(len);
+    Ok([&iv[..], &buffer].concat())
+}
+
+/// Encrypt the file key for `/UE` or `/OE`
+fn wrap_file_key(hash: &[u8; 32], file_key: &[u8; 32]) -> Vec<u8> {
+    let mut key = file_key.to_vec();
+    Aes256CbcEnc::new(hash.into(), &[0u8; 16].into())
+        .encrypt_padded_mut::<NoPadding>(&mut key, 32)
+        .expect("the file key is two blocks long");
+    key
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], AppError> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).map_err(|e| AppError::PdfError(format!("No secure random source: {}", e)))?;
+    Ok(bytes)
+}
+
+/// Decrypt AES-CBC data whose first block is the IV
+fn aes_cbc_decrypt<C>(key: &[u8], data: &[u8]) -> Option<Vec<u8>>
+where
+    C: KeyIvInit + BlockDecryptMut,
+{
+    if data.len() < 32 || data.len() % 16 != 0 {
+        return None;
+    }
+    let (iv, encrypted) = data.split_at(16);
+    let mut buffer = encrypted.to_vec();
+    let len = C::new_from_slices(key, iv).ok()?.decrypt_padded_mut::<Pkcs7>(&mut buffer).ok()?.len();
+    buffer.truncate(len);
+    Some(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encrypt every string and stream of a single-page document the way a
+    /// revision 3 RC4 or revision 4 AES-128 writer would
+    fn encrypt_legacy(path: &Path, user_password: &[u8], owner_password: &[u8], aes: bool) {
+        let mut doc = test_document();
+        let id = b"0123456789abcdef".to_vec();
+        let permissions = -3900i32;
+        let revision = if aes { 4 } else { 3 };
+
+        let mut legacy = LegacyParameters {
+            revision,
+            key_len: 16,
+            owner: &[],
+            user: &[],
+            permissions,
+            id: id.clone(),
+            encrypt_metadata: true,
+        };
+        let owner_key = legacy.owner_key(owner_password);
+        let owner = (0..=19u8).fold(pad_password(user_password).to_vec(), |data, i| rc4(&xor_key(&owner_key, i), &data));
+        legacy.owner = &owner;
+        let key = legacy.user_key(user_password);
+        let hash = Md5::new().chain_update(PASSWORD_PADDING).chain_update(&id).finalize().to_vec();
+        let mut user = (0..=19u8).fold(hash, |data, i| rc4(&xor_key(&key, i), &data));
+        user.resize(32, 0);
+
+        let handler = SecurityHandler {
+            key,
+            string_method: CryptMethod::Identity,
+            stream_method: CryptMethod::Identity,
+            encrypt_metadata: true,
+        };
+        for (&object_id, object) in doc.objects.iter_mut() {
+            encrypt_object(object, &|data: &[u8]| {
+                if aes {
+                    let iv = [7u8; 16];
+                    let mut buffer = [data, &[0u8; 16]].concat();
+                    let len = cbc::Encryptor::<aes::Aes128>::new_from_slices(&handler.object_key(object_id, true), &iv)
+                        .unwrap()
+                        .encrypt_padded_mut::<Pkcs7>(&mut buffer, data.len())
+                        .unwrap()
+                        .len();
+                    [&iv[..], &buffer[..len]].concat()
+                } else {
+                    rc4(&handler.object_key(object_id, false), data)
+                }
+            });
+        }
+
+        let mut encrypt = Dictionary::from_iter(vec![
+            ("Filter", Object::Name(b"Standard".to_vec())),
+            ("V", Object::Integer(if aes { 4 } else { 2 })),
+            ("R", Object::Integer(revision)),
+            ("Length", Object::Integer(128)),
+            ("O", Object::string_literal(owner.clone())),
+            ("U", Object::string_literal(user)),
+            ("P", Object::Integer(permissions as i64)),
+        ]);
+        if aes {
+            let filter = Dictionary::from_iter(vec![("CFM", Object::Name(b"AESV2".to_vec()))]);
+            encrypt.set("CF", Dictionary::from_iter(vec![("StdCF", Object::Dictionary(filter))]));
+            encrypt.set("StmF", Object::Name(b"StdCF".to_vec()));
+            encrypt.set("StrF", Object::Name(b"StdCF".to_vec()));
+        }
+        let encrypt_id = doc.add_object(encrypt);
+        doc.trailer.set("Encrypt", Object::Reference(encrypt_id));
+        doc.trailer.set("ID", Object::Array(vec![Object::string_literal(id.clone()), Object::string_literal(id)]));
+        doc.save(path).unwrap();
+    }
+
+    fn encrypt_object(object: &mut Object, encrypt: &dyn Fn(&[u8]) -> Vec<u8>) {
+        match object {
+            Object::String(bytes, _) => *bytes = encrypt(bytes),
+            Object::Array(items) => items.iter_mut().for_each(|item| encrypt_object(item, encrypt)),
+            Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, value)| encrypt_object(value, encrypt)),
+            Object::Stream(stream) => {
+                let content = encrypt(&stream.content);
+                stream.set_content(content);
+            }
+            _ => {}
+        }
+    }
+
+    fn test_document() -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(lopdf::Stream::new(Dictionary::new(), b"BT (Secret) Tj ET".to_vec()));
+        let page_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(pages_id)),
+            ("MediaBox", Object::Array(vec![0.into(), 0.into(), 612.into(), 792.into()])),
+            ("Contents", Object::Reference(content_id)),
+        ]));
+        doc.objects.insert(pages_id, Object::Dictionary(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Kids", Object::Array(vec![Object::Reference(page_id)])),
+            ("Count", Object::Integer(1)),
+        ])));
+        let catalog_id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+        ]));
+        let info_id = doc.add_object(Dictionary::from_iter(vec![("Title", Object::string_literal("Contract"))]));
+        doc.trailer.set("Root", Object::Reference(catalog_id));
+        doc.trailer.set("Info", Object::Reference(info_id));
+        doc
+    }
+
+    fn page_text(doc: &Document) -> Vec<u8> {
+        let page_id = doc.page_iter().next().unwrap();
+        doc.get_page_content(page_id).unwrap()
+    }
+
+    #[test]
+    fn test_rc4_known_vector() {
+        assert_eq!(rc4(b"Key", b"Plaintext"), [0xBB, 0xF3, 0x16, 0xE8, 0xD9, 0x40, 0xAF, 0x0A, 0xD3]);
+    }
+
+    #[test]
+    fn test_load_rc4_document_with_user_and_owner_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rc4.pdf");
+        encrypt_legacy(&path, b"user", b"owner", false);
+        let path = path.to_string_lossy().to_string();
+
+        for password in ["user", "owner"] {
+            let (doc, permissions) = PdfSecurityService::load_with_permissions(&path, Some(password)).unwrap();
+            assert_eq!(page_text(&doc), b"BT (Secret) Tj ET");
+            let permissions = permissions.unwrap();
+            assert!(permissions.print && !permissions.modify);
+        }
+    }
+
+    #[test]
+    fn test_load_aes128_document_decrypts_strings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("aes.pdf");
+        encrypt_legacy(&path, b"", b"owner", true);
+
+        // An empty user password opens the document without prompting
+        let doc = PdfSecurityService::load(&path.to_string_lossy(), None).unwrap();
+        assert_eq!(page_text(&doc), b"BT (Secret) Tj ET");
+        let info_id = doc.trailer.get(b"Info").and_then(Object::as_reference).unwrap();
+        let title = doc.get_dictionary(info_id).unwrap().get(b"Title").unwrap();
+        assert_eq!(title.as_str().unwrap(), b"Contract");
+    }
+
+    #[test]
+    fn test_load_reports_missing_and_wrong_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked.pdf");
+        encrypt_legacy(&path, b"user", b"owner", false);
+        let path = path.to_string_lossy().to_string();
+
+        assert!(matches!(PdfSecurityService::load(&path, None), Err(AppError::EncryptedDocument(_))));
+        assert!(matches!(PdfSecurityService::load(&path, Some("nope")), Err(AppError::EncryptedDocument(_))));
+    }
+
+    #[test]
+    fn test_reencrypt_keeps_source_passwords() {
+        let dir = tempfile::tempdir().unwrap();
+        for aes in [false, true] {
+            let path = dir.path().join("source.pdf");
+            encrypt_legacy(&path, b"user", b"owner", aes);
+            let (mut doc, cipher) = PdfSecurityService::load_with_cipher(&path.to_string_lossy(), Some("user")).unwrap();
+            let extra = doc.add_object(lopdf::Stream::new(Dictionary::new(), b"Added".to_vec()));
+            PdfSecurityService::reencrypt(&mut doc, &cipher.unwrap()).unwrap();
+            let copy = dir.path().join("copy.pdf");
+            doc.save(&copy).unwrap();
+            let copy = copy.to_string_lossy().to_string();
+
+            assert!(!std::fs::read(&copy).unwrap().windows(6).any(|window| window == b"Secret"));
+            assert!(matches!(PdfSecurityService::load(&copy, None), Err(AppError::EncryptedDocument(_))));
+            for password in ["user", "owner"] {
+                let (doc, permissions) = PdfSecurityService::load_with_permissions(&copy, Some(password)).unwrap();
+                assert_eq!(page_text(&doc), b"BT (Secret) Tj ET");
+                assert_eq!(doc.get_object(extra).unwrap().as_stream().unwrap().content, b"Added");
+                assert!(!permissions.unwrap().modify);
+            }
+        }
+    }
+
+    #[test]
+    fn test_aes256_file_key_from_user_and_owner_password() {
+        let file_key = [9u8; 32];
+        let (user_salts, owner_salts) = ([1u8; 16], [2u8; 16]);
+        let wrap = |hash: [u8; 32]| wrap_file_key(&hash, &file_key);
+
+        let user = [hash_r6(b"user", &user_salts[..8], &[], 6).to_vec(), user_salts.to_vec()].concat();
+        let user_key = wrap(hash_r6(b"user", &user_salts[8..], &[], 6));
+        let owner = [hash_r6(b"owner", &owner_salts[..8], &user, 6).to_vec(), owner_salts.to_vec()].concat();
+        let owner_key = wrap(hash_r6(b"owner", &owner_salts[8..], &user, 6));
+
+        for password in [&b"user"[..], b"owner"] {
+            let key = SecurityHandler::aes256_file_key(password, 6, &owner, &user, &owner_key, &user_key);
+            assert_eq!(key.unwrap(), file_key);
+        }
+        assert!(SecurityHandler::aes256_file_key(b"guess", 6, &owner, &user, &owner_key, &user_key).is_none());
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use crate::models::pdf::{SplitConfig, SplitMode, SplitPart};
+use crate::services::pdf_merger::PdfMergerService;
+use crate::services::pdf_security::{DocumentCipher, PdfSecurityService};
+use crate::utils::error::AppError;
+use crate::utils::page_range::parse_page_range;
+
+/// Estimated serialized size of an object apart from its stream data
+const OBJECT_OVERHEAD: u64 = 64;
+
+pub struct PdfSplitterService;
+
+impl PdfSplitterService {
+    /// Split one PDF into several files in `config.output_dir`
+    pub fn split(config: &SplitConfig) -> Result<Vec<SplitPart>, AppError> {
+        let (mut doc, cipher) = PdfSecurityService::load_with_cipher(&config.input_path, config.password.as_deref())?;
+        let cipher = cipher.as_ref();
+        // Every part gets its own Pages tree, so inherited attributes have to
+        // live on the pages
+        PdfMergerService::resolve_inherited_attributes(&mut doc);
+
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        if page_ids.is_empty() {
+            return Err(AppError::PdfError("Document has no pages".to_string()));
+        }
+
+        let plan = match config.mode {
+            SplitMode::EveryNPages => Self::plan_every_n_pages(page_ids.len(), config.pages_per_part)?,
+            SplitMode::Ranges => Self::plan_ranges(&config.ranges, page_ids.len())?,
+            SplitMode::Bookmarks => Self::plan_bookmarks(&doc, &page_ids)?,
+            SplitMode::MaxSize => Self::plan_max_size(&doc, &page_ids, config.max_file_size, cipher)?,
+        };
+
+        fs::create_dir_all(&config.output_dir)?;
+        let name = Path::new(&config.input_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let width = plan.len().to_string().len();
+        let mut used_names = HashSet::new();
+        let paths: Vec<PathBuf> = plan
+            .iter()
+            .enumerate()
+            .map(|(index, part)| {
+                let file_name = Self::part_file_name(&config.name_template, &name, index, width, part, &mut used_names);
+                Path::new(&config.output_dir).join(file_name)
+            })
+            .collect();
+        // Check every name before writing anything, so a bad template
+        // leaves the output directory as it was
+        let input = fs::canonicalize(&config.input_path)?;
+        for path in &paths {
+            if fs::canonicalize(path).is_ok_and(|path| path == input) {
+                return Err(AppError::ConfigError(format!(
+                    "Part {} would overwrite the input file",
+                    path.display()
+                )));
+            }
+            if path.exists() && !config.overwrite {
+                return Err(AppError::ConfigError(format!("{} already exists", path.display())));
+            }
+        }
+
+        let mut parts = Vec::new();
+        for (part, path) in plan.into_iter().zip(paths) {
+            let ids: Vec<ObjectId> = part.pages.iter().map(|&page| page_ids[page - 1]).collect();
+            let bytes = Self::write_part(&doc, &ids, cipher)?;
+            fs::write(&path, &bytes)?;
+            parts.push(SplitPart {
+                path: path.to_string_lossy().to_string(),
+                pages: part.pages,
+                title: part.title,
+                file_size: bytes.len() as u64,
+            });
+        }
+
+        log::info!("Split {} into {} parts", config.input_path, parts.len());
+        Ok(parts)
+    }
+
+    fn plan_every_n_pages(page_count: usize, pages_per_part: usize) -> Result<Vec<PlannedPart>, AppError> {
+        if pages_per_part == 0 {
+            return Err(AppError::ConfigError("Pages per part must be at least 1".to_string()));
+        }
+        let pages: Vec<usize> = (1..=page_count).collect();
+        Ok(pages
+            .chunks(pages_per_part)
+            .map(|chunk| PlannedPart { pages: chunk.to_vec(), title: None })
+            .collect())
+    }
+
+    fn plan_ranges(ranges: &[String], page_count: usize) -> Result<Vec<PlannedPart>, AppError> {
+        if ranges.is_empty() {
+            return Err(AppError::ConfigError("No page ranges to split by".to_string()));
+        }
+        ranges
+            .iter()
+            .map(|range| {
+                // A part holds each page once, in the order first listed
+                let mut pages = parse_page_range(range, page_count)?;
+                let mut seen = HashSet::new();
+                pages.retain(|&page| seen.insert(page));
+                Ok(PlannedPart { pages, title: None })
+            })
+            .collect()
+    }
+
+    /// One part per top-level bookmark, running up to the next one. Pages
+    /// before the first bookmark form a part of their own.
+    fn plan_bookmarks(doc: &Document, page_ids: &[ObjectId]) -> Result<Vec<PlannedPart>, AppError> {
+        let page_index: HashMap<ObjectId, usize> = page_ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let mut starts: Vec<(usize, Option<String>)> = Self::top_level_bookmarks(doc)
+            .into_iter()
+            .filter_map(|(title, page)| Some((*page_index.get(&page)?, Some(title))))
+            .collect();
+        if starts.is_empty() {
+            return Err(AppError::ConfigError("The document has no top-level bookmarks to split at".to_string()));
+        }
+        starts.sort_by_key(|(start, _)| *start);
+        starts.dedup_by_key(|(start, _)| *start);
+        if starts[0].0 > 0 {
+            starts.insert(0, (0, None));
+        }
+
+        let ends: Vec<usize> = starts.iter().skip(1).map(|(start, _)| *start).chain([page_ids.len()]).collect();
+        Ok(starts
+            .into_iter()
+            .zip(ends)
+            .map(|((start, title), end)| PlannedPart { pages: (start + 1..=end).collect(), title })
+            .collect())
+    }
+
+    /// Title and target page of each top-level outline item
+    fn top_level_bookmarks(doc: &Document) -> Vec<(String, ObjectId)> {
+        let mut current = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(|outlines| doc.dereference(outlines))
+            .and_then(|(_, outlines)| outlines.as_dict())
+            .and_then(|outlines| outlines.get(b"First"))
+            .and_then(Object::as_reference)
+            .ok();
+
+        let mut bookmarks = Vec::new();
+        let mut visited = HashSet::new();
+        while let Some(item_id) = current {
+            if !visited.insert(item_id) {
+                break;
+            }
+            let Ok(item) = doc.get_dictionary(item_id) else {
+                break;
+            };
+            current = item.get(b"Next").and_then(Object::as_reference).ok();
+
+            let title = item
+                .get(b"Title")
+                .and_then(|title| doc.dereference(title))
+                .and_then(|(_, title)| lopdf::decode_text_string(title))
+                .unwrap_or_default();
+            let target = item.get(b"Dest").ok().or_else(|| match item.get(b"A").and_then(|a| doc.dereference(a)) {
+                Ok((_, Object::Dictionary(action))) => action.get(b"D").ok(),
+                _ => None,
+            });
+            let page = target
+                .and_then(|target| PdfMergerService::resolve_dest(doc, target))
+                .and_then(|dest| dest.first()?.as_reference().ok());
+            if let Some(page) = page {
+                bookmarks.push((title.trim().to_string(), page));
+            }
+        }
+        bookmarks
+    }
+
+    /// Fill each part with pages while the estimated size stays under
+    /// `max_file_size`, then check the real size and give pages back until
+    /// it fits. A single page larger than the limit becomes its own part.
+    fn plan_max_size(
+        doc: &Document,
+        page_ids: &[ObjectId],
+        max_file_size: u64,
+        cipher: Option<&DocumentCipher>,
+    ) -> Result<Vec<PlannedPart>, AppError> {
+        if max_file_size == 0 {
+            return Err(AppError::ConfigError("Maximum part size must be greater than zero".to_string()));
+        }
+        let page_objects: Vec<BTreeSet<ObjectId>> = page_ids
+            .iter()
+            .map(|&id| PdfMergerService::collect_reachable(doc, &[id]))
+            .collect();
+
+        let mut plan = Vec::new();
+        let mut start = 0;
+        while start < page_ids.len() {
+            // Objects shared between pages (fonts, images) only count once
+            let mut counted = HashSet::new();
+            let mut estimate = 0;
+            let mut end = start;
+            while end < page_ids.len() {
+                let added: u64 = page_objects[end]
+                    .iter()
+                    .filter(|id| !counted.contains(*id))
+                    .map(|&id| Self::estimated_size(doc, id))
+                    .sum();
+                if end > start && estimate + added > max_file_size {
+                    break;
+                }
+                estimate += added;
+                counted.extend(page_objects[end].iter().copied());
+                end += 1;
+            }
+
+            loop {
+                let size = Self::write_part(doc, &page_ids[start..end], cipher)?.len() as u64;
+                if size <= max_file_size {
+                    break;
+                }
+                if end - start == 1 {
+                    log::warn!("Page {} alone exceeds the maximum part size ({} bytes)", start + 1, size);
+                    break;
+                }
+                let fitting = ((end - start) as u64 * max_file_size / size) as usize;
+                end = start + fitting.clamp(1, end - start - 1);
+            }
+
+            plan.push(PlannedPart { pages: (start + 1..=end).collect(), title: None });
+            start = end;
+        }
+        Ok(plan)
+    }
+
+    fn estimated_size(doc: &Document, id: ObjectId) -> u64 {
+        match doc.get_object(id) {
+            Ok(Object::Stream(stream)) => stream.content.len() as u64 + stream.dict.len() as u64 * 16 + OBJECT_OVERHEAD,
+            Ok(Object::Dictionary(dict)) => dict.len() as u64 * 16 + OBJECT_OVERHEAD,
+            _ => OBJECT_OVERHEAD,
+        }
+    }
+
+    /// Build a document from `pages` of `doc`, copying only the objects they
+    /// use, and return it serialized, encrypted like the source when
+    /// `cipher` is given
+    fn write_part(doc: &Document, pages: &[ObjectId], cipher: Option<&DocumentCipher>) -> Result<Vec<u8>, AppError> {
+        let mut part = Document::with_version(&doc.version);
+        let reachable = PdfMergerService::collect_reachable(doc, pages);
+        let mut id_map: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+        PdfMergerService::copy_objects(doc, &reachable, &mut id_map, &mut part);
+        let page_map: BTreeMap<ObjectId, ObjectId> = pages
+            .iter()
+            .filter_map(|id| Some((*id, *id_map.get(id)?)))
+            .collect();
+
+        let pages_id = part.new_object_id();
+        let kids: Vec<Object> = pages
+            .iter()
+            .filter_map(|id| page_map.get(id))
+            .map(|&id| Object::Reference(id))
+            .collect();
+        for &page_id in page_map.values() {
+            if let Ok(page) = part.get_dictionary_mut(page_id) {
+                page.set("Parent", Object::Reference(pages_id));
+            }
+        }
+        part.objects.insert(pages_id, Object::Dictionary(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Count", Object::Integer(kids.len() as i64)),
+            ("Kids", Object::Array(kids)),
+        ])));
+
+        let mut catalog = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", Object::Reference(pages_id)),
+        ]);
+        let outlines = PdfMergerService::collect_outlines(doc, &id_map, &page_map);
+        if !outlines.is_empty() {
+            catalog.set("Outlines", Object::Reference(PdfMergerService::write_outlines(&mut part, outlines)));
+        }
+        let catalog_id = part.add_object(catalog);
+        part.trailer.set("Root", Object::Reference(catalog_id));
+
+        // Keep the document title, author and so on
+        if let Ok((_, info @ Object::Dictionary(_))) = doc.trailer.get(b"Info").and_then(|info| doc.dereference(info)) {
+            let info_id = part.add_object(PdfMergerService::remap_object(info, &id_map));
+            part.trailer.set("Info", Object::Reference(info_id));
+        }
+
+        part.compress();
+        if let Some(cipher) = cipher {
+            PdfSecurityService::reencrypt(&mut part, cipher)?;
+        }
+        let mut bytes = Vec::new();
+        part.save_to(&mut bytes)
+            .map_err(|e| AppError::PdfError(format!("Failed to write split part: {}", e)))?;
+        Ok(bytes)
+    }
+
+    /// Expand the name template for one part into a unique, safe file name
+    fn part_file_name(
+        template: &str,
+        name: &str,
+        index: usize,
+        width: usize,
+        part: &PlannedPart,
+        used_names: &mut HashSet<String>,
+    ) -> String {
+        let number = |page: Option<&usize>| page.map(usize::to_string).unwrap_or_default();
+        let title = part.title.clone().unwrap_or_else(|| format!("part{}", index + 1));
+        let stem = template
+            .replace("{name}", name)
+            .replace("{index}", &format!("{:0width$}", index + 1, width = width))
+            .replace("{title}", &title)
+            .replace("{start}", &number(part.pages.first()))
+            .replace("{end}", &number(part.pages.last()));
+
+        let stem: String = stem
+            .chars()
+            .map(|c| if c.is_control() || r#"/\:*?"<>|"#.contains(c) { '_' } else { c })
+            .collect();
+        let stem = match stem.trim() {
+            "" => format!("part{}", index + 1),
+            stem => stem.to_string(),
+        };
+
+        let mut file_name = format!("{}.pdf", stem);
+        let mut n = 2;
+        while !used_names.insert(file_name.to_lowercase()) {
+            file_name = format!("{}_{}.pdf", stem, n);
+            n += 1;
+        }
+        file_name
+    }
+}
+
+/// Pages (1-based) and bookmark title of one part before it is written
+struct PlannedPart {
+    pages: Vec<usize>,
+    title: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pdf::OutputSecurity;
+    use crate::services::test_support::*;
+
+    fn split_config(input: &str, output_dir: &Path, mode: SplitMode) -> SplitConfig {
+        SplitConfig {
+            input_path: input.to_string(),
+            password: None,
+            output_dir: output_dir.to_string_lossy().to_string(),
+            mode,
+            pages_per_part: 0,
+            ranges: Vec::new(),
+            max_file_size: 0,
+            name_template: "{name}_{index}".to_string(),
+            overwrite: false,
+        }
+    }
+
+    fn part_markers(parts: &[SplitPart]) -> Vec<Vec<i64>> {
+        parts.iter().map(|part| page_markers(&part.path)).collect()
+    }
+
+    #[test]
+    fn test_split_every_n_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = save_test_document(&mut build_test_document(5), dir.path(), "book.pdf");
+        let mut config = split_config(&input, &dir.path().join("out"), SplitMode::EveryNPages);
+        config.pages_per_part = 2;
+
+        let parts = PdfSplitterService::split(&config).unwrap();
+        assert_eq!(part_markers(&parts), vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert!(parts[2].path.ends_with("book_3.pdf"));
+
+        config.pages_per_part = 0;
+        assert!(PdfSplitterService::split(&config).is_err());
+    }
+
+    #[test]
+    fn test_split_by_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = save_test_document(&mut build_test_document(5), dir.path(), "book.pdf");
+        let mut config = split_config(&input, dir.path(), SplitMode::Ranges);
+        config.ranges = vec!["3-1".to_string(), "2,2,5".to_string()];
+        config.name_template = "{name} p{start}-{end}".to_string();
+
+        let parts = PdfSplitterService::split(&config).unwrap();
+        assert_eq!(part_markers(&parts), vec![vec![2, 1, 0], vec![1, 4]]);
+        assert!(parts[1].path.ends_with("book p2-5.pdf"));
+    }
+
+    #[test]
+    fn test_split_by_bookmarks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(6);
+        add_test_outline(&mut doc, &[("Intro", 2), ("Part: One/Two", 4)]);
+        let input = save_test_document(&mut doc, dir.path(), "book.pdf");
+        let mut config = split_config(&input, dir.path(), SplitMode::Bookmarks);
+        config.name_template = "{index} {title}".to_string();
+
+        let parts = PdfSplitterService::split(&config).unwrap();
+        assert_eq!(part_markers(&parts), vec![vec![0], vec![1, 2], vec![3, 4, 5]]);
+        assert_eq!(parts[1].title.as_deref(), Some("Intro"));
+        assert!(parts[0].path.ends_with("1 part1.pdf"));
+        assert!(parts[2].path.ends_with("3 Part_ One_Two.pdf"));
+
+        // Each part keeps the bookmarks that point into it
+        let part = Document::load(&parts[1].path).unwrap();
+        let outlines_id = part.catalog().unwrap().get(b"Outlines").and_then(Object::as_reference).unwrap();
+        assert_eq!(outline_titles(&part, part.get_dictionary(outlines_id).unwrap()), vec!["Intro"]);
+    }
+
+    #[test]
+    fn test_split_by_max_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(6);
+        // Give every page an incompressible 20 KB thumbnail
+        let mut seed: u32 = 1;
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        for page_id in page_ids {
+            let noise: Vec<u8> = (0..20_000)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    (seed >> 16) as u8
+                })
+                .collect();
+            let thumb_id = doc.add_object(lopdf::Stream::new(Dictionary::new(), noise));
+            doc.get_dictionary_mut(page_id).unwrap().set("Thumb", Object::Reference(thumb_id));
+        }
+        let input = save_test_document(&mut doc, dir.path(), "scan.pdf");
+        let mut config = split_config(&input, dir.path(), SplitMode::MaxSize);
+        config.max_file_size = 50_000;
+
+        let parts = PdfSplitterService::split(&config).unwrap();
+        assert!(parts.len() >= 3);
+        assert!(parts.iter().all(|part| part.file_size <= 50_000));
+        let pages: Vec<i64> = part_markers(&parts).into_iter().flatten().collect();
+        assert_eq!(pages, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_split_never_overwrites_the_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = save_test_document(&mut build_test_document(3), dir.path(), "book.pdf");
+        let mut config = split_config(&input, dir.path(), SplitMode::Ranges);
+        config.ranges = vec!["1".to_string(), "2-3".to_string()];
+        config.name_template = "{name}".to_string();
+        config.overwrite = true;
+
+        // The first part would be named book.pdf
+        assert!(matches!(PdfSplitterService::split(&config), Err(AppError::ConfigError(_))));
+        assert_eq!(page_markers(&input), vec![0, 1, 2]);
+
+        config.name_template = "{name}_{index}".to_string();
+        config.overwrite = false;
+        PdfSplitterService::split(&config).unwrap();
+        assert!(matches!(PdfSplitterService::split(&config), Err(AppError::ConfigError(_))));
+        config.overwrite = true;
+        assert_eq!(part_markers(&PdfSplitterService::split(&config).unwrap()), vec![vec![0], vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_split_keeps_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(4);
+        let security = OutputSecurity { user_password: "secret".to_string(), ..Default::default() };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let input = save_test_document(&mut doc, dir.path(), "locked.pdf");
+        let mut config = split_config(&input, &dir.path().join("out"), SplitMode::EveryNPages);
+        config.password = Some("secret".to_string());
+        config.pages_per_part = 2;
+
+        let parts = PdfSplitterService::split(&config).unwrap();
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            assert!(matches!(PdfSecurityService::load(&part.path, None), Err(AppError::EncryptedDocument(_))));
+            assert_eq!(PdfSecurityService::load(&part.path, Some("secret")).unwrap().get_pages().len(), 2);
+        }
+    }
+}
@@ -0,0 +1,1113 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use ttf_parser::{name_id, Face};
+use crate::models::pdf::{HeaderFooter, Stamp, StampConfig, StampContent, StampPosition, StampResult};
+use crate::services::pdf_merger::PdfMergerService;
+use crate::services::pdf_security::PdfSecurityService;
+use crate::utils::date::DateTime;
+use crate::utils::error::AppError;
+use crate::utils::page_range::parse_page_range;
+
+
+/// Distance of corner and edge stamps from the page border, in points
+const STAMP_MARGIN: f32 = 36.0;
+
+/// Line height of multi-line text stamps relative to the font size
+const LINE_SPACING: f32 = 1.2;
+
+pub struct PdfStamperService;
+
+impl PdfStamperService {
+    /// Stamp an existing PDF and save the result to `config.output_path`
+    pub fn stamp(config: &StampConfig) -> Result<StampResult, AppError> {
+        if config.stamps.is_empty() {
+            return Err(AppError::ConfigError("No stamps to apply".to_string()));
+        }
+
+        let (mut doc, cipher) = PdfSecurityService::load_with_cipher(&config.input_path, config.password.as_deref())?;
+        PdfMergerService::resolve_inherited_attributes(&mut doc);
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        let stamped_pages = Self::apply(&mut doc, &page_ids, &config.stamps)?;
+
+        // Drop what decryption and object stream expansion left unreferenced
+        doc.prune_objects();
+        doc.compress();
+        if let Some(cipher) = &cipher {
+            PdfSecurityService::reencrypt(&mut doc, cipher)?;
+        }
+        doc.save(&config.output_path)
+            .map_err(|e| AppError::PdfError(format!("Failed to save stamped PDF: {}", e)))?;
+
+        log::info!(
+            "Applied {} stamps to {} pages of {}",
+            config.stamps.len(),
+            stamped_pages,
+            config.input_path
+        );
+        Ok(StampResult {
+            output_path: config.output_path.clone(),
+            stamped_pages,
+        })
+    }
+
+    /// Draw `stamps` onto `page_ids` (the document's pages in order, which
+    /// `Stamp.pages` refers to). Every page keeps its own content; stamps are
+    /// added as extra content streams with their own resources. Returns the
+    /// number of pages stamped.
+    pub fn apply(doc: &mut Document, page_ids: &[ObjectId], stamps: &[Stamp]) -> Result<usize, AppError> {
+        let mut prepared = Vec::new();
+        for stamp in stamps {
+            prepared.push(Self::prepare(doc, stamp, page_ids.len())?);
+        }
+        Self::draw(doc, page_ids, &prepared)
+    }
+
+    /// Draw headers and footers onto `page_ids`, numbering the pages in the
+    /// order given. `sources` holds the name of the file each page came from.
+    pub fn apply_headers_footers(
+        doc: &mut Document,
+        page_ids: &[ObjectId],
+        sources: &[String],
+        headers_footers: &[HeaderFooter],
+    ) -> Result<usize, AppError> {
+        let today = DateTime::now().date_string();
+        let mut prepared = Vec::new();
+        for header in headers_footers {
+            let date = header.date.as_deref().unwrap_or(&today);
+            let texts = (0..page_ids.len())
+                .map(|index| Self::expand_header_template(header, index, page_ids.len(), &sources[index], date))
+                .collect();
+            prepared.push(PreparedStamp {
+                pages: (1..=page_ids.len()).collect(),
+                behind: false,
+                position: header.position,
+                margin: (header.margin_x, header.margin_y),
+                rotation: 0.0,
+                scale: 1.0,
+                ext_gstate: None,
+                graphic: Self::text_graphic(doc, texts, header.font_size, &header.color, header.font_path.as_deref())?,
+            });
+        }
+        Self::draw(doc, page_ids, &prepared)
+    }
+
+    /// Fill in the tokens of a header or footer template for the page at
+    /// `index` of `total`. `{total}` counts the pages; `{last}` is the number
+    /// the last page gets, which differs when numbering doesn't start at 1.
+    fn expand_header_template(header: &HeaderFooter, index: usize, total: usize, source: &str, date: &str) -> String {
+        let number = |index: usize| {
+            let number = header.start_number + index as u64;
+            format!("{:0width$}", number, width = header.zero_padding)
+        };
+        header
+            .template
+            .replace("{page}", &number(index))
+            .replace("{total}", &total.to_string())
+            .replace("{last}", &number(total.saturating_sub(1)))
+            .replace("{file}", source)
+            .replace("{date}", date)
+    }
+
+    /// Draw prepared stamps onto their pages
+    fn draw(doc: &mut Document, page_ids: &[ObjectId], prepared: &[PreparedStamp]) -> Result<usize, AppError> {
+        // Opacity needs PDF 1.4, embedded OpenType fonts PDF 1.6
+        let needs_opentype = prepared
+            .iter()
+            .any(|p| matches!(&p.graphic, StampGraphic::Text { font, .. } if font.opentype));
+        let min_version = if needs_opentype { "1.6" } else { "1.4" };
+        if doc.version.as_str() < min_version {
+            doc.version = min_version.to_string();
+        }
+
+        let mut streams: HashMap<Vec<u8>, ObjectId> = HashMap::new();
+        let mut stamped_pages = 0;
+        for (index, &page_id) in page_ids.iter().enumerate() {
+            let page_stamps: Vec<&PreparedStamp> =
+                prepared.iter().filter(|p| p.pages.contains(&(index + 1))).collect();
+            if page_stamps.is_empty() {
+                continue;
+            }
+
+            let page = doc.get_dictionary(page_id)?;
+            let bbox = ["CropBox", "MediaBox"]
+                .iter()
+                .find_map(|key| page.get(key.as_bytes()).ok().and_then(|obj| PdfMergerService::rect_of(doc, obj)))
+                .unwrap_or([0.0, 0.0, 612.0, 792.0]);
+            let rotate = page.get(b"Rotate").and_then(Object::as_i64).unwrap_or(0).rem_euclid(360);
+            let mut resources = page
+                .get(b"Resources")
+                .ok()
+                .and_then(|obj| doc.dereference(obj).ok())
+                .and_then(|(_, obj)| obj.as_dict().ok().cloned())
+                .unwrap_or_default();
+
+            let mut over = Vec::new();
+            let mut under = Vec::new();
+            for stamp in page_stamps {
+                let operations = Self::stamp_operations(doc, stamp, index, &mut resources, bbox, rotate);
+                let target = if stamp.behind { &mut under } else { &mut over };
+                target.extend(operations);
+            }
+
+            let mut contents = Vec::new();
+            if !under.is_empty() {
+                contents.push(Self::content_stream(doc, &mut streams, Vec::new(), under)?);
+            }
+            let original = Self::page_contents(doc, page_id);
+            if over.is_empty() {
+                contents.extend(original);
+            } else {
+                // Isolate the page's graphics state so the stamp is drawn in
+                // the default coordinate system
+                contents.push(Self::content_stream(doc, &mut streams, Vec::new(), vec![Operation::new("q", vec![])])?);
+                contents.extend(original);
+                contents.push(Self::content_stream(doc, &mut streams, vec![Operation::new("Q", vec![])], over)?);
+            }
+
+            let page = doc.get_dictionary_mut(page_id)?;
+            page.set("Resources", Object::Dictionary(resources));
+            page.set("Contents", Object::Array(contents));
+            stamped_pages += 1;
+        }
+        Ok(stamped_pages)
+    }
+
+    /// Validate a stamp and add the objects shared by every page it goes on
+    fn prepare(doc: &mut Document, stamp: &Stamp, page_count: usize) -> Result<PreparedStamp, AppError> {
+        if !(0.0..=1.0).contains(&stamp.opacity) {
+            return Err(AppError::ConfigError(format!(
+                "Stamp opacity must be between 0 and 1, got {}",
+                stamp.opacity
+            )));
+        }
+        let pages: BTreeSet<usize> = match &stamp.pages {
+            Some(range) => parse_page_range(range, page_count)?.into_iter().collect(),
+            None => (1..=page_count).collect(),
+        };
+
+        let graphic = match &stamp.content {
+            StampContent::Text { text, font_size, color, font_path } => {
+                Self::text_graphic(doc, vec![text.clone()], *font_size, color, font_path.as_deref())?
+            }
+            StampContent::Image { path } => {
+                let (id, width, height) = Self::embed_image(doc, path)?;
+                StampGraphic::XObject { id, bbox: [0.0, 0.0, 1.0, 1.0], aspect: height / width }
+            }
+            StampContent::Pdf { path, page, password } => {
+                let (id, (width, height)) = Self::embed_pdf_page(doc, path, *page, password.as_deref())?;
+                StampGraphic::XObject { id, bbox: [0.0, 0.0, width, height], aspect: height / width }
+            }
+        };
+        if matches!(graphic, StampGraphic::XObject { .. }) && stamp.scale <= 0.0 {
+            return Err(AppError::ConfigError(format!("Invalid stamp scale {}", stamp.scale)));
+        }
+
+        let ext_gstate = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"ExtGState".to_vec())),
+            ("CA", Object::Real(stamp.opacity)),
+            ("ca", Object::Real(stamp.opacity)),
+        ]));
+        Ok(PreparedStamp {
+            pages,
+            behind: stamp.behind,
+            position: stamp.position,
+            margin: (STAMP_MARGIN, STAMP_MARGIN),
+            rotation: stamp.rotation,
+            scale: stamp.scale,
+            ext_gstate: Some(ext_gstate),
+            graphic,
+        })
+    }
+
+    /// Embed a font for `texts` and return the text graphic drawing them
+    fn text_graphic(
+        doc: &mut Document,
+        texts: Vec<String>,
+        font_size: f32,
+        color: &str,
+        font_path: Option<&str>,
+    ) -> Result<StampGraphic, AppError> {
+        if font_size <= 0.0 {
+            return Err(AppError::ConfigError(format!("Invalid font size {}", font_size)));
+        }
+        let color = Self::parse_color(color)?;
+        let chars: BTreeSet<char> = texts.iter().flat_map(|text| text.chars()).filter(|c| !c.is_control()).collect();
+        let (data, index) = Self::load_font(&chars, font_path)?;
+        let font = Self::embed_font(doc, &data, index, &chars)?;
+        Ok(StampGraphic::Text { font, texts, font_size, color })
+    }
+
+    /// Content operators drawing one stamp on the page at `page_index`, with
+    /// the given visible box and `/Rotate`. Resources the operators use are
+    /// added to `resources`.
+    fn stamp_operations(
+        doc: &Document,
+        stamp: &PreparedStamp,
+        page_index: usize,
+        resources: &mut Dictionary,
+        bbox: [f32; 4],
+        rotate: i64,
+    ) -> Vec<Operation> {
+        // Sizes and positions are worked out on the page as displayed, so
+        // stamps stay upright on pages with a /Rotate
+        let (box_width, box_height) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+        let (view_width, view_height) = if rotate % 180 == 0 {
+            (box_width, box_height)
+        } else {
+            (box_height, box_width)
+        };
+
+        let lines = match &stamp.graphic {
+            StampGraphic::Text { font, texts, .. } => {
+                font.layout(if texts.len() == 1 { &texts[0] } else { &texts[page_index] })
+            }
+            StampGraphic::XObject { .. } => Vec::new(),
+        };
+        let (width, height) = match &stamp.graphic {
+            StampGraphic::Text { font, font_size, .. } => font.block_size(&lines, *font_size),
+            StampGraphic::XObject { aspect, .. } => {
+                let width = view_width * stamp.scale;
+                (width, width * aspect)
+            }
+        };
+        let (sin, cos) = stamp.rotation.to_radians().sin_cos();
+        let extent = (
+            (width * cos).abs() + (height * sin).abs(),
+            (width * sin).abs() + (height * cos).abs(),
+        );
+        let center = Self::anchor(stamp.position, (view_width, view_height), extent, stamp.margin);
+        let (x, y) = Self::to_user_space(center, rotate, bbox);
+        let (sin, cos) = (stamp.rotation + rotate as f32).to_radians().sin_cos();
+
+        let mut operations = vec![Operation::new("q", vec![])];
+        if let Some(ext_gstate) = stamp.ext_gstate {
+            let gs_name = Self::add_resource(doc, resources, b"ExtGState", "PdfcraftGS", ext_gstate);
+            operations.push(Operation::new("gs", vec![Object::Name(gs_name)]));
+        }
+        operations.push(Operation::new("cm", [cos, sin, -sin, cos, x, y].into_iter().map(Object::Real).collect()));
+
+        match &stamp.graphic {
+            StampGraphic::Text { font, font_size, color, .. } => {
+                let font_name = Self::add_resource(doc, resources, b"Font", "PdfcraftF", font.id);
+                operations.push(Operation::new("BT", vec![]));
+                operations.push(Operation::new("Tf", vec![Object::Name(font_name), Object::Real(*font_size)]));
+                operations.push(Operation::new("rg", color.iter().copied().map(Object::Real).collect()));
+                let mut baseline = height / 2.0 - font.ascent * font_size;
+                for line in &lines {
+                    let line_x = -line.width * font_size / 2.0;
+                    operations.push(Operation::new(
+                        "Tm",
+                        [1.0, 0.0, 0.0, 1.0, line_x, baseline].into_iter().map(Object::Real).collect(),
+                    ));
+                    operations.push(Operation::new(
+                        "Tj",
+                        vec![Object::String(line.codes.clone(), StringFormat::Hexadecimal)],
+                    ));
+                    baseline -= font_size * LINE_SPACING;
+                }
+                operations.push(Operation::new("ET", vec![]));
+            }
+            StampGraphic::XObject { id, bbox, .. } => {
+                let name = Self::add_resource(doc, resources, b"XObject", "PdfcraftX", *id);
+                let sx = width / (bbox[2] - bbox[0]);
+                let sy = height / (bbox[3] - bbox[1]);
+                let matrix = [sx, 0.0, 0.0, sy, -width / 2.0 - sx * bbox[0], -height / 2.0 - sy * bbox[1]];
+                operations.push(Operation::new("cm", matrix.into_iter().map(Object::Real).collect()));
+                operations.push(Operation::new("Do", vec![Object::Name(name)]));
+            }
+        }
+        operations.push(Operation::new("Q", vec![]));
+        operations
+    }
+
+    /// Center of a stamp whose rotated bounding box is `extent`, on a page
+    /// of `view` size as displayed, keeping `margin` from the page edges
+    fn anchor(position: StampPosition, view: (f32, f32), extent: (f32, f32), margin: (f32, f32)) -> (f32, f32) {
+        let left = margin.0 + extent.0 / 2.0;
+        let right = view.0 - margin.0 - extent.0 / 2.0;
+        let bottom = margin.1 + extent.1 / 2.0;
+        let top = view.1 - margin.1 - extent.1 / 2.0;
+        let (center_x, center_y) = (view.0 / 2.0, view.1 / 2.0);
+        match position {
+            StampPosition::Center => (center_x, center_y),
+            StampPosition::Top => (center_x, top),
+            StampPosition::Bottom => (center_x, bottom),
+            StampPosition::TopLeft => (left, top),
+            StampPosition::TopRight => (right, top),
+            StampPosition::BottomLeft => (left, bottom),
+            StampPosition::BottomRight => (right, bottom),
+        }
+    }
+
+    /// Map a point on the page as displayed back to default user space
+    fn to_user_space(point: (f32, f32), rotate: i64, bbox: [f32; 4]) -> (f32, f32) {
+        let (width, height) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+        let (x, y) = point;
+        let (u, v) = match rotate {
+            90 => (width - y, x),
+            180 => (width - x, height - y),
+            270 => (y, height - x),
+            _ => (x, y),
+        };
+        (bbox[0] + u, bbox[1] + v)
+    }
+
+    /// Add a reference to `category` of a page's resources under a name not
+    /// used yet, returning that name
+    fn add_resource(doc: &Document, resources: &mut Dictionary, category: &[u8], prefix: &str, id: ObjectId) -> Vec<u8> {
+        let mut entries = resources
+            .get(category)
+            .ok()
+            .and_then(|obj| doc.dereference(obj).ok())
+            .and_then(|(_, obj)| obj.as_dict().ok().cloned())
+            .unwrap_or_default();
+        if let Some((name, _)) = entries.iter().find(|(_, value)| value.as_reference().ok() == Some(id)) {
+            return name.clone();
+        }
+
+        let name = (0..)
+            .map(|n| format!("{}{}", prefix, n).into_bytes())
+            .find(|name| !entries.has(name))
+            .unwrap_or_default();
+        entries.set(name.clone(), Object::Reference(id));
+        resources.set(category, Object::Dictionary(entries));
+        name
+    }
+
+    /// Content streams of a page as a list of references
+    fn page_contents(doc: &Document, page_id: ObjectId) -> Vec<Object> {
+        let Ok(page) = doc.get_dictionary(page_id) else {
+            return Vec::new();
+        };
+        match page.get(b"Contents") {
+            Ok(Object::Reference(id)) => match doc.get_object(*id) {
+                Ok(Object::Array(items)) => items.clone(),
+                _ => vec![Object::Reference(*id)],
+            },
+            Ok(Object::Array(items)) => items.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Add a content stream, reusing an identical one added for an earlier page
+    fn content_stream(
+        doc: &mut Document,
+        streams: &mut HashMap<Vec<u8>, ObjectId>,
+        mut operations: Vec<Operation>,
+        rest: Vec<Operation>,
+    ) -> Result<Object, AppError> {
+        operations.extend(rest);
+        // Streams are concatenated when drawn; start on a fresh token in case
+        // the previous one does not end with whitespace
+        let mut data = b"\n".to_vec();
+        data.extend(Content { operations }.encode()?);
+        let id = match streams.get(&data) {
+            Some(&id) => id,
+            None => {
+                let id = doc.add_object(Stream::new(Dictionary::new(), data.clone()));
+                streams.insert(data, id);
+                id
+            }
+        };
+        Ok(Object::Reference(id))
+    }
+
+    /// Parse a `#RRGGBB` color into RGB components between 0 and 1
+    fn parse_color(color: &str) -> Result<[f32; 3], AppError> {
+        let hex = color.trim().trim_start_matches('#');
+        let invalid = || AppError::ConfigError(format!("Invalid stamp color \"{}\"", color));
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut rgb = [0.0; 3];
+        for (i, component) in rgb.iter_mut().enumerate() {
+            let value = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+            *component = value as f32 / 255.0;
+        }
+        Ok(rgb)
+    }
+
+    /// Fonts tried, in order, for text without a `font_path`. The CJK fonts
+    /// come first so Chinese, Japanese and Korean text renders out of the box.
+    fn font_candidates() -> Vec<&'static str> {
+        if cfg!(target_os = "macos") {
+            vec![
+                "/System/Library/Fonts/PingFang.ttc",
+                "/System/Library/Fonts/Hiragino Sans GB.ttc",
+                "/System/Library/Fonts/STHeiti Light.ttc",
+                "/Library/Fonts/Arial Unicode.ttf",
+                "/System/Library/Fonts/Supplemental/Arial.ttf",
+            ]
+        } else if cfg!(target_os = "windows") {
+            vec![
+                r"C:\Windows\Fonts\msyh.ttc",
+                r"C:\Windows\Fonts\simsun.ttc",
+                r"C:\Windows\Fonts\msgothic.ttc",
+                r"C:\Windows\Fonts\malgun.ttf",
+                r"C:\Windows\Fonts\arial.ttf",
+            ]
+        } else {
+            vec![
+                "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+                "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+                "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+                "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+                "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+                "/usr/share/fonts/TTF/DejaVuSans.ttf",
+            ]
+        }
+    }
+
+    /// Read the font file for a text stamp and pick the face in it that has
+    /// a glyph for every character. Without `font_path` the first installed
+    /// candidate font that covers the text is used.
+    fn load_font(chars: &BTreeSet<char>, font_path: Option<&str>) -> Result<(Vec<u8>, u32), AppError> {
+        if chars.is_empty() {
+            return Err(AppError::ConfigError("Stamp text is empty".to_string()));
+        }
+        let text: String = chars.iter().collect();
+        let chars: Vec<char> = chars.iter().copied().collect();
+
+        if let Some(path) = font_path {
+            if !Path::new(path).exists() {
+                return Err(AppError::FileNotFound(path.to_string()));
+            }
+            let data = fs::read(path)?;
+            return match Self::covering_face(&data, &chars) {
+                Some(index) => Ok((data, index)),
+                None => Err(AppError::ConfigError(format!(
+                    "Font {} cannot display \"{}\"",
+                    path, text
+                ))),
+            };
+        }
+
+        for candidate in Self::font_candidates() {
+            let Ok(data) = fs::read(candidate) else {
+                continue;
+            };
+            if let Some(index) = Self::covering_face(&data, &chars) {
+                log::debug!("Using font {} for stamp text", candidate);
+                return Ok((data, index));
+            }
+        }
+        Err(AppError::ConfigError(format!(
+            "No installed font can display \"{}\"; choose a font file for the stamp",
+            text
+        )))
+    }
+
+    /// Index of the first face of a font file or collection with glyphs for
+    /// all of `chars`
+    fn covering_face(data: &[u8], chars: &[char]) -> Option<u32> {
+        let count = ttf_parser::fonts_in_collection(data).unwrap_or(1);
+        (0..count).find(|&index| {
+            Face::parse(data, index)
+                .map(|face| chars.iter().all(|&c| face.glyph_index(c).is_some()))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Embed a subset of a font as a Type0 font with Identity-H encoding, so
+    /// any script can be drawn by glyph id. A ToUnicode map keeps the text
+    /// searchable and copyable.
+    fn embed_font(doc: &mut Document, data: &[u8], index: u32, chars: &BTreeSet<char>) -> Result<EmbeddedFont, AppError> {
+        let face = Face::parse(data, index).map_err(|e| AppError::PdfError(format!("Unreadable font: {}", e)))?;
+        let units = 1000.0 / face.units_per_em() as f32;
+
+        let mut glyphs: BTreeMap<u16, (char, u16)> = BTreeMap::new();
+        let mut char_glyphs = HashMap::new();
+        for &c in chars {
+            let Some(glyph) = face.glyph_index(c) else {
+                continue;
+            };
+            let advance = face.glyph_hor_advance(glyph).unwrap_or(0);
+            glyphs.entry(glyph.0).or_insert((c, advance));
+            char_glyphs.insert(c, (glyph.0, advance as f32 / face.units_per_em() as f32));
+        }
+
+        let mut subset_glyphs: Vec<u16> = glyphs.keys().copied().collect();
+        subset_glyphs.insert(0, 0);
+        let font_data = subsetter::subset(data, index, subsetter::Profile::pdf(&subset_glyphs))
+            .map_err(|e| AppError::PdfError(format!("Failed to subset font: {}", e)))?;
+
+        let opentype = face.tables().cff.is_some() || face.tables().cff2.is_some();
+        let base_font = Self::subset_font_name(&face, &subset_glyphs);
+
+        let font_file = if opentype {
+            let dict = Dictionary::from_iter(vec![("Subtype", Object::Name(b"OpenType".to_vec()))]);
+            doc.add_object(Stream::new(dict, font_data))
+        } else {
+            let dict = Dictionary::from_iter(vec![("Length1", Object::Integer(font_data.len() as i64))]);
+            doc.add_object(Stream::new(dict, font_data))
+        };
+
+        let bbox = face.global_bounding_box();
+        let ascent = face.ascender() as f32 * units;
+        let descent = face.descender() as f32 * units;
+        let descriptor = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"FontDescriptor".to_vec())),
+            ("FontName", Object::Name(base_font.clone())),
+            ("Flags", Object::Integer(4)),
+            (
+                "FontBBox",
+                Object::Array(
+                    [bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max]
+                        .into_iter()
+                        .map(|v| Object::Integer((v as f32 * units).round() as i64))
+                        .collect(),
+                ),
+            ),
+            ("ItalicAngle", Object::Real(face.italic_angle())),
+            ("Ascent", Object::Integer(ascent.round() as i64)),
+            ("Descent", Object::Integer(descent.round() as i64)),
+            (
+                "CapHeight",
+                Object::Integer(face.capital_height().map_or(ascent, |h| h as f32 * units).round() as i64),
+            ),
+            ("StemV", Object::Integer(80)),
+            (if opentype { "FontFile3" } else { "FontFile2" }, Object::Reference(font_file)),
+        ]));
+
+        let widths: Vec<Object> = glyphs
+            .iter()
+            .flat_map(|(&glyph, &(_, advance))| {
+                [
+                    Object::Integer(glyph as i64),
+                    Object::Array(vec![Object::Integer((advance as f32 * units).round() as i64)]),
+                ]
+            })
+            .collect();
+        let system_info = Dictionary::from_iter(vec![
+            ("Registry", Object::string_literal("Adobe")),
+            ("Ordering", Object::string_literal("Identity")),
+            ("Supplement", Object::Integer(0)),
+        ]);
+        let mut cid_font = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            (
+                "Subtype",
+                Object::Name(if opentype { b"CIDFontType0".to_vec() } else { b"CIDFontType2".to_vec() }),
+            ),
+            ("BaseFont", Object::Name(base_font.clone())),
+            ("CIDSystemInfo", Object::Dictionary(system_info)),
+            ("FontDescriptor", Object::Reference(descriptor)),
+            ("W", Object::Array(widths)),
+        ]);
+        if !opentype {
+            cid_font.set("CIDToGIDMap", Object::Name(b"Identity".to_vec()));
+        }
+        let cid_font = doc.add_object(cid_font);
+
+        let to_unicode = doc.add_object(Stream::new(Dictionary::new(), Self::to_unicode_cmap(&glyphs)));
+        let id = doc.add_object(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type0".to_vec())),
+            ("BaseFont", Object::Name(base_font)),
+            ("Encoding", Object::Name(b"Identity-H".to_vec())),
+            ("DescendantFonts", Object::Array(vec![Object::Reference(cid_font)])),
+            ("ToUnicode", Object::Reference(to_unicode)),
+        ]));
+
+        Ok(EmbeddedFont {
+            id,
+            glyphs: char_glyphs,
+            ascent: ascent / 1000.0,
+            descent: descent / 1000.0,
+            opentype,
+        })
+    }
+
+    /// PostScript name of a font with the six letter tag that marks a subset
+    fn subset_font_name(face: &Face, glyphs: &[u16]) -> Vec<u8> {
+        let name: String = face
+            .names()
+            .into_iter()
+            .find(|name| name.name_id == name_id::POST_SCRIPT_NAME && name.is_unicode())
+            .and_then(|name| name.to_string())
+            .unwrap_or_else(|| "PdfcraftStamp".to_string())
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect();
+
+        let mut hasher = DefaultHasher::new();
+        glyphs.hash(&mut hasher);
+        let mut hash = hasher.finish();
+        let mut tag = String::new();
+        for _ in 0..6 {
+            tag.push((b'A' + (hash % 26) as u8) as char);
+            hash /= 26;
+        }
+        format!("{}+{}", tag, name).into_bytes()
+    }
+
+    /// ToUnicode CMap mapping glyph ids back to the characters they were
+    /// picked for
+    fn to_unicode_cmap(glyphs: &BTreeMap<u16, (char, u16)>) -> Vec<u8> {
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+             /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+             1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+        let entries: Vec<(&u16, &(char, u16))> = glyphs.iter().collect();
+        // A bfchar block holds at most 100 entries
+        for chunk in entries.chunks(100) {
+            cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+            for (glyph, (c, _)) in chunk {
+                let mut units = [0u16; 2];
+                let utf16: String = c.encode_utf16(&mut units).iter().map(|u| format!("{:04X}", u)).collect();
+                cmap.push_str(&format!("<{:04X}> <{}>\n", glyph, utf16));
+            }
+            cmap.push_str("endbfchar\n");
+        }
+        cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+        cmap.into_bytes()
+    }
+
+    /// Add a raster image as an image XObject. Grayscale and RGB JPEGs are
+    /// embedded as they are; everything else is stored as 8-bit RGB with the
+    /// alpha channel as a soft mask. Returns the id and pixel size.
+    fn embed_image(doc: &mut Document, path: &str) -> Result<(ObjectId, f32, f32), AppError> {
+        if !Path::new(path).exists() {
+            return Err(AppError::FileNotFound(path.to_string()));
+        }
+        let data = fs::read(path)?;
+        let image = image::load_from_memory(&data)
+            .map_err(|e| AppError::PdfError(format!("Failed to read image {}: {}", path, e)))?;
+        let (width, height) = (image.width(), image.height());
+
+        let mut dict = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"XObject".to_vec())),
+            ("Subtype", Object::Name(b"Image".to_vec())),
+            ("Width", Object::Integer(width as i64)),
+            ("Height", Object::Integer(height as i64)),
+            ("BitsPerComponent", Object::Integer(8)),
+        ]);
+
+        let is_jpeg = image::guess_format(&data).ok() == Some(image::ImageFormat::Jpeg);
+        let id = match Self::jpeg_components(&data).filter(|_| is_jpeg) {
+            Some(components @ (1 | 3)) => {
+                let color_space: &[u8] = if components == 1 { b"DeviceGray" } else { b"DeviceRGB" };
+                dict.set("ColorSpace", Object::Name(color_space.to_vec()));
+                dict.set("Filter", Object::Name(b"DCTDecode".to_vec()));
+                doc.add_object(Stream::new(dict, data))
+            }
+            _ => {
+                dict.set("ColorSpace", Object::Name(b"DeviceRGB".to_vec()));
+                if image.color().has_alpha() {
+                    let alpha: Vec<u8> = image.to_rgba8().pixels().map(|p| p.0[3]).collect();
+                    let mask = Dictionary::from_iter(vec![
+                        ("Type", Object::Name(b"XObject".to_vec())),
+                        ("Subtype", Object::Name(b"Image".to_vec())),
+                        ("Width", Object::Integer(width as i64)),
+                        ("Height", Object::Integer(height as i64)),
+                        ("BitsPerComponent", Object::Integer(8)),
+                        ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+                    ]);
+                    dict.set("SMask", Object::Reference(doc.add_object(Stream::new(mask, alpha))));
+                }
+                doc.add_object(Stream::new(dict, image.to_rgb8().into_raw()))
+            }
+        };
+        Ok((id, width as f32, height as f32))
+    }
+
+    /// Number of color components in the frame header of a JPEG file. CMYK
+    /// JPEGs (4 components) need an inverted Decode array in most files, so
+    /// only 1 and 3 are embedded without re-encoding.
+    fn jpeg_components(data: &[u8]) -> Option<u8> {
+        let mut pos = 2;
+        while pos + 4 <= data.len() {
+            if data[pos] != 0xFF {
+                return None;
+            }
+            let marker = data[pos + 1];
+            let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+            if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                return data.get(pos + 9).copied();
+            }
+            pos += 2 + length;
+        }
+        None
+    }
+
+    /// Copy a page of another PDF into `doc` as a form XObject, returning
+    /// its id and displayed size
+    fn embed_pdf_page(
+        doc: &mut Document,
+        path: &str,
+        page: usize,
+        password: Option<&str>,
+    ) -> Result<(ObjectId, (f32, f32)), AppError> {
+        let source = PdfSecurityService::load(path, password)?;
+        let page_ids: Vec<ObjectId> = source.page_iter().collect();
+        let page_id = *page
+            .checked_sub(1)
+            .and_then(|index| page_ids.get(index))
+            .ok_or_else(|| AppError::ConfigError(format!("{} has no page {}", path, page)))?;
+        PdfMergerService::page_to_form(&source, page_id, &mut BTreeMap::new(), doc)
+    }
+}
+
+/// A stamp with its shared objects already added to the document
+struct PreparedStamp {
+    /// 1-based page numbers to stamp
+    pages: BTreeSet<usize>,
+    behind: bool,
+    position: StampPosition,
+    /// Horizontal and vertical distance from the page edges
+    margin: (f32, f32),
+    rotation: f32,
+    scale: f32,
+    /// Opacity settings; `None` draws fully opaque
+    ext_gstate: Option<ObjectId>,
+    graphic: StampGraphic,
+}
+
+enum StampGraphic {
+    Text {
+        font: EmbeddedFont,
+        /// One text per page, or a single text drawn on every page
+        texts: Vec<String>,
+        font_size: f32,
+        color: [f32; 3],
+    },
+    /// Image or form XObject drawn into `bbox`, with height/width `aspect`
+    XObject {
+        id: ObjectId,
+        bbox: [f32; 4],
+        aspect: f32,
+    },
+}
+
+/// A Type0 font embedded for the characters of one or more texts
+struct EmbeddedFont {
+    id: ObjectId,
+    /// Glyph id and advance width (relative to the font size) by character
+    glyphs: HashMap<char, (u16, f32)>,
+    /// Ascent and descent relative to the font size
+    ascent: f32,
+    descent: f32,
+    opentype: bool,
+}
+
+impl EmbeddedFont {
+    /// Split `text` into lines of glyph codes
+    fn layout(&self, text: &str) -> Vec<TextLine> {
+        text.split('\n')
+            .map(|line| {
+                let mut codes = Vec::new();
+                let mut width = 0.0;
+                for c in line.chars() {
+                    if let Some(&(glyph, advance)) = self.glyphs.get(&c) {
+                        codes.extend(glyph.to_be_bytes());
+                        width += advance;
+                    }
+                }
+                TextLine { codes, width }
+            })
+            .collect()
+    }
+
+    /// Width and height of laid out text at `font_size`
+    fn block_size(&self, lines: &[TextLine], font_size: f32) -> (f32, f32) {
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max) * font_size;
+        let height = (lines.len() - 1) as f32 * font_size * LINE_SPACING + (self.ascent - self.descent) * font_size;
+        (width, height)
+    }
+}
+
+struct TextLine {
+    /// Two-byte glyph ids
+    codes: Vec<u8>,
+    /// Advance width relative to the font size
+    width: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pdf::{MergeConfig, OutputSecurity, PdfFileEntry};
+    use crate::services::test_support::*;
+
+    /// Font shipped with the tests, so that they don't depend on the fonts
+    /// installed on the machine
+    const TEST_FONT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/PdfcraftTest.ttf");
+
+    fn stamp(content: StampContent) -> Stamp {
+        Stamp {
+            content,
+            pages: None,
+            position: StampPosition::Center,
+            rotation: 0.0,
+            opacity: 0.5,
+            scale: 0.5,
+            behind: false,
+        }
+    }
+
+    fn text(text: &str) -> StampContent {
+        StampContent::Text {
+            text: text.to_string(),
+            font_size: 48.0,
+            color: "#FF0000".to_string(),
+            font_path: Some(TEST_FONT.to_string()),
+        }
+    }
+
+    /// Resource dictionary `category` of a page, resolved
+    fn page_resources(doc: &Document, page_id: ObjectId, category: &[u8]) -> Dictionary {
+        let resources = doc.get_dictionary(page_id).unwrap().get(b"Resources").unwrap();
+        let (_, category) = doc.dereference(resources.as_dict().unwrap().get(category).unwrap()).unwrap();
+        category.as_dict().unwrap().clone()
+    }
+
+    #[test]
+    fn test_stamp_text_watermark() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(3);
+        let rotated = doc.page_iter().nth(1).unwrap();
+        doc.get_dictionary_mut(rotated).unwrap().set("Rotate", Object::Integer(90));
+        let input = save_test_document(&mut doc, dir.path(), "input.pdf");
+        let output = dir.path().join("stamped.pdf").to_string_lossy().to_string();
+
+        let config = StampConfig {
+            input_path: input,
+            password: None,
+            output_path: output.clone(),
+            stamps: vec![Stamp { pages: Some("2-3".to_string()), ..stamp(text("DRAFT\nCopy")) }],
+        };
+        let result = PdfStamperService::stamp(&config).unwrap();
+        assert_eq!(result.stamped_pages, 2);
+
+        let stamped = Document::load(&output).unwrap();
+        let pages: Vec<ObjectId> = stamped.page_iter().collect();
+        assert!(stamped.get_page_fonts(pages[0]).unwrap().is_empty());
+
+        let fonts = stamped.get_page_fonts(pages[1]).unwrap();
+        let font = fonts.values().next().unwrap();
+        assert_eq!(font.get(b"Subtype").unwrap().as_name().unwrap(), b"Type0");
+        assert_eq!(font.get(b"Encoding").unwrap().as_name().unwrap(), b"Identity-H");
+        let gs = page_resources(&stamped, pages[1], b"ExtGState");
+        let (_, gs) = stamped.dereference(gs.iter().next().unwrap().1).unwrap();
+        assert_eq!(gs.as_dict().unwrap().get(b"ca").unwrap().as_float().unwrap(), 0.5);
+
+        // The original drawing is kept, followed by the two text lines
+        let content = Content::decode(&stamped.get_page_content(pages[1]).unwrap()).unwrap();
+        let operators: Vec<&str> = content.operations.iter().map(|op| op.operator.as_str()).collect();
+        assert_eq!(operators[..5], ["q", "m", "l", "S", "Q"]);
+        assert_eq!(operators.iter().filter(|&&op| op == "Tj").count(), 2);
+        // Rotated by the page's 90 degrees around the page center
+        let cm = content.operations.iter().find(|op| op.operator == "cm").unwrap();
+        let matrix: Vec<f32> = cm.operands.iter().map(|v| v.as_float().unwrap()).collect();
+        assert!(matrix[0].abs() < 1e-4 && (matrix[1] - 1.0).abs() < 1e-4);
+        assert!((matrix[4] - 306.0).abs() < 0.01 && (matrix[5] - 396.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_stamp_keeps_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut doc = build_test_document(2);
+        let security = OutputSecurity { user_password: "secret".to_string(), ..Default::default() };
+        PdfSecurityService::encrypt(&mut doc, &security).unwrap();
+        let input = save_test_document(&mut doc, dir.path(), "locked.pdf");
+        let output = dir.path().join("stamped.pdf").to_string_lossy().to_string();
+
+        let config = StampConfig {
+            input_path: input,
+            password: Some("secret".to_string()),
+            output_path: output.clone(),
+            stamps: vec![stamp(text("DRAFT"))],
+        };
+        assert_eq!(PdfStamperService::stamp(&config).unwrap().stamped_pages, 2);
+        assert!(matches!(PdfSecurityService::load(&output, None), Err(AppError::EncryptedDocument(_))));
+        let stamped = PdfSecurityService::load(&output, Some("secret")).unwrap();
+        let page_id = stamped.page_iter().next().unwrap();
+        assert_eq!(stamped.get_page_fonts(page_id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stamp_image_behind_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("logo.png");
+        image::RgbaImage::from_pixel(4, 2, image::Rgba([0, 128, 255, 100])).save(&png).unwrap();
+        let jpeg = dir.path().join("photo.jpg");
+        image::RgbImage::from_pixel(8, 8, image::Rgb([200, 10, 10])).save(&jpeg).unwrap();
+
+        let mut doc = build_test_document(2);
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        let stamps = vec![
+            Stamp {
+                position: StampPosition::TopRight,
+                behind: true,
+                ..stamp(StampContent::Image { path: png.to_string_lossy().to_string() })
+            },
+            Stamp {
+                pages: Some("1".to_string()),
+                ..stamp(StampContent::Image { path: jpeg.to_string_lossy().to_string() })
+            },
+        ];
+        assert_eq!(PdfStamperService::apply(&mut doc, &page_ids, &stamps).unwrap(), 2);
+
+        let images = page_resources(&doc, page_ids[0], b"XObject");
+        let filters: Vec<Option<&[u8]>> = images
+            .iter()
+            .map(|(_, id)| {
+                let image = doc.get_object(id.as_reference().unwrap()).unwrap().as_stream().unwrap();
+                image.dict.get(b"Filter").and_then(Object::as_name).ok()
+            })
+            .collect();
+        assert_eq!(filters, vec![None, Some(b"DCTDecode".as_slice())]);
+        assert_eq!(page_resources(&doc, page_ids[1], b"XObject").len(), 1);
+
+        // Drawn first, 306pt wide, in the top right corner
+        let content = Content::decode(&doc.get_page_content(page_ids[1]).unwrap()).unwrap();
+        assert_eq!(content.operations[0].operator, "q");
+        let cm = content.operations.iter().find(|op| op.operator == "cm").unwrap();
+        let center: Vec<f32> = cm.operands[4..].iter().map(|v| v.as_float().unwrap()).collect();
+        assert_eq!(center, vec![612.0 - 36.0 - 153.0, 792.0 - 36.0 - 76.5]);
+    }
+
+    #[test]
+    fn test_merge_stamps_pdf_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = save_test_document(&mut build_test_document(1), dir.path(), "first.pdf");
+        let second = save_test_document(&mut build_test_document(2), dir.path(), "second.pdf");
+        let logo = save_test_document(&mut build_test_document(3), dir.path(), "logo.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![
+                PdfFileEntry { path: first, ..Default::default() },
+                PdfFileEntry { path: second, ..Default::default() },
+            ],
+            output_path: output.clone(),
+            stamps: vec![Stamp {
+                pages: Some("2-".to_string()),
+                ..stamp(StampContent::Pdf { path: logo, page: 3, password: None })
+            }],
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let pages: Vec<ObjectId> = merged.page_iter().collect();
+        assert_eq!(pages.len(), 3);
+        let resources = merged.get_dictionary(pages[0]).unwrap().get(b"Resources");
+        assert!(resources.is_err() || !resources.unwrap().as_dict().unwrap().has(b"XObject"));
+
+        let forms = page_resources(&merged, pages[2], b"XObject");
+        let form = merged.get_object(forms.iter().next().unwrap().1.as_reference().unwrap()).unwrap();
+        let form = form.as_stream().unwrap();
+        assert_eq!(form.dict.get(b"Subtype").unwrap().as_name().unwrap(), b"Form");
+        let drawing = Content::decode(&form.get_plain_content().unwrap()).unwrap();
+        assert_eq!(drawing.operations[1].operands[0].as_i64().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_stamp_rejects_invalid_settings() {
+        let mut doc = build_test_document(1);
+        let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+        let color = StampContent::Text {
+            text: "x".to_string(),
+            font_size: 12.0,
+            color: "red".to_string(),
+            font_path: None,
+        };
+        let invalid = [
+            stamp(color),
+            Stamp { opacity: 1.5, ..stamp(text("x")) },
+            Stamp { pages: Some("2".to_string()), ..stamp(text("x")) },
+            stamp(StampContent::Image { path: "/missing/logo.png".to_string() }),
+        ];
+        for stamp in invalid {
+            assert!(PdfStamperService::apply(&mut doc, &page_ids, &[stamp]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_to_user_space_keeps_stamps_upright() {
+        let bbox = [0.0, 0.0, 600.0, 800.0];
+        // Top left as displayed, for each page rotation
+        assert_eq!(PdfStamperService::to_user_space((10.0, 790.0), 0, bbox), (10.0, 790.0));
+        assert_eq!(PdfStamperService::to_user_space((10.0, 590.0), 90, bbox), (10.0, 10.0));
+        assert_eq!(PdfStamperService::to_user_space((10.0, 790.0), 180, bbox), (590.0, 10.0));
+        assert_eq!(PdfStamperService::to_user_space((10.0, 590.0), 270, bbox), (590.0, 790.0));
+    }
+
+    fn footer(template: &str) -> HeaderFooter {
+        HeaderFooter {
+            template: template.to_string(),
+            position: StampPosition::Bottom,
+            margin_x: 36.0,
+            margin_y: 24.0,
+            font_size: 10.0,
+            color: "#000000".to_string(),
+            font_path: Some(TEST_FONT.to_string()),
+            start_number: 1,
+            zero_padding: 0,
+            date: Some("2024-05-01".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_expand_header_template() {
+        let bates = HeaderFooter { start_number: 123, zero_padding: 6, ..footer("ABC{page}") };
+        assert_eq!(PdfStamperService::expand_header_template(&bates, 2, 10, "a", "d"), "ABC000125");
+        let page = footer("Page {page} of {total} - {file} {date}");
+        assert_eq!(
+            PdfStamperService::expand_header_template(&page, 1, 5, "exhibit", "2024-05-01"),
+            "Page 2 of 5 - exhibit 2024-05-01"
+        );
+        let later = HeaderFooter { start_number: 5, ..footer("Page {page} of {total}, {page}-{last}") };
+        assert_eq!(PdfStamperService::expand_header_template(&later, 0, 10, "a", "d"), "Page 5 of 10, 5-14");
+    }
+
+    #[test]
+    fn test_merge_numbers_pages_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = save_test_document(&mut build_test_document(1), dir.path(), "first.pdf");
+        let second = save_test_document(&mut build_test_document(3), dir.path(), "second.pdf");
+        let output = dir.path().join("merged.pdf").to_string_lossy().to_string();
+
+        let config = MergeConfig {
+            files: vec![
+                PdfFileEntry { path: first, ..Default::default() },
+                PdfFileEntry { path: second, selected_pages: Some(vec![3, 1]), ..Default::default() },
+            ],
+            output_path: output.clone(),
+            headers_footers: vec![
+                footer("Page {page} of {total}"),
+                HeaderFooter {
+                    position: StampPosition::TopRight,
+                    start_number: 41,
+                    zero_padding: 4,
+                    ..footer("{file}-{page}")
+                },
+            ],
+            ..Default::default()
+        };
+        PdfMergerService::merge(&config).unwrap();
+
+        let merged = Document::load(&output).unwrap();
+        let texts: Vec<String> = (1..=3).map(|page| merged.extract_text(&[page]).unwrap()).collect();
+        for (text, expected) in texts.iter().zip(["first-0041", "second-0042", "second-0043"]) {
+            assert!(text.contains(expected), "{:?} lacks {}", text, expected);
+        }
+        assert!(texts[2].contains("Page 3 of 3"));
+    }
+
+    #[test]
+    fn test_to_unicode_cmap() {
+        let glyphs = BTreeMap::from([(3, ('水', 1000)), (0x1F, ('𠀀', 1000))]);
+        let cmap = String::from_utf8(PdfStamperService::to_unicode_cmap(&glyphs)).unwrap();
+        assert!(cmap.contains("2 beginbfchar\n<0003> <6C34>\n<001F> <D840DC00>\nendbfchar"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// Build a minimal document with `page_count` Letter pages, each drawing
+/// a different line
+pub fn build_test_document(page_count: usize) -> Document {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let mut kids = Vec::new();
+    for i in 0..page_count {
+        let content = format!("0 0 m {} {} l S", i, i).into_bytes();
+        let content_id = doc.add_object(lopdf::Stream::new(Dictionary::new(), content));
+        let mut page = Dictionary::new();
+        page.set("Type", Object::Name(b"Page".to_vec()));
+        page.set("Parent", Object::Reference(pages_id));
+        page.set("MediaBox", Object::Array(vec![0.into(), 0.into(), 612.into(), 792.into()]));
+        page.set("Contents", Object::Reference(content_id));
+        kids.push(Object::Reference(doc.add_object(page)));
+    }
+    let mut pages = Dictionary::new();
+    pages.set("Type", Object::Name(b"Pages".to_vec()));
+    pages.set("Count", Object::Integer(page_count as i64));
+    pages.set("Kids", Object::Array(kids));
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+    catalog.set("Pages", Object::Reference(pages_id));
+    let catalog_id = doc.add_object(catalog);
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+    doc
+}
+
+/// Add a flat outline with one item per `(title, page_number)` pair
+pub fn add_test_outline(doc: &mut Document, items: &[(&str, usize)]) {
+    let pages: Vec<ObjectId> = doc.page_iter().collect();
+    let outlines_id = doc.new_object_id();
+    let ids: Vec<ObjectId> = items.iter().map(|_| doc.new_object_id()).collect();
+    for (i, (title, page)) in items.iter().enumerate() {
+        let mut item = Dictionary::new();
+        item.set("Title", lopdf::text_string(title));
+        item.set("Parent", Object::Reference(outlines_id));
+        item.set("Dest", Object::Array(vec![
+            Object::Reference(pages[page - 1]),
+            Object::Name(b"Fit".to_vec()),
+        ]));
+        if i + 1 < ids.len() {
+            item.set("Next", Object::Reference(ids[i + 1]));
+        }
+        doc.objects.insert(ids[i], Object::Dictionary(item));
+    }
+    let mut outlines = Dictionary::new();
+    outlines.set("First", Object::Reference(ids[0]));
+    outlines.set("Last", Object::Reference(*ids.last().unwrap()));
+    outlines.set("Count", Object::Integer(ids.len() as i64));
+    doc.objects.insert(outlines_id, Object::Dictionary(outlines));
+    doc.catalog_mut().unwrap().set("Outlines", Object::Reference(outlines_id));
+}
+
+pub fn save_test_document(doc: &mut Document, dir: &Path, name: &str) -> String {
+    let path = dir.join(name);
+    doc.save(&path).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+pub fn outline_titles(doc: &Document, parent: &Dictionary) -> Vec<String> {
+    let mut titles = Vec::new();
+    let mut current = parent.get(b"First").and_then(Object::as_reference).ok();
+    while let Some(id) = current {
+        let item = doc.get_dictionary(id).unwrap();
+        titles.push(lopdf::decode_text_string(item.get(b"Title").unwrap()).unwrap());
+        current = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    titles
+}
+
+/// The page index of `build_test_document` drawn by every page of a saved file
+pub fn page_markers(path: &str) -> Vec<i64> {
+    let merged = Document::load(path).unwrap();
+    merged
+        .page_iter()
+        .map(|page_id| {
+            let content = Content::decode(&merged.get_page_content(page_id).unwrap()).unwrap();
+            content.operations[1].operands[0].as_i64().unwrap()
+        })
+        .collect()
+}
@@ -0,0 +1,567 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use encoding_rs::{Encoding, BIG5, EUC_JP, EUC_KR, GB18030, SHIFT_JIS};
+use flate2::read::GzDecoder;
+
+/// Longest range of codes taken from one `bfrange` entry
+const MAX_RANGE_CODES: u32 = 0xFFFF;
+
+/// Orderings of the Adobe character collections with bundled Unicode tables
+const COLLECTIONS: [&str; 4] = ["GB1", "CNS1", "Japan1", "Korea1"];
+
+/// Directory of the gzipped `Adobe-<Ordering>-UCS2` CMaps, set by the app to
+/// its resources
+static COLLECTION_DIR: OnceLock<PathBuf> = OnceLock::new();
+static COLLECTION_TABLES: [OnceLock<Option<CMap>>; 4] =
+    [OnceLock::new(), OnceLock::new(), OnceLock::new(), OnceLock::new()];
+
+/// Byte ranges that split the strings of a font into character codes of
+/// one to four bytes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Codespace {
+    /// Lowest and highest value of each byte of a code
+    ranges: Vec<Vec<(u8, u8)>>,
+}
+
+impl Codespace {
+    pub fn single_byte() -> Self {
+        Self::from_bytes(&[(&[0x00], &[0xFF])])
+    }
+
+    pub fn two_byte() -> Self {
+        Self::from_bytes(&[(&[0x00, 0x00], &[0xFF, 0xFF])])
+    }
+
+    fn from_bytes(ranges: &[(&[u8], &[u8])]) -> Self {
+        let mut codespace = Self::default();
+        for (low, high) in ranges {
+            codespace.add(low, high);
+        }
+        codespace
+    }
+
+    fn add(&mut self, low: &[u8], high: &[u8]) {
+        if low.len() == high.len() && (1..=4).contains(&low.len()) {
+            self.ranges.push(low.iter().zip(high).map(|(&low, &high)| (low, high)).collect());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Split a string into codes and their lengths in bytes. A code is the
+    /// shortest run of bytes that lies in a range; bytes that match none
+    /// make a code as long as the shortest range.
+    pub fn split(&self, bytes: &[u8]) -> Vec<(u32, usize)> {
+        let shortest = self.ranges.iter().map(Vec::len).min().unwrap_or(1);
+        let mut codes = Vec::new();
+        let mut index = 0;
+        while index < bytes.len() {
+            let rest = &bytes[index..];
+            let len = (1..=rest.len().min(4))
+                .find(|&len| {
+                    self.ranges.iter().any(|range| {
+                        range.len() == len && range.iter().zip(rest).all(|(&(low, high), byte)| (low..=high).contains(byte))
+                    })
+                })
+                .unwrap_or(shortest.min(rest.len()));
+            codes.push((code_value(&rest[..len]), len));
+            index += len;
+        }
+        codes
+    }
+}
+
+/// An embedded CMap: the codespace of a font's strings with the CIDs
+/// (`cidchar`, `cidrange`) or the Unicode text (`bfchar`, `bfrange`) of
+/// its codes, as Type0 `/Encoding` and `/ToUnicode` streams hold them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CMap {
+    pub codespace: Codespace,
+    /// First code, last code and the CID of the first code
+    cid_ranges: Vec<(u32, u32, u32)>,
+    unicode: HashMap<u32, String>,
+    /// Predefined CMap named by `usecmap`, whose mappings this one extends
+    pub parent: Option<String>,
+}
+
+impl CMap {
+    /// Read a CMap file; anything it doesn't understand is skipped
+    pub fn parse(data: &[u8]) -> Self {
+        let mut cmap = Self::default();
+        let mut operands = Vec::new();
+        for token in tokenize(data) {
+            let Token::Keyword(keyword) = token else {
+                operands.push(token);
+                continue;
+            };
+            match keyword.as_str() {
+                "endcodespacerange" => {
+                    for pair in operands.chunks_exact(2) {
+                        if let [Token::Hex(low), Token::Hex(high)] = pair {
+                            cmap.codespace.add(low, high);
+                        }
+                    }
+                }
+                "endcidchar" => {
+                    for pair in operands.chunks_exact(2) {
+                        if let [Token::Hex(code), Token::Number(cid)] = pair {
+                            let code = code_value(code);
+                            cmap.cid_ranges.push((code, code, *cid as u32));
+                        }
+                    }
+                }
+                "endcidrange" => {
+                    for entry in operands.chunks_exact(3) {
+                        if let [Token::Hex(low), Token::Hex(high), Token::Number(cid)] = entry {
+                            cmap.cid_ranges.push((code_value(low), code_value(high), *cid as u32));
+                        }
+                    }
+                }
+                "endbfchar" => {
+                    for pair in operands.chunks_exact(2) {
+                        if let [Token::Hex(code), Token::Hex(text)] = pair {
+                            cmap.unicode.insert(code_value(code), utf16_text(text));
+                        }
+                    }
+                }
+                "endbfrange" => {
+                    for entry in operands.chunks_exact(3) {
+                        let [Token::Hex(low), Token::Hex(high), target] = entry else {
+                            continue;
+                        };
+                        let (low, high) = (code_value(low), code_value(high));
+                        let codes = low..=high.min(low.saturating_add(MAX_RANGE_CODES));
+                        match target {
+                            // Consecutive codes map to consecutive characters
+                            Token::Hex(text) => {
+                                for (offset, code) in codes.enumerate() {
+                                    cmap.unicode.insert(code, utf16_text(&offset_last_unit(text, offset as u32)));
+                                }
+                            }
+                            // Or each code to its own string
+                            Token::Array(texts) => {
+                                for (code, text) in codes.zip(texts) {
+                                    if let Token::Hex(text) = text {
+                                        cmap.unicode.insert(code, utf16_text(text));
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                "usecmap" => {
+                    if let Some(Token::Name(name)) = operands.last() {
+                        cmap.parent = Some(name.clone());
+                    }
+                }
+                _ => {}
+            }
+            operands.clear();
+        }
+        cmap
+    }
+
+    /// Unicode text of the code `code`
+    pub fn unicode(&self, code: u32) -> Option<&str> {
+        self.unicode.get(&code).map(String::as_str)
+    }
+
+    /// CID of the code `code`
+    pub fn cid(&self, code: u32) -> Option<u32> {
+        self.cid_ranges
+            .iter()
+            .rev()
+            .find(|(low, high, _)| (low..=high).contains(&&code))
+            .map(|(low, _, cid)| cid + (code - low))
+    }
+
+    pub fn has_unicode(&self) -> bool {
+        !self.unicode.is_empty()
+    }
+}
+
+/// A CMap that PDF readers know by name, such as `GBK-EUC-H`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredefinedCMap {
+    pub codespace: Codespace,
+    codes: PredefinedCodes,
+}
+
+/// What the codes of a predefined CMap are
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PredefinedCodes {
+    /// Two-byte CIDs, as with `Identity-H`
+    Identity,
+    Ucs2,
+    Utf16,
+    Utf8,
+    Utf32,
+    /// A national character set of one of the CJK collections
+    Charset(&'static Encoding),
+}
+
+/// First and last code of a codespace range
+type CodeRange = (&'static [u8], &'static [u8]);
+
+impl PredefinedCMap {
+    /// The CMap `name` in horizontal or vertical form. Besides the Identity
+    /// and Unicode CMaps these are the GB, Big Five, Shift-JIS, EUC-JP and
+    /// KS X 1001 encodings of the Adobe-GB1, CNS1, Japan1 and Korea1
+    /// collections.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let base = name.strip_suffix("-H").or_else(|| name.strip_suffix("-V"))?;
+        let one_byte: CodeRange = (&[0x00], &[0x80]);
+        let (codes, ranges): (PredefinedCodes, Vec<CodeRange>) = if base == "Identity" {
+            (PredefinedCodes::Identity, vec![(&[0x00, 0x00], &[0xFF, 0xFF])])
+        } else if base.starts_with("Uni") && base.contains("UCS2") {
+            (PredefinedCodes::Ucs2, vec![(&[0x00, 0x00], &[0xFF, 0xFF])])
+        } else if base.starts_with("Uni") && base.contains("UTF16") {
+            let ranges: Vec<CodeRange> = vec![
+                (&[0x00, 0x00], &[0xD7, 0xFF]),
+                (&[0xD8, 0x00, 0xDC, 0x00], &[0xDB, 0xFF, 0xDF, 0xFF]),
+                (&[0xE0, 0x00], &[0xFF, 0xFF]),
+            ];
+            (PredefinedCodes::Utf16, ranges)
+        } else if base.starts_with("Uni") && base.contains("UTF8") {
+            let ranges: Vec<CodeRange> = vec![
+                (&[0x00], &[0x7F]),
+                (&[0xC2, 0x80], &[0xDF, 0xBF]),
+                (&[0xE0, 0x80, 0x80], &[0xEF, 0xBF, 0xBF]),
+                (&[0xF0, 0x80, 0x80, 0x80], &[0xF4, 0xBF, 0xBF, 0xBF]),
+            ];
+            (PredefinedCodes::Utf8, ranges)
+        } else if base.starts_with("Uni") && base.contains("UTF32") {
+            (PredefinedCodes::Utf32, vec![(&[0x00, 0x00, 0x00, 0x00], &[0x00, 0x10, 0xFF, 0xFF])])
+        } else if base.starts_with("GB") {
+            let mut ranges = vec![one_byte, (&[0x81, 0x40], &[0xFE, 0xFE])];
+            if base.starts_with("GBK2K") {
+                ranges.push((&[0x81, 0x30, 0x81, 0x30], &[0xFE, 0x39, 0xFE, 0x39]));
+            }
+            (PredefinedCodes::Charset(GB18030), ranges)
+        } else if base.starts_with("B5") || base.ends_with("-B5") {
+            (PredefinedCodes::Charset(BIG5), vec![one_byte, (&[0x81, 0x40], &[0xFE, 0xFE])])
+        } else if base.contains("RKSJ") {
+            let ranges: Vec<CodeRange> = vec![
+                one_byte,
+                (&[0xA0], &[0xDF]),
+                (&[0x81, 0x40], &[0x9F, 0xFC]),
+                (&[0xE0, 0x40], &[0xFC, 0xFC]),
+            ];
+            (PredefinedCodes::Charset(SHIFT_JIS), ranges)
+        } else if base == "EUC" {
+            let ranges: Vec<CodeRange> = vec![
+                one_byte,
+                (&[0x8E, 0xA0], &[0x8E, 0xDF]),
+                (&[0xA1, 0xA1], &[0xFE, 0xFE]),
+                (&[0x8F, 0xA1, 0xA1], &[0x8F, 0xFE, 0xFE]),
+            ];
+            (PredefinedCodes::Charset(EUC_JP), ranges)
+        } else if base.starts_with("KSC") {
+            (PredefinedCodes::Charset(EUC_KR), vec![one_byte, (&[0x81, 0x41], &[0xFE, 0xFE])])
+        } else {
+            return None;
+        };
+        Some(Self {
+            codespace: Codespace::from_bytes(&ranges),
+            codes,
+        })
+    }
+
+    /// Codes are CIDs, as with `Identity-H`, rather than characters
+    pub fn is_identity(&self) -> bool {
+        self.codes == PredefinedCodes::Identity
+    }
+
+    /// Unicode text of a code `len` bytes long
+    pub fn unicode(&self, code: u32, len: usize) -> Option<String> {
+        let bytes = &code.to_be_bytes()[4 - len.clamp(1, 4)..];
+        match self.codes {
+            PredefinedCodes::Identity => None,
+            PredefinedCodes::Ucs2 | PredefinedCodes::Utf32 => char::from_u32(code).map(String::from),
+            PredefinedCodes::Utf16 => String::from_utf16(
+                &bytes.chunks(2).map(|pair| u16::from(pair[0]) << 8 | u16::from(pair.get(1).copied().unwrap_or(0))).collect::<Vec<_>>(),
+            )
+            .ok(),
+            PredefinedCodes::Utf8 => std::str::from_utf8(bytes).ok().map(String::from),
+            PredefinedCodes::Charset(encoding) => {
+                encoding.decode_without_bom_handling_and_without_replacement(bytes).map(|text| text.into_owned())
+            }
+        }
+    }
+}
+
+/// Use the collection tables in `dir`; only the first call counts
+pub fn set_collection_dir(dir: PathBuf) {
+    let _ = COLLECTION_DIR.set(dir);
+}
+
+/// Unicode of a CID of the Adobe-GB1, CNS1, Japan1 or Korea1 collection,
+/// read from the collection's `Adobe-<Ordering>-UCS2` table. Without the
+/// table only CIDs 1 to 95 decode: every collection begins with the printable
+/// ASCII characters.
+pub fn collection_unicode(ordering: &str, cid: u32) -> Option<String> {
+    let index = COLLECTIONS.iter().position(|name| *name == ordering)?;
+    let table = COLLECTION_TABLES[index].get_or_init(|| match collection_dir() {
+        Some(dir) => load_collection_table(dir, ordering),
+        None => {
+            log::warn!("No directory set for the Unicode table of Adobe-{}", ordering);
+            None
+        }
+    });
+    match table.as_ref().and_then(|table| table.unicode(cid)) {
+        Some(text) => Some(text.to_string()),
+        None => (1..=95).contains(&cid).then(|| char::from(0x1F + cid as u8).to_string()),
+    }
+}
+
+#[cfg(not(test))]
+fn collection_dir() -> Option<&'static PathBuf> {
+    COLLECTION_DIR.get()
+}
+
+/// Tests read the tables of the source tree
+#[cfg(test)]
+fn collection_dir() -> Option<&'static PathBuf> {
+    Some(COLLECTION_DIR.get_or_init(|| PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/cmaps"))))
+}
+
+/// Read the gzipped `Adobe-<ordering>-UCS2.gz` CMap of `dir`, which maps
+/// the collection's CIDs to Unicode
+pub fn load_collection_table(dir: &Path, ordering: &str) -> Option<CMap> {
+    let path = dir.join(format!("Adobe-{}-UCS2.gz", ordering));
+    let mut data = Vec::new();
+    if let Err(e) = File::open(&path).and_then(|file| GzDecoder::new(file).read_to_end(&mut data)) {
+        log::warn!("No Unicode table for Adobe-{} at {}: {}", ordering, path.display(), e);
+        return None;
+    }
+    Some(CMap::parse(&data)).filter(CMap::has_unicode)
+}
+
+/// The parts of PostScript syntax that CMap files use
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Hex(Vec<u8>),
+    Number(f64),
+    Name(String),
+    Keyword(String),
+    Array(Vec<Token>),
+    Other,
+}
+
+fn tokenize(data: &[u8]) -> Vec<Token> {
+    let is_delimiter = |byte: u8| b"()<>[]{}/%".contains(&byte) || byte.is_ascii_whitespace();
+    // Arrays being read, innermost last
+    let mut stack: Vec<Vec<Token>> = vec![Vec::new()];
+    let mut index = 0;
+    while index < data.len() {
+        let byte = data[index];
+        let token = match byte {
+            _ if byte.is_ascii_whitespace() => {
+                index += 1;
+                continue;
+            }
+            b'%' => {
+                while index < data.len() && !matches!(data[index], b'\r' | b'\n') {
+                    index += 1;
+                }
+                continue;
+            }
+            b'[' => {
+                stack.push(Vec::new());
+                index += 1;
+                continue;
+            }
+            b']' => {
+                index += 1;
+                if stack.len() == 1 {
+                    continue;
+                }
+                Token::Array(stack.pop().unwrap_or_default())
+            }
+            b'<' | b'>' if data.get(index + 1) == Some(&byte) => {
+                index += 2;
+                Token::Other
+            }
+            b'<' => {
+                let end = data[index..].iter().position(|&b| b == b'>').map_or(data.len(), |end| index + end);
+                let digits: Vec<u8> = data[index + 1..end]
+                    .iter()
+                    .filter_map(|&b| (b as char).to_digit(16).map(|digit| digit as u8))
+                    .collect();
+                index = end + 1;
+                // An odd last digit is followed by an implied 0
+                Token::Hex(digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)).collect())
+            }
+            b'(' => {
+                // Literal strings only appear in the CIDSystemInfo header
+                let mut depth = 0;
+                while index < data.len() {
+                    match data[index] {
+                        b'\\' => index += 1,
+                        b'(' => depth += 1,
+                        b')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    index += 1;
+                }
+                index += 1;
+                Token::Other
+            }
+            _ => {
+                let start = index;
+                index += 1;
+                while index < data.len() && !is_delimiter(data[index]) {
+                    index += 1;
+                }
+                let word = String::from_utf8_lossy(&data[start..index]).into_owned();
+                if let Some(name) = word.strip_prefix('/') {
+                    Token::Name(name.to_string())
+                } else if let Ok(number) = word.parse() {
+                    Token::Number(number)
+                } else if word.len() == 1 && is_delimiter(byte) {
+                    Token::Other
+                } else {
+                    Token::Keyword(word)
+                }
+            }
+        };
+        if let Some(tokens) = stack.last_mut() {
+            tokens.push(token);
+        }
+    }
+    // Arrays left open at the end are dropped
+    stack.truncate(1);
+    stack.pop().unwrap_or_default()
+}
+
+/// Big-endian value of a code of up to four bytes
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |value, &byte| value << 8 | u32::from(byte))
+}
+
+/// `text` with its last UTF-16 unit increased by `offset`, as consecutive
+/// codes of a `bfrange` map to
+fn offset_last_unit(text: &[u8], offset: u32) -> Vec<u8> {
+    let mut text = text.to_vec();
+    let len = text.len();
+    if len >= 2 {
+        let last = (u32::from(text[len - 2]) << 8 | u32::from(text[len - 1])).wrapping_add(offset);
+        text[len - 2] = (last >> 8) as u8;
+        text[len - 1] = last as u8;
+    } else if len == 1 {
+        text[0] = text[0].wrapping_add(offset as u8);
+    }
+    text
+}
+
+/// Decode UTF-16BE, as ToUnicode destinations are written
+fn utf16_text(bytes: &[u8]) -> String {
+    if bytes.len() == 1 {
+        return char::from(bytes[0]).to_string();
+    }
+    let units: Vec<u16> = bytes.chunks(2).map(|pair| u16::from(pair[0]) << 8 | u16::from(pair.get(1).copied().unwrap_or(0))).collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_to_unicode_cmap() {
+        let cmap = CMap::parse(
+            b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap\n\
+              /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+              1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+              2 beginbfchar <0003> <0020> <0a2f> <4E2D6587> endbfchar\n\
+              2 beginbfrange <0010> <0012> <0041>\n\
+              <0100> <0102> [<7B2C> <4E00> <D835DC00>] endbfrange\n\
+              endcmap CMapName currentdict /CMap defineresource pop end end",
+        );
+        assert_eq!(cmap.unicode(0x03), Some(" "));
+        assert_eq!(cmap.unicode(0x0A2F), Some("中文"));
+        assert_eq!(cmap.unicode(0x10), Some("A"));
+        assert_eq!(cmap.unicode(0x12), Some("C"));
+        assert_eq!(cmap.unicode(0x13), None);
+        assert_eq!(cmap.unicode(0x100), Some("第"));
+        assert_eq!(cmap.unicode(0x101), Some("一"));
+        // Surrogate pairs give one character outside the BMP
+        assert_eq!(cmap.unicode(0x102), Some("𝐀"));
+        assert_eq!(cmap.codespace.split(&[0x00, 0x03, 0x0A, 0x2F]), vec![(0x03, 2), (0x0A2F, 2)]);
+    }
+
+    #[test]
+    fn test_mixed_length_codespace_and_cid_ranges() {
+        let cmap = CMap::parse(
+            b"/GBK-EUC-H usecmap\n\
+              2 begincodespacerange <00> <80> <8140> <FEFE> endcodespacerange\n\
+              1 begincidchar <41> 34 endcidchar\n\
+              1 begincidrange <b0a1> <b0fe> 814 endcidrange",
+        );
+        assert_eq!(cmap.parent.as_deref(), Some("GBK-EUC-H"));
+        assert_eq!(cmap.codespace.split(b"A\xB0\xA2\x81"), vec![(0x41, 1), (0xB0A2, 2), (0x81, 1)]);
+        assert_eq!(cmap.cid(0x41), Some(34));
+        assert_eq!(cmap.cid(0xB0A2), Some(815));
+        assert_eq!(cmap.cid(0x42), None);
+    }
+
+    #[test]
+    fn test_predefined_cmaps_decode_their_charsets() {
+        let decode = |name: &str, bytes: &[u8]| -> String {
+            let cmap = PredefinedCMap::by_name(name).unwrap();
+            cmap.codespace
+                .split(bytes)
+                .into_iter()
+                .map(|(code, len)| cmap.unicode(code, len).unwrap_or_else(|| "\u{FFFD}".to_string()))
+                .collect()
+        };
+        assert_eq!(decode("GBK-EUC-H", b"A\xD6\xD0\xCE\xC4"), "A中文");
+        assert_eq!(decode("ETen-B5-H", b"\xA4\xA4\xA4\xE5"), "中文");
+        assert_eq!(decode("90ms-RKSJ-V", b"\x93\xFA\x96\x7B\xB1"), "日本ｱ");
+        assert_eq!(decode("KSCms-UHC-H", b"\xC7\xD1\xB1\xB9"), "한국");
+        assert_eq!(decode("UniGB-UTF16-H", b"\x4E\x2D\xD8\x35\xDC\x00"), "中𝐀");
+        assert_eq!(decode("UniJIS-UTF8-H", "日本".as_bytes()), "日本");
+        assert!(PredefinedCMap::by_name("Identity-H").unwrap().is_identity());
+        assert_eq!(PredefinedCMap::by_name("Identity-V").unwrap().unicode(0x4E2D, 2), None);
+        assert!(PredefinedCMap::by_name("CNS-EUC-H").is_none());
+
+        assert_eq!(collection_unicode("GB1", 34).as_deref(), Some("A"));
+        assert_eq!(collection_unicode("Identity", 34), None);
+    }
+
+    #[test]
+    fn test_collection_tables_map_cids() {
+        let dir = tempfile::tempdir().unwrap();
+        // A made-up table laid out like Adobe-GB1-UCS2: CIDs as codes of two
+        // bytes mapped to UTF-16
+        let table = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap\n\
+            /CMapName /Adobe-GB1-UCS2 def /CMapType 2 def\n\
+            1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+            2 beginbfrange <0001> <005F> <0020> <0060> <0062> <3000> endbfrange\n\
+            2 beginbfchar <0334> <554A> <1E8E> <D840DC00> endbfchar\n\
+            endcmap CMapName currentdict /CMap defineresource pop end end";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        std::io::Write::write_all(&mut encoder, table).unwrap();
+        std::fs::write(dir.path().join("Adobe-GB1-UCS2.gz"), encoder.finish().unwrap()).unwrap();
+
+        let gb1 = load_collection_table(dir.path(), "GB1").unwrap();
+        assert_eq!(gb1.unicode(34), Some("A"));
+        assert_eq!(gb1.unicode(97), Some("\u{3001}"));
+        assert_eq!(gb1.unicode(820), Some("啊"));
+        assert_eq!(gb1.unicode(7822), Some("\u{20000}"));
+        assert_eq!(gb1.unicode(821), None);
+        assert!(load_collection_table(dir.path(), "Japan1").is_none());
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A calendar date and time in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        Self::from_unix(seconds)
+    }
+
+    /// Convert seconds since 1970-01-01 using Howard Hinnant's civil date
+    /// algorithm
+    pub fn from_unix(seconds: i64) -> Self {
+        let days = seconds.div_euclid(86_400);
+        let time = seconds.rem_euclid(86_400) as u32;
+
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: time / 3600,
+            minute: time / 60 % 60,
+            second: time % 60,
+        }
+    }
+
+    /// `2024-02-29`
+    pub fn date_string(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// PDF date string, `D:20240229120000Z`
+    pub fn to_pdf(&self) -> String {
+        format!(
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+
+    /// ISO 8601 as used in XMP, `2024-02-29T12:00:00Z`
+    pub fn to_xmp(&self) -> String {
+        format!(
+            "{}T{:02}:{:02}:{:02}Z",
+            self.date_string(),
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+/// Convert a PDF date string such as `D:20240229123456+02'00'` to ISO 8601,
+/// `2024-02-29T12:34:56+02:00`. Fields left out at the end take their
+/// earliest value; a date without a time zone stays without one.
+pub fn pdf_date_to_iso(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = value.strip_prefix("D:").unwrap_or(value);
+    let digits = value.bytes().take_while(u8::is_ascii_digit).count();
+    if !(4..=14).contains(&digits) || digits % 2 != 0 {
+        return None;
+    }
+    let field = |start: usize, default: u32| value.get(start..start + 2).filter(|_| start < digits).map_or(Some(default), |v| v.parse().ok());
+    let (month, day) = (field(4, 1)?, field(6, 1)?);
+    let (hour, minute, second) = (field(8, 0)?, field(10, 0)?, field(12, 0)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let zone = &value[digits..];
+    let zone = match zone.chars().next() {
+        None => String::new(),
+        Some('Z') => "Z".to_string(),
+        Some(sign @ ('+' | '-')) => {
+            let offset: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            format!("{}{}:{}", sign, offset.get(..2)?, offset.get(2..4).unwrap_or("00"))
+        }
+        Some(_) => return None,
+    };
+    Some(format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        &value[..4],
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        zone
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_unix() {
+        assert_eq!(DateTime::from_unix(0).date_string(), "1970-01-01");
+        let leap_day = DateTime::from_unix(19_782 * 86_400 + 12 * 3600 + 34 * 60 + 56);
+        assert_eq!(leap_day.to_pdf(), "D:20240229123456Z");
+        assert_eq!(leap_day.to_xmp(), "2024-02-29T12:34:56Z");
+        assert_eq!(DateTime::from_unix(-1).to_xmp(), "1969-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_pdf_date_to_iso() {
+        assert_eq!(pdf_date_to_iso("D:20240229123456+02'00'").unwrap(), "2024-02-29T12:34:56+02:00");
+        assert_eq!(pdf_date_to_iso("D:20240229123456Z").unwrap(), "2024-02-29T12:34:56Z");
+        assert_eq!(pdf_date_to_iso("D:199812").unwrap(), "1998-12-01T00:00:00");
+        assert_eq!(pdf_date_to_iso("D:20240229123456-0530").unwrap(), "2024-02-29T12:34:56-05:30");
+        assert_eq!(pdf_date_to_iso("yesterday"), None);
+        assert_eq!(pdf_date_to_iso("D:20241301"), None);
+    }
+}
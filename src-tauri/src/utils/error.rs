@@ -5,6 +5,9 @@ pub enum AppError {
     #[error("PDF error: {0}")]
     PdfError(String),
 
+    #[error("Encrypted document: {0}")]
+    EncryptedDocument(String),
+
     #[error("File not found: {0}")]
     FileNotFound(String),
 
@@ -1,2 +1,8 @@
+pub mod cmap;
+pub mod date;
 pub mod error;
+pub mod page_range;
+pub mod path;
+pub mod pdf_font;
+pub mod pdf_writer;
 pub mod progress;
@@ -0,0 +1,73 @@
+use crate::utils::error::AppError;
+
+/// Parse a page range string such as `"1-3,7,10-"` into 1-based page numbers.
+///
+/// Items are separated by commas. `a-b` selects a span (descending when
+/// `a > b`), `a-` runs to the last page and `-b` starts at the first one.
+/// Order and duplicates are preserved.
+pub fn parse_page_range(spec: &str, page_count: usize) -> Result<Vec<usize>, AppError> {
+    let mut pages = Vec::new();
+    for item in spec.split(',').map(str::trim) {
+        if item.is_empty() {
+            continue;
+        }
+        let (start, end) = match item.split_once('-') {
+            Some((start, end)) => (
+                parse_page_number(start, 1, page_count, item)?,
+                parse_page_number(end, page_count, page_count, item)?,
+            ),
+            None => {
+                let page = parse_page_number(item, 0, page_count, item)?;
+                (page, page)
+            }
+        };
+        if start <= end {
+            pages.extend(start..=end);
+        } else {
+            pages.extend((end..=start).rev());
+        }
+    }
+
+    if pages.is_empty() {
+        return Err(AppError::ConfigError(format!("Page range \"{}\" selects no pages", spec)));
+    }
+    Ok(pages)
+}
+
+fn parse_page_number(value: &str, default: usize, page_count: usize, item: &str) -> Result<usize, AppError> {
+    let value = value.trim();
+    let page = if value.is_empty() {
+        default
+    } else {
+        value
+            .parse()
+            .map_err(|_| AppError::ConfigError(format!("Invalid page range \"{}\"", item)))?
+    };
+    if page == 0 || page > page_count {
+        return Err(AppError::ConfigError(format!(
+            "Page range \"{}\" is outside 1-{}",
+            item, page_count
+        )));
+    }
+    Ok(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_page_range() {
+        assert_eq!(parse_page_range("1-3,7,10-", 12).unwrap(), vec![1, 2, 3, 7, 10, 11, 12]);
+        assert_eq!(parse_page_range(" -2 , 5-4, 2", 5).unwrap(), vec![1, 2, 5, 4, 2]);
+        assert_eq!(parse_page_range("-", 3).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_page_range_rejects_invalid_input() {
+        assert!(parse_page_range("", 5).is_err());
+        assert!(parse_page_range("0", 5).is_err());
+        assert!(parse_page_range("4-6", 5).is_err());
+        assert!(parse_page_range("two", 5).is_err());
+    }
+}
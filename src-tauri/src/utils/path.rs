@@ -0,0 +1,175 @@
+use ttf_parser::OutlineBuilder;
+
+/// Straight segments each curve is split into
+const CURVE_SEGMENTS: usize = 12;
+
+/// A path of straight lines, with curves flattened as they are added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    pub contours: Vec<Contour>,
+}
+
+/// One subpath: its points in drawing order and whether it was closed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contour {
+    pub points: Vec<(f32, f32)>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn is_empty(&self) -> bool {
+        self.contours.iter().all(|contour| contour.points.len() < 2)
+    }
+
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        if self.contours.last().is_some_and(|contour| contour.points.len() == 1) {
+            self.contours.pop();
+        }
+        self.contours.push(Contour {
+            points: vec![(x, y)],
+            closed: false,
+        });
+    }
+
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        match self.contours.last_mut() {
+            Some(contour) if !contour.closed => contour.points.push((x, y)),
+            // A segment after a close starts where the closed subpath did
+            Some(contour) => {
+                let start = contour.points[0];
+                self.contours.push(Contour {
+                    points: vec![start, (x, y)],
+                    closed: false,
+                });
+            }
+            None => self.move_to(x, y),
+        }
+    }
+
+    /// A cubic Bézier curve from the current point
+    pub fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let Some(&(x0, y0)) = self.current_point() else {
+            return self.move_to(x, y);
+        };
+        for step in 1..=CURVE_SEGMENTS {
+            let t = step as f32 / CURVE_SEGMENTS as f32;
+            let s = 1.0 - t;
+            let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+            self.line_to(a * x0 + b * x1 + c * x2 + d * x, a * y0 + b * y1 + c * y2 + d * y);
+        }
+    }
+
+    /// A quadratic Bézier curve from the current point, as TrueType glyphs use
+    pub fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let Some(&(x0, y0)) = self.current_point() else {
+            return self.move_to(x, y);
+        };
+        let control = |start: f32, control: f32| start + 2.0 / 3.0 * (control - start);
+        self.curve_to(control(x0, x1), control(y0, y1), control(x, x1), control(y, y1), x, y);
+    }
+
+    pub fn close(&mut self) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.closed = true;
+        }
+    }
+
+    /// A closed rectangle, as the `re` operator adds
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.move_to(x, y);
+        self.line_to(x + width, y);
+        self.line_to(x + width, y + height);
+        self.line_to(x, y + height);
+        self.close();
+    }
+
+    pub fn current_point(&self) -> Option<&(f32, f32)> {
+        let contour = self.contours.last()?;
+        match contour.closed {
+            true => contour.points.first(),
+            false => contour.points.last(),
+        }
+    }
+
+    /// The path with every point mapped through `matrix`
+    pub fn transform(&self, matrix: &[f32; 6]) -> Path {
+        let contours = self
+            .contours
+            .iter()
+            .map(|contour| Contour {
+                points: contour
+                    .points
+                    .iter()
+                    .map(|&(x, y)| {
+                        (
+                            matrix[0] * x + matrix[2] * y + matrix[4],
+                            matrix[1] * x + matrix[3] * y + matrix[5],
+                        )
+                    })
+                    .collect(),
+                closed: contour.closed,
+            })
+            .collect();
+        Path { contours }
+    }
+
+    /// Edges of the area a fill covers: every contour, closed or not
+    pub fn edges(&self) -> impl Iterator<Item = ((f32, f32), (f32, f32))> + '_ {
+        self.contours.iter().flat_map(|contour| {
+            let points = &contour.points;
+            (0..points.len()).map(move |index| (points[index], points[(index + 1) % points.len()]))
+        })
+    }
+
+    /// The outline of the path drawn with lines `width` wide, as a path to
+    /// fill with the nonzero rule
+    pub fn stroke(&self, width: f32) -> Path {
+        let half = width / 2.0;
+        let mut outline = Path::default();
+        for contour in &self.contours {
+            let points = &contour.points;
+            let segments = match contour.closed {
+                true => points.len(),
+                false => points.len().saturating_sub(1),
+            };
+            for index in 0..segments {
+                let ((x0, y0), (x1, y1)) = (points[index], points[(index + 1) % points.len()]);
+                let length = (x1 - x0).hypot(y1 - y0);
+                if length == 0.0 {
+                    continue;
+                }
+                // Extended by half the width at both ends so joins have no gaps
+                let (dx, dy) = ((x1 - x0) / length * half, (y1 - y0) / length * half);
+                let (nx, ny) = (-dy, dx);
+                outline.move_to(x0 - dx + nx, y0 - dy + ny);
+                outline.line_to(x0 - dx - nx, y0 - dy - ny);
+                outline.line_to(x1 + dx - nx, y1 + dy - ny);
+                outline.line_to(x1 + dx + nx, y1 + dy + ny);
+                outline.close();
+            }
+        }
+        outline
+    }
+}
+
+impl OutlineBuilder for Path {
+    fn move_to(&mut self, x: f32, y: f32) {
+        Path::move_to(self, x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        Path::line_to(self, x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        Path::quad_to(self, x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        Path::curve_to(self, x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        Path::close(self);
+    }
+}
@@ -0,0 +1,757 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId};
+use ttf_parser::{cff, cmap, Face, GlyphId, PlatformId, RawFace, Tag};
+
+use crate::utils::cmap::{collection_unicode, CMap, Codespace, PredefinedCMap};
+use crate::utils::path::Path;
+
+/// Font descriptor flags
+const FLAG_ITALIC: i64 = 1 << 6;
+const FLAG_FORCE_BOLD: i64 = 1 << 18;
+/// Weight from which a font counts as bold
+const BOLD_WEIGHT: f32 = 600.0;
+
+/// Glyph names of `/Differences` arrays that aren't a single letter or a
+/// `uniXXXX` name
+const GLYPH_NAMES: [(&str, char); 123] = [
+    ("space", ' '), ("exclam", '!'), ("quotedbl", '"'), ("numbersign", '#'), ("dollar", '$'),
+    ("percent", '%'), ("ampersand", '&'), ("quotesingle", '\''), ("quoteright", '\u{2019}'),
+    ("parenleft", '('), ("parenright", ')'), ("asterisk", '*'), ("plus", '+'), ("comma", ','),
+    ("hyphen", '-'), ("period", '.'), ("slash", '/'), ("zero", '0'), ("one", '1'), ("two", '2'),
+    ("three", '3'), ("four", '4'), ("five", '5'), ("six", '6'), ("seven", '7'), ("eight", '8'),
+    ("nine", '9'), ("colon", ':'), ("semicolon", ';'), ("less", '<'), ("equal", '='),
+    ("greater", '>'), ("question", '?'), ("at", '@'), ("bracketleft", '['), ("backslash", '\\'),
+    ("bracketright", ']'), ("asciicircum", '^'), ("underscore", '_'), ("grave", '`'),
+    ("quoteleft", '\u{2018}'), ("braceleft", '{'), ("bar", '|'), ("braceright", '}'),
+    ("asciitilde", '~'), ("exclamdown", '¡'), ("cent", '¢'), ("sterling", '£'), ("yen", '¥'),
+    ("section", '§'), ("dieresis", '¨'), ("copyright", '©'), ("ordfeminine", 'ª'),
+    ("guillemotleft", '«'), ("logicalnot", '¬'), ("registered", '®'), ("macron", '¯'),
+    ("degree", '°'), ("plusminus", '±'), ("acute", '´'), ("mu", 'µ'), ("paragraph", '¶'),
+    ("periodcentered", '·'), ("cedilla", '¸'), ("ordmasculine", 'º'), ("guillemotright", '»'),
+    ("questiondown", '¿'), ("multiply", '×'), ("divide", '÷'), ("germandbls", 'ß'),
+    ("Agrave", 'À'), ("Aacute", 'Á'), ("Adieresis", 'Ä'), ("Ccedilla", 'Ç'), ("Egrave", 'È'),
+    ("Eacute", 'É'), ("Ntilde", 'Ñ'), ("Odieresis", 'Ö'), ("Oslash", 'Ø'), ("Udieresis", 'Ü'),
+    ("agrave", 'à'), ("aacute", 'á'), ("acircumflex", 'â'), ("adieresis", 'ä'), ("aring", 'å'),
+    ("ae", 'æ'), ("ccedilla", 'ç'), ("egrave", 'è'), ("eacute", 'é'), ("ecircumflex", 'ê'),
+    ("edieresis", 'ë'), ("igrave", 'ì'), ("iacute", 'í'), ("icircumflex", 'î'), ("idieresis", 'ï'),
+    ("ntilde", 'ñ'), ("ograve", 'ò'), ("oacute", 'ó'), ("ocircumflex", 'ô'), ("odieresis", 'ö'),
+    ("oslash", 'ø'), ("ugrave", 'ù'), ("uacute", 'ú'), ("ucircumflex", 'û'), ("udieresis", 'ü'),
+    ("endash", '\u{2013}'), ("emdash", '\u{2014}'), ("quotedblleft", '\u{201C}'),
+    ("quotedblright", '\u{201D}'), ("quotesinglbase", '\u{201A}'), ("quotedblbase", '\u{201E}'),
+    ("dagger", '\u{2020}'), ("daggerdbl", '\u{2021}'), ("bullet", '\u{2022}'),
+    ("ellipsis", '\u{2026}'), ("perthousand", '\u{2030}'), ("trademark", '\u{2122}'),
+    ("minus", '\u{2212}'), ("fi", '\u{FB01}'), ("fl", '\u{FB02}'), ("Euro", '\u{20AC}'),
+    ("nbspace", '\u{A0}'), ("sfthyphen", '\u{AD}'),
+];
+
+/// Fonts already read, by the object holding their dictionary
+pub type FontCache = HashMap<ObjectId, Rc<PdfFont>>;
+
+/// What the codes of a font's strings stand for and how far each one moves
+/// the pen
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfFont {
+    /// `/BaseFont` without the tag of a subset, such as `ABCDEF+`
+    pub name: String,
+    pub bold: bool,
+    pub italic: bool,
+    /// Glyph widths in thousandths of text space units, by code for simple
+    /// fonts and by CID for Type0 fonts
+    widths: HashMap<u32, f32>,
+    missing_width: f32,
+    codespace: Codespace,
+    to_unicode: Option<CMap>,
+    encoding: FontEncoding,
+    outlines: Option<GlyphOutlines>,
+}
+
+/// The embedded font program, for drawing glyphs
+#[derive(Debug, Clone, PartialEq)]
+struct GlyphOutlines {
+    data: Vec<u8>,
+    /// A bare CFF font from `/FontFile3`, not wrapped in an OpenType font
+    bare_cff: bool,
+    /// Glyph ids by code for simple fonts and by CID for Type0 fonts; the
+    /// CIDs are glyph ids themselves when there is no table
+    glyph_ids: Option<HashMap<u32, u16>>,
+    /// Outlines already read, in units of the font size
+    cache: RefCell<HashMap<u16, Option<Rc<Path>>>>,
+}
+
+/// Meaning of the codes of a font that has no ToUnicode entry for them
+#[derive(Debug, Clone, PartialEq)]
+enum FontEncoding {
+    /// Single bytes through a base encoding changed by `/Differences`
+    Simple(Vec<Option<char>>),
+    /// Codes of a Type0 font, through its `/Encoding` CMap to characters or
+    /// to CIDs
+    Cid {
+        predefined: Option<PredefinedCMap>,
+        embedded: Option<Box<CMap>>,
+        /// `/Ordering` of the descendant's `/CIDSystemInfo`, such as `GB1`
+        ordering: String,
+        /// Characters of CIDs, from the `cmap` table of the embedded font
+        font_program: HashMap<u32, char>,
+    },
+}
+
+/// One character code of a shown string
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyph {
+    pub code: u32,
+    /// Width in thousandths of text space units
+    pub width: f32,
+    /// Unicode text of the code, when known
+    pub text: Option<String>,
+    /// Word spacing applies to the glyph, as it does to the single-byte code 32
+    pub is_space: bool,
+}
+
+impl PdfFont {
+    /// Read a font dictionary: simple fonts with `/Widths` and an encoding,
+    /// Type0 fonts with the `/W` and `/DW` of their descendant and their
+    /// `/Encoding` CMap, and the ToUnicode CMap of either
+    pub fn load(doc: &Document, font: &Dictionary) -> Self {
+        let get = |dict: &Dictionary, key: &[u8]| -> Option<Object> {
+            Some(doc.dereference(dict.get(key).ok()?).ok()?.1.clone())
+        };
+        let to_unicode = get(font, b"ToUnicode")
+            .and_then(|cmap| cmap.as_stream().ok()?.get_plain_content().ok())
+            .map(|data| CMap::parse(&data))
+            .filter(CMap::has_unicode);
+        let name = get(font, b"BaseFont")
+            .and_then(|name| Some(String::from_utf8_lossy(name.as_name().ok()?).into_owned()))
+            .unwrap_or_default();
+        let name = match name.split_once('+') {
+            Some((tag, rest)) if tag.len() == 6 && tag.bytes().all(|b| b.is_ascii_uppercase()) => rest.to_string(),
+            _ => name,
+        };
+
+        let mut widths = HashMap::new();
+        let (missing_width, descriptor, encoding, codespace, outlines);
+        if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Type0"[..]) {
+            let descendant = get(font, b"DescendantFonts")
+                .and_then(|fonts| Some(doc.dereference(fonts.as_array().ok()?.first()?).ok()?.1.as_dict().ok()?.clone()))
+                .unwrap_or_default();
+            missing_width = get(&descendant, b"DW").as_ref().and_then(|w| number(doc, w)).unwrap_or(1000.0);
+            let entries = get(&descendant, b"W").and_then(|w| w.as_array().ok().cloned()).unwrap_or_default();
+            cid_widths(doc, &entries, &mut widths);
+            descriptor = get(&descendant, b"FontDescriptor");
+
+            // A named CMap, or an embedded one that may build on a named one
+            let (mut predefined, mut embedded) = (None, None);
+            match get(font, b"Encoding") {
+                Some(Object::Name(cmap)) => predefined = PredefinedCMap::by_name(&String::from_utf8_lossy(&cmap)),
+                Some(Object::Stream(stream)) => {
+                    let cmap = stream.get_plain_content().map(|data| CMap::parse(&data)).unwrap_or_default();
+                    let parent = cmap.parent.clone().or_else(|| {
+                        let name = get(&stream.dict, b"UseCMap")?;
+                        Some(String::from_utf8_lossy(name.as_name().ok()?).into_owned())
+                    });
+                    predefined = parent.as_deref().and_then(PredefinedCMap::by_name);
+                    embedded = Some(Box::new(cmap));
+                }
+                _ => {}
+            }
+            codespace = embedded
+                .as_ref()
+                .map(|cmap| cmap.codespace.clone())
+                .filter(|codespace| !codespace.is_empty())
+                .or_else(|| predefined.as_ref().map(|cmap| cmap.codespace.clone()))
+                .unwrap_or_else(Codespace::two_byte);
+            let ordering = get(&descendant, b"CIDSystemInfo")
+                .and_then(|info| get(info.as_dict().ok()?, b"Ordering"))
+                .and_then(|ordering| Some(String::from_utf8_lossy(ordering.as_str().ok()?).into_owned()))
+                .unwrap_or_default();
+            // Only worth reading when nothing else gives the characters
+            let font_program = if to_unicode.is_none() && predefined.as_ref().map_or(true, PredefinedCMap::is_identity) {
+                font_program_unicode(doc, &descendant, descriptor.as_ref())
+            } else {
+                HashMap::new()
+            };
+            encoding = FontEncoding::Cid {
+                predefined,
+                embedded,
+                ordering,
+                font_program,
+            };
+            outlines = embedded_program(doc, descriptor.as_ref()).map(|(data, bare_cff)| {
+                let glyph_ids = match bare_cff {
+                    true => cff_cid_glyphs(&data),
+                    false => cid_to_gid(doc, &descendant).map(|gids| {
+                        gids.into_iter().enumerate().map(|(cid, gid)| (cid as u32, gid)).collect()
+                    }),
+                };
+                GlyphOutlines::new(data, bare_cff, glyph_ids)
+            });
+        } else {
+            let first_char = get(font, b"FirstChar").as_ref().and_then(|c| number(doc, c)).unwrap_or(0.0) as u32;
+            if let Some(Object::Array(list)) = get(font, b"Widths") {
+                for (offset, width) in list.iter().enumerate() {
+                    if let Some(width) = number(doc, width) {
+                        widths.insert(first_char + offset as u32, width);
+                    }
+                }
+            }
+            descriptor = get(font, b"FontDescriptor");
+            missing_width = descriptor
+                .as_ref()
+                .and_then(|descriptor| get(descriptor.as_dict().ok()?, b"MissingWidth"))
+                .as_ref()
+                .and_then(|w| number(doc, w))
+                // Standard 14 fonts come without widths
+                .unwrap_or(if widths.is_empty() { 500.0 } else { 0.0 });
+            let font_encoding = get(font, b"Encoding");
+            let table = simple_encoding(doc, font_encoding.as_ref());
+            outlines = embedded_program(doc, descriptor.as_ref()).map(|(data, bare_cff)| {
+                let glyph_ids = simple_glyphs(doc, &data, bare_cff, &table, font_encoding.as_ref());
+                GlyphOutlines::new(data, bare_cff, Some(glyph_ids))
+            });
+            encoding = FontEncoding::Simple(table);
+            codespace = Codespace::single_byte();
+        }
+
+        let descriptor = descriptor.and_then(|d| d.as_dict().ok().cloned()).unwrap_or_default();
+        let flags = get(&descriptor, b"Flags").and_then(|f| f.as_i64().ok()).unwrap_or(0);
+        let weight = get(&descriptor, b"FontWeight").as_ref().and_then(|w| number(doc, w)).unwrap_or(0.0);
+        let italic_angle = get(&descriptor, b"ItalicAngle").as_ref().and_then(|a| number(doc, a)).unwrap_or(0.0);
+        let style = name.to_ascii_lowercase();
+        let bold = flags & FLAG_FORCE_BOLD != 0
+            || weight >= BOLD_WEIGHT
+            || ["bold", "black", "heavy", "semibold", "demi"].iter().any(|word| style.contains(word));
+        let italic = flags & FLAG_ITALIC != 0
+            || italic_angle != 0.0
+            || ["italic", "oblique"].iter().any(|word| style.contains(word));
+
+        Self {
+            name,
+            bold,
+            italic,
+            widths,
+            missing_width,
+            codespace,
+            to_unicode,
+            encoding,
+            outlines,
+        }
+    }
+
+    /// Whether glyphs can be drawn from an embedded font program
+    pub fn has_outlines(&self) -> bool {
+        self.outlines.is_some()
+    }
+
+    /// Outline of the glyph of a code in units of the font size, with the
+    /// origin at the pen position; `None` for empty and missing glyphs
+    pub fn glyph_outline(&self, code: u32) -> Option<Rc<Path>> {
+        self.outlines.as_ref()?.outline(self.cid(code)?)
+    }
+
+    /// CID of a code of a Type0 font, or the code itself for simple fonts
+    fn cid(&self, code: u32) -> Option<u32> {
+        match &self.encoding {
+            FontEncoding::Simple(_) => Some(code),
+            FontEncoding::Cid { predefined, embedded, .. } => {
+                embedded.as_ref().and_then(|cmap| cmap.cid(code)).or_else(|| {
+                    // Other CMaps need the collection's tables to give CIDs
+                    predefined.as_ref().filter(|cmap| cmap.is_identity()).map(|_| code)
+                })
+            }
+        }
+    }
+
+    /// Split a shown string into character codes
+    pub fn glyphs(&self, bytes: &[u8]) -> Vec<Glyph> {
+        self.codespace
+            .split(bytes)
+            .into_iter()
+            .map(|(code, len)| {
+                let cid = self.cid(code);
+                Glyph {
+                    code,
+                    width: cid.and_then(|cid| self.widths.get(&cid)).copied().unwrap_or(self.missing_width),
+                    text: self.text(code, len, cid),
+                    is_space: len == 1 && code == 32,
+                }
+            })
+            .collect()
+    }
+
+    /// Unicode text of a code: from the ToUnicode CMap, else from the
+    /// encoding, else for CIDs from the embedded font or the collection
+    fn text(&self, code: u32, len: usize, cid: Option<u32>) -> Option<String> {
+        if let Some(text) = self.to_unicode.as_ref().and_then(|cmap| cmap.unicode(code)).filter(|text| is_meaningful(text)) {
+            return Some(text.to_string());
+        }
+        match &self.encoding {
+            FontEncoding::Simple(table) => table.get(code as usize).copied().flatten().map(String::from),
+            FontEncoding::Cid {
+                predefined,
+                ordering,
+                font_program,
+                ..
+            } => predefined
+                .as_ref()
+                .and_then(|cmap| cmap.unicode(code, len))
+                .or_else(|| {
+                    let cid = cid?;
+                    font_program.get(&cid).map(|c| c.to_string()).or_else(|| collection_unicode(ordering, cid))
+                })
+                .filter(|text| is_meaningful(text)),
+        }
+    }
+}
+
+/// Text that stands for characters, not the replacement character, private
+/// use code points or control codes that broken ToUnicode maps give
+fn is_meaningful(text: &str) -> bool {
+    !text.is_empty()
+        && text.chars().all(|c| {
+            !matches!(c as u32, 0xE000..=0xF8FF | 0xF0000..=0x10FFFF | 0xFFFD) && (!c.is_control() || c.is_whitespace())
+        })
+}
+
+/// Characters of the glyphs of an embedded TrueType font, by CID through
+/// the descendant's `/CIDToGIDMap`. Subsets often keep the Unicode `cmap`
+/// table of the font they were cut from.
+fn font_program_unicode(doc: &Document, descendant: &Dictionary, descriptor: Option<&Object>) -> HashMap<u32, char> {
+    let mut unicode = HashMap::new();
+    let Some(descriptor) = descriptor.and_then(|descriptor| descriptor.as_dict().ok()) else {
+        return unicode;
+    };
+    let program = descriptor
+        .get(b"FontFile2")
+        .ok()
+        .and_then(|program| doc.dereference(program).ok()?.1.as_stream().ok()?.get_plain_content().ok());
+    let Some(cmap) = program.as_deref().and_then(|data| {
+        let face = RawFace::parse(data, 0).ok()?;
+        cmap::Table::parse(face.table(Tag::from_bytes(b"cmap"))?)
+    }) else {
+        return unicode;
+    };
+
+    let mut by_glyph = HashMap::new();
+    for subtable in cmap.subtables.into_iter().filter(|subtable| subtable.is_unicode()) {
+        subtable.codepoints(|codepoint| {
+            if let (Some(glyph), Some(c)) = (subtable.glyph_index(codepoint), char::from_u32(codepoint)) {
+                by_glyph.entry(u32::from(glyph.0)).or_insert(c);
+            }
+        });
+    }
+    match cid_to_gid(doc, descendant) {
+        Some(gids) => {
+            for (cid, glyph) in gids.into_iter().enumerate() {
+                if let Some(&c) = by_glyph.get(&u32::from(glyph)) {
+                    unicode.insert(cid as u32, c);
+                }
+            }
+        }
+        None => unicode = by_glyph,
+    }
+    unicode
+}
+
+/// Glyph ids by CID from a stream `/CIDToGIDMap`; `None` for `/Identity`,
+/// the default
+fn cid_to_gid(doc: &Document, descendant: &Dictionary) -> Option<Vec<u16>> {
+    let (_, map) = doc.dereference(descendant.get(b"CIDToGIDMap").ok()?).ok()?;
+    let data = map.as_stream().ok()?.get_plain_content().ok()?;
+    Some(data.chunks_exact(2).map(|glyph| u16::from_be_bytes([glyph[0], glyph[1]])).collect())
+}
+
+/// The font program of a descriptor and whether it is a bare CFF font:
+/// `/FontFile2` and OpenType `/FontFile3` are read, Type 1 `/FontFile` isn't
+fn embedded_program(doc: &Document, descriptor: Option<&Object>) -> Option<(Vec<u8>, bool)> {
+    let descriptor = descriptor?.as_dict().ok()?;
+    let stream = |key: &[u8]| doc.dereference(descriptor.get(key).ok()?).ok()?.1.as_stream().ok();
+    let (data, bare_cff) = if let Some(program) = stream(b"FontFile2") {
+        (program.get_plain_content().ok()?, false)
+    } else {
+        let program = stream(b"FontFile3")?;
+        let subtype = program.dict.get(b"Subtype").and_then(Object::as_name).ok();
+        (program.get_plain_content().ok()?, subtype != Some(&b"OpenType"[..]))
+    };
+    let valid = match bare_cff {
+        true => cff::Table::parse(&data).is_some(),
+        false => Face::parse(&data, 0).is_ok(),
+    };
+    valid.then_some((data, bare_cff))
+}
+
+/// Glyph ids by CID of a CID-keyed CFF font; `None` when glyphs aren't
+/// keyed by CID
+fn cff_cid_glyphs(data: &[u8]) -> Option<HashMap<u32, u16>> {
+    let table = cff::Table::parse(data)?;
+    let glyph_ids: HashMap<u32, u16> = (0..table.number_of_glyphs())
+        .filter_map(|glyph| Some((u32::from(table.glyph_cid(GlyphId(glyph))?), glyph)))
+        .collect();
+    (!glyph_ids.is_empty()).then_some(glyph_ids)
+}
+
+/// Glyph ids of the 256 codes of a simple font. TrueType glyphs are looked up
+/// by the character of the code, then by the code in a symbol or Macintosh
+/// `cmap`; CFF glyphs by the `/Differences` name, then by the font's encoding.
+fn simple_glyphs(
+    doc: &Document,
+    data: &[u8],
+    bare_cff: bool,
+    table: &[Option<char>],
+    encoding: Option<&Object>,
+) -> HashMap<u32, u16> {
+    let mut glyph_ids = HashMap::new();
+    if bare_cff {
+        let Some(font) = cff::Table::parse(data) else {
+            return glyph_ids;
+        };
+        let names: HashMap<usize, String> = encoding
+            .and_then(|encoding| encoding.as_dict().ok()?.get(b"Differences").and_then(Object::as_array).ok())
+            .map(|differences| difference_names(doc, differences).into_iter().collect())
+            .unwrap_or_default();
+        for code in 0..=255u8 {
+            let glyph = names
+                .get(&usize::from(code))
+                .and_then(|name| font.glyph_index_by_name(name))
+                .or_else(|| font.glyph_index(code));
+            if let Some(glyph) = glyph.filter(|glyph| glyph.0 != 0) {
+                glyph_ids.insert(u32::from(code), glyph.0);
+            }
+        }
+        return glyph_ids;
+    }
+
+    let Ok(face) = Face::parse(data, 0) else {
+        return glyph_ids;
+    };
+    let subtables: Vec<cmap::Subtable> =
+        face.tables().cmap.map(|cmap| cmap.subtables.into_iter().collect()).unwrap_or_default();
+    for code in 0..=255u32 {
+        let glyph = subtables.iter().find_map(|subtable| {
+            let glyph = match (subtable.platform_id, subtable.encoding_id) {
+                (PlatformId::Windows, 0) => subtable.glyph_index(0xF000 | code).or_else(|| subtable.glyph_index(code)),
+                (PlatformId::Macintosh, 0) => subtable.glyph_index(code),
+                _ if subtable.is_unicode() => {
+                    subtable.glyph_index(u32::from(table.get(code as usize).copied().flatten()?))
+                }
+                _ => None,
+            };
+            glyph.filter(|glyph| glyph.0 != 0)
+        });
+        match glyph {
+            Some(glyph) => {
+                glyph_ids.insert(code, glyph.0);
+            }
+            // Subsets without a `cmap` number their glyphs by code
+            None if subtables.is_empty() => {
+                glyph_ids.insert(code, code as u16);
+            }
+            None => {}
+        }
+    }
+    glyph_ids
+}
+
+impl GlyphOutlines {
+    fn new(data: Vec<u8>, bare_cff: bool, glyph_ids: Option<HashMap<u32, u16>>) -> Self {
+        Self {
+            data,
+            bare_cff,
+            glyph_ids,
+            cache: RefCell::default(),
+        }
+    }
+
+    fn outline(&self, key: u32) -> Option<Rc<Path>> {
+        let glyph = match &self.glyph_ids {
+            Some(glyph_ids) => *glyph_ids.get(&key)?,
+            None => u16::try_from(key).ok()?,
+        };
+        if let Some(cached) = self.cache.borrow().get(&glyph) {
+            return cached.clone();
+        }
+        let mut path = Path::default();
+        let outline = if self.bare_cff {
+            cff::Table::parse(&self.data).and_then(|font| {
+                font.outline(GlyphId(glyph), &mut path).ok()?;
+                let m = font.matrix();
+                Some(path.transform(&[m.sx, m.ky, m.kx, m.sy, m.tx, m.ty]))
+            })
+        } else {
+            Face::parse(&self.data, 0).ok().and_then(|face| {
+                face.outline_glyph(GlyphId(glyph), &mut path)?;
+                let scale = 1.0 / f32::from(face.units_per_em());
+                Some(path.transform(&[scale, 0.0, 0.0, scale, 0.0, 0.0]))
+            })
+        };
+        let outline = outline.filter(|path| !path.is_empty()).map(Rc::new);
+        self.cache.borrow_mut().insert(glyph, outline.clone());
+        outline
+    }
+}
+
+fn number(doc: &Document, obj: &Object) -> Option<f32> {
+    doc.dereference(obj).ok().and_then(|(_, obj)| obj.as_float().ok())
+}
+
+/// Widths of a CID font's `/W` array: either `first [w1 w2 ...]` or
+/// `first last w`
+fn cid_widths(doc: &Document, entries: &[Object], widths: &mut HashMap<u32, f32>) {
+    let mut index = 0;
+    while index + 1 < entries.len() {
+        let Some(first) = number(doc, &entries[index]).map(|first| first as u32) else {
+            break;
+        };
+        match doc.dereference(&entries[index + 1]).ok().map(|(_, obj)| obj) {
+            Some(Object::Array(list)) => {
+                for (offset, width) in list.iter().enumerate() {
+                    if let Some(width) = number(doc, width) {
+                        widths.insert(first + offset as u32, width);
+                    }
+                }
+                index += 2;
+            }
+            Some(last) => {
+                let (Some(last), Some(width)) = (number(doc, last), entries.get(index + 2).and_then(|w| number(doc, w)))
+                else {
+                    break;
+                };
+                // Ranges come from the file; don't let one blow up memory
+                for code in first..=(last as u32).min(first.saturating_add(0xFFFF)) {
+                    widths.insert(code, width);
+                }
+                index += 3;
+            }
+            None => break,
+        }
+    }
+}
+
+/// Characters of the 256 codes of a simple font: the named base encoding,
+/// StandardEncoding when there is none, with `/Differences` applied
+fn simple_encoding(doc: &Document, encoding: Option<&Object>) -> Vec<Option<char>> {
+    let (base, differences) = match encoding {
+        Some(Object::Name(name)) => (Some(name.clone()), None),
+        Some(Object::Dictionary(dict)) => (
+            dict.get(b"BaseEncoding").and_then(Object::as_name).ok().map(<[u8]>::to_vec),
+            dict.get(b"Differences").and_then(Object::as_array).ok(),
+        ),
+        _ => (None, None),
+    };
+
+    // lopdf keeps its encoding tables private but hands them out for fonts
+    let lookup = Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Font".to_vec())),
+        ("Encoding", Object::Name(base.unwrap_or_else(|| b"StandardEncoding".to_vec()))),
+    ]);
+    let mut table: Vec<Option<char>> = match lookup.get_font_encoding(doc) {
+        Ok(Encoding::OneByteEncoding(table)) => {
+            table.iter().map(|unit| unit.and_then(|unit| char::from_u32(u32::from(unit)))).collect()
+        }
+        _ => (0..=255u8).map(|byte| Some(char::from(byte))).collect(),
+    };
+
+    for (code, name) in differences.map(|differences| difference_names(doc, differences)).unwrap_or_default() {
+        if let Some(slot) = table.get_mut(code) {
+            *slot = glyph_char(&name);
+        }
+    }
+    table
+}
+
+/// The codes a `/Differences` array names glyphs for, with the names
+fn difference_names(doc: &Document, differences: &[Object]) -> Vec<(usize, String)> {
+    let mut names = Vec::new();
+    let mut code = 0usize;
+    for item in differences {
+        match doc.dereference(item).map(|(_, obj)| obj) {
+            Ok(Object::Integer(start)) => code = (*start).clamp(0, 255) as usize,
+            Ok(Object::Name(name)) => {
+                names.push((code, String::from_utf8_lossy(name).into_owned()));
+                code += 1;
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Character of an Adobe glyph name
+fn glyph_char(name: &str) -> Option<char> {
+    // Suffixes such as `.sc` or `.alt` name variants of the same character
+    let name = name.split('.').next().unwrap_or(name);
+    if name.len() == 1 && name.as_bytes()[0].is_ascii_alphabetic() {
+        return name.chars().next();
+    }
+    if let Some(hex) = name.strip_prefix("uni").filter(|hex| hex.len() == 4) {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    if let Some(hex) = name.strip_prefix('u').filter(|hex| (4..=6).contains(&hex.len())) {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    GLYPH_NAMES.iter().find(|(glyph, _)| *glyph == name).map(|(_, c)| *c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::Stream;
+
+    #[test]
+    fn test_simple_font_with_differences() {
+        let mut doc = Document::with_version("1.5");
+        let descriptor = doc.add_object(Dictionary::from_iter(vec![
+            ("Flags", Object::Integer(FLAG_ITALIC)),
+            ("ItalicAngle", Object::Integer(-12)),
+        ]));
+        let font = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type1".to_vec())),
+            ("BaseFont", Object::Name(b"ABCDEF+Minion-BoldIt".to_vec())),
+            ("FirstChar", Object::Integer(65)),
+            ("Widths", Object::Array(vec![600.into(), 400.into()])),
+            ("FontDescriptor", Object::Reference(descriptor)),
+            (
+                "Encoding",
+                Object::Dictionary(Dictionary::from_iter(vec![
+                    ("BaseEncoding", Object::Name(b"WinAnsiEncoding".to_vec())),
+                    (
+                        "Differences",
+                        Object::Array(vec![
+                            66.into(),
+                            Object::Name(b"fi".to_vec()),
+                            Object::Name(b"uni4E2D".to_vec()),
+                        ]),
+                    ),
+                ])),
+            ),
+        ]);
+
+        let font = PdfFont::load(&doc, &font);
+        assert_eq!(font.name, "Minion-BoldIt");
+        assert!(font.bold && font.italic);
+        let glyphs = font.glyphs(b"ABC\x93 ");
+        let texts: Vec<Option<&str>> = glyphs.iter().map(|glyph| glyph.text.as_deref()).collect();
+        assert_eq!(texts, vec![Some("A"), Some("\u{FB01}"), Some("中"), Some("\u{201C}"), Some(" ")]);
+        assert_eq!(glyphs.iter().map(|glyph| glyph.width).collect::<Vec<_>>(), vec![600.0, 400.0, 0.0, 0.0, 0.0]);
+        assert!(glyphs[4].is_space);
+    }
+
+    #[test]
+    fn test_type0_font_with_to_unicode() {
+        let mut doc = Document::with_version("1.5");
+        let cmap = doc.add_object(Stream::new(
+            Dictionary::new(),
+            b"2 beginbfchar <0001> <4F60> <0002> <597D> endbfchar".to_vec(),
+        ));
+        let descendant = doc.add_object(Dictionary::from_iter(vec![
+            ("Subtype", Object::Name(b"CIDFontType2".to_vec())),
+            ("DW", Object::Integer(1000)),
+            ("W", Object::Array(vec![2.into(), Object::Array(vec![500.into()])])),
+        ]));
+        let font = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"Type0".to_vec())),
+            ("BaseFont", Object::Name(b"SimHei".to_vec())),
+            ("Encoding", Object::Name(b"Identity-H".to_vec())),
+            ("DescendantFonts", Object::Array(vec![Object::Reference(descendant)])),
+            ("ToUnicode", Object::Reference(cmap)),
+        ]);
+
+        let font = PdfFont::load(&doc, &font);
+        assert!(!font.bold && !font.italic);
+        let glyphs = font.glyphs(&[0, 1, 0, 2, 0, 3]);
+        assert_eq!(
+            glyphs.iter().map(|glyph| (glyph.code, glyph.width, glyph.text.as_deref())).collect::<Vec<_>>(),
+            vec![(1, 1000.0, Some("你")), (2, 500.0, Some("好")), (3, 1000.0, None)]
+        );
+        assert!(glyphs.iter().all(|glyph| !glyph.is_space));
+    }
+
+    /// A TrueType font holding only a format 12 Unicode `cmap` for
+    /// `(character, glyph)` pairs
+    fn cmap_only_font(pairs: &[(char, u32)]) -> Vec<u8> {
+        let mut cmap = Vec::new();
+        cmap.extend_from_slice(&[0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12]);
+        cmap.extend_from_slice(&[0, 12, 0, 0]);
+        cmap.extend_from_slice(&(16 + 12 * pairs.len() as u32).to_be_bytes());
+        cmap.extend_from_slice(&0u32.to_be_bytes());
+        cmap.extend_from_slice(&(pairs.len() as u32).to_be_bytes());
+        for &(c, glyph) in pairs {
+            for value in [c as u32, c as u32, glyph] {
+                cmap.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        let mut font = vec![0, 1, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0];
+        font.extend_from_slice(b"cmap");
+        font.extend_from_slice(&0u32.to_be_bytes());
+        font.extend_from_slice(&28u32.to_be_bytes());
+        font.extend_from_slice(&(cmap.len() as u32).to_be_bytes());
+        font.extend_from_slice(&cmap);
+        font
+    }
+
+    #[test]
+    fn test_type0_font_without_to_unicode_falls_back() {
+        let mut doc = Document::with_version("1.5");
+        let program = doc.add_object(Stream::new(Dictionary::new(), cmap_only_font(&[('中', 5), ('文', 6)])));
+        let descriptor = doc.add_object(Dictionary::from_iter(vec![("FontFile2", Object::Reference(program))]));
+        let gid_map = doc.add_object(Stream::new(Dictionary::new(), vec![0, 0, 0, 5, 0, 6]));
+        let descendant = doc.add_object(Dictionary::from_iter(vec![
+            ("Subtype", Object::Name(b"CIDFontType2".to_vec())),
+            (
+                "CIDSystemInfo",
+                Object::Dictionary(Dictionary::from_iter(vec![
+                    ("Registry", Object::string_literal("Adobe")),
+                    ("Ordering", Object::string_literal("GB1")),
+                    ("Supplement", Object::Integer(2)),
+                ])),
+            ),
+            ("FontDescriptor", Object::Reference(descriptor)),
+            ("CIDToGIDMap", Object::Reference(gid_map)),
+        ]));
+        let type0 = |encoding: Object| {
+            Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"Font".to_vec())),
+                ("Subtype", Object::Name(b"Type0".to_vec())),
+                ("BaseFont", Object::Name(b"SimSun".to_vec())),
+                ("Encoding", encoding),
+                ("DescendantFonts", Object::Array(vec![Object::Reference(descendant)])),
+            ])
+        };
+        let texts = |font: &PdfFont, bytes: &[u8]| -> Vec<Option<String>> {
+            font.glyphs(bytes).into_iter().map(|glyph| glyph.text).collect()
+        };
+
+        // Identity CIDs through the font's cmap table, then the ASCII CIDs
+        // every collection shares
+        let identity = PdfFont::load(&doc, &type0(Object::Name(b"Identity-H".to_vec())));
+        assert_eq!(
+            texts(&identity, &[0, 1, 0, 2, 0, 34, 0x1F, 0x40]),
+            vec![Some("中".to_string()), Some("文".to_string()), Some("A".to_string()), None]
+        );
+
+        // Codes of a named CMap are characters of its charset
+        let gbk = PdfFont::load(&doc, &type0(Object::Name(b"GBK-EUC-H".to_vec())));
+        assert_eq!(
+            texts(&gbk, b"A\xD6\xD0"),
+            vec![Some("A".to_string()), Some("中".to_string())]
+        );
+        assert!(gbk.glyphs(b" ")[0].is_space);
+
+        // An embedded CMap splits codes by its codespace and maps them to
+        // CIDs, whose characters come from the font
+        let cmap = doc.add_object(Stream::new(
+            Dictionary::new(),
+            b"2 begincodespacerange <00> <7F> <8000> <FFFF> endcodespacerange\n\
+              1 begincidrange <8001> <8002> 1 endcidrange"
+                .to_vec(),
+        ));
+        let embedded = PdfFont::load(&doc, &type0(Object::Reference(cmap)));
+        assert_eq!(
+            texts(&embedded, b"\x80\x02\x80\x01"),
+            vec![Some("文".to_string()), Some("中".to_string())]
+        );
+    }
+}
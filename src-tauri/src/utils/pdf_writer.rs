@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use lopdf::{Dictionary, Object, ObjectId, Stream, StringFormat};
+use crate::utils::error::AppError;
+
+/// Where the cross-reference section finds an object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XrefEntry {
+    Free,
+    /// Byte offset and generation of an object written to the file
+    Offset(usize, u16),
+    /// Number of the object stream holding the object, and its index there
+    Compressed(u32, usize),
+}
+
+/// Serialize an object in PDF syntax; lopdf keeps its writer private
+pub fn write_object(out: &mut Vec<u8>, obj: &Object) -> io::Result<()> {
+    match obj {
+        Object::Null => out.extend_from_slice(b"null"),
+        Object::Boolean(value) => write!(out, "{}", value)?,
+        Object::Integer(value) => write!(out, "{}", value)?,
+        Object::Real(value) => write!(out, "{}", value)?,
+        Object::Name(name) => write_name(out, name)?,
+        Object::String(bytes, StringFormat::Literal) => {
+            out.push(b'(');
+            for &byte in bytes {
+                match byte {
+                    b'(' | b')' | b'\\' => out.extend_from_slice(&[b'\\', byte]),
+                    b'\r' => out.extend_from_slice(b"\\r"),
+                    _ => out.push(byte),
+                }
+            }
+            out.push(b')');
+        }
+        Object::String(bytes, StringFormat::Hexadecimal) => {
+            out.push(b'<');
+            for byte in bytes {
+                write!(out, "{:02X}", byte)?;
+            }
+            out.push(b'>');
+        }
+        Object::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ');
+                }
+                write_object(out, item)?;
+            }
+            out.push(b']');
+        }
+        Object::Dictionary(dict) => write_dictionary(out, dict, None)?,
+        Object::Stream(stream) => {
+            write_dictionary(out, &stream.dict, Some(stream.content.len()))?;
+            out.extend_from_slice(b"stream\n");
+            out.extend_from_slice(&stream.content);
+            out.extend_from_slice(b"\nendstream");
+        }
+        Object::Reference((id, generation)) => write!(out, "{} {} R", id, generation)?,
+    }
+    Ok(())
+}
+
+/// Write `id gen obj ... endobj`, returning its cross-reference entry
+pub fn write_indirect_object(out: &mut Vec<u8>, (id, generation): ObjectId, obj: &Object) -> io::Result<XrefEntry> {
+    let offset = out.len();
+    writeln!(out, "{} {} obj", id, generation)?;
+    write_object(out, obj)?;
+    out.extend_from_slice(b"\nendobj\n");
+    Ok(XrefEntry::Offset(offset, generation))
+}
+
+/// Finish a file with a cross-reference stream as object `id`, holding
+/// `entries` and the trailer keys of `trailer`
+pub fn write_xref_stream(
+    out: &mut Vec<u8>,
+    id: u32,
+    entries: &BTreeMap<u32, XrefEntry>,
+    mut trailer: Dictionary,
+) -> io::Result<()> {
+    let start = out.len();
+    let mut entries = entries.clone();
+    entries.insert(id, XrefEntry::Offset(start, 0));
+
+    let rows: Vec<(u8, u64, u64)> = entries
+        .iter()
+        .map(|(&number, entry)| match *entry {
+            XrefEntry::Free => (0, 0, if number == 0 { 65535 } else { 0 }),
+            XrefEntry::Offset(offset, generation) => (1, offset as u64, generation as u64),
+            XrefEntry::Compressed(container, index) => (2, container as u64, index as u64),
+        })
+        .collect();
+    // Offsets past 4 GiB need wider fields than the usual 1 4 2
+    let width = |max: u64, usual: usize| if max >> (usual * 8) == 0 { usual } else { 8 };
+    let widths = [
+        1,
+        width(rows.iter().map(|row| row.1).max().unwrap_or(0), 4),
+        width(rows.iter().map(|row| row.2).max().unwrap_or(0), 2),
+    ];
+    let mut data = Vec::with_capacity(widths.iter().sum::<usize>() * rows.len());
+    for (kind, field, index) in rows {
+        data.push(kind);
+        data.extend_from_slice(&field.to_be_bytes()[8 - widths[1]..]);
+        data.extend_from_slice(&index.to_be_bytes()[8 - widths[2]..]);
+    }
+    let index = subsections(&entries)
+        .into_iter()
+        .flat_map(|(first, count)| [Object::Integer(first as i64), Object::Integer(count as i64)])
+        .collect();
+
+    trailer.set("Type", Object::Name(b"XRef".to_vec()));
+    trailer.set("Size", Object::Integer(trailer_size(&trailer).max(id as i64 + 1)));
+    trailer.set("Index", Object::Array(index));
+    trailer.set("W", Object::Array(widths.iter().map(|&width| Object::Integer(width as i64)).collect()));
+    let mut stream = Stream::new(trailer, data);
+    stream.compress().map_err(|e| io::Error::other(e.to_string()))?;
+    write_indirect_object(out, (id, 0), &Object::Stream(stream))?;
+    write!(out, "startxref\n{}\n%%EOF\n", start)
+}
+
+/// Finish a file with a classic cross-reference table and trailer
+pub fn write_xref_table(out: &mut Vec<u8>, entries: &BTreeMap<u32, XrefEntry>, trailer: &Dictionary) -> io::Result<()> {
+    let start = out.len();
+    out.extend_from_slice(b"xref\n");
+    for (first, count) in subsections(entries) {
+        writeln!(out, "{} {}", first, count)?;
+        for number in first..first + count {
+            match entries[&number] {
+                XrefEntry::Offset(offset, _) if offset > 9_999_999_999 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "a cross-reference table can't hold offsets past 10 GB",
+                    ))
+                }
+                XrefEntry::Offset(offset, generation) => write!(out, "{:010} {:05} n\r\n", offset, generation)?,
+                XrefEntry::Free => write!(out, "0000000000 {:05} f\r\n", if number == 0 { 65535 } else { 0 })?,
+                XrefEntry::Compressed(..) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "a cross-reference table can't point into object streams",
+                    ))
+                }
+            }
+        }
+    }
+    out.extend_from_slice(b"trailer\n");
+    write_object(out, &Object::Dictionary(trailer.clone()))?;
+    write!(out, "\nstartxref\n{}\n%%EOF\n", start)
+}
+
+/// Append an incremental update to the complete file `original`: `objects`
+/// replace or add to the objects of the file, and `trailer` (Root, Info,
+/// ID, Encrypt and Size) is written with a `/Prev` link to the previous
+/// section. The update uses the same kind of cross-reference section as the
+/// file, so earlier bytes, and with them any signatures, stay untouched.
+pub fn append_incremental_update(
+    original: &[u8],
+    objects: &BTreeMap<ObjectId, Object>,
+    trailer: &Dictionary,
+) -> Result<Vec<u8>, AppError> {
+    let previous = last_startxref(original)
+        .ok_or_else(|| AppError::PdfError("The file has no cross-reference section".to_string()))?;
+    let stream_xref = !original
+        .get(previous..)
+        .and_then(|section| section.get(section.iter().position(|byte| !byte.is_ascii_whitespace())?..))
+        .is_some_and(|section| section.starts_with(b"xref"));
+
+    let mut out = original.to_vec();
+    if !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    let mut entries = BTreeMap::new();
+    for (&id, obj) in objects {
+        entries.insert(id.0, write_indirect_object(&mut out, id, obj)?);
+    }
+
+    let mut trailer = trailer.clone();
+    trailer.set("Prev", Object::Integer(previous as i64));
+    let size = trailer_size(&trailer).max(entries.keys().last().map_or(0, |&id| id as i64 + 1));
+    trailer.set("Size", Object::Integer(size));
+    if stream_xref {
+        let id = u32::try_from(size)
+            .map_err(|_| AppError::PdfError(format!("Invalid cross-reference size {}", size)))?;
+        write_xref_stream(&mut out, id, &entries, trailer)?;
+    } else {
+        write_xref_table(&mut out, &entries, &trailer)?;
+    }
+    Ok(out)
+}
+
+/// Offset named by the last `startxref` keyword of a file
+fn last_startxref(data: &[u8]) -> Option<usize> {
+    let keyword = b"startxref";
+    let position = data.windows(keyword.len()).rposition(|window| window == keyword)?;
+    let digits: Vec<u8> = data[position + keyword.len()..]
+        .iter()
+        .skip_while(|byte| byte.is_ascii_whitespace())
+        .take_while(|byte| byte.is_ascii_digit())
+        .copied()
+        .collect();
+    std::str::from_utf8(&digits).ok()?.parse().ok()
+}
+
+fn trailer_size(trailer: &Dictionary) -> i64 {
+    trailer.get(b"Size").and_then(Object::as_i64).unwrap_or(0)
+}
+
+/// Runs of consecutive object numbers as `(first, count)`
+fn subsections(entries: &BTreeMap<u32, XrefEntry>) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &number in entries.keys() {
+        match runs.last_mut() {
+            Some((first, count)) if *first + *count == number => *count += 1,
+            _ => runs.push((number, 1)),
+        }
+    }
+    runs
+}
+
+/// Write a dictionary; streams pass their real `length`, which replaces
+/// any `/Length` entry
+fn write_dictionary(out: &mut Vec<u8>, dict: &Dictionary, length: Option<usize>) -> io::Result<()> {
+    out.extend_from_slice(b"<<");
+    for (key, value) in dict.iter() {
+        if length.is_some() && key == b"Length" {
+            continue;
+        }
+        write_name(out, key)?;
+        out.push(b' ');
+        write_object(out, value)?;
+    }
+    if let Some(length) = length {
+        write!(out, "/Length {}", length)?;
+    }
+    out.extend_from_slice(b">>");
+    Ok(())
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[u8]) -> io::Result<()> {
+    out.push(b'/');
+    for &byte in name {
+        if b" \t\n\r\x0C()<>[]{}/%#".contains(&byte) || !(33..=126).contains(&byte) {
+            write!(out, "#{:02X}", byte)?;
+        } else {
+            out.push(byte);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsections_and_startxref() {
+        let entries: BTreeMap<u32, XrefEntry> = [0, 1, 2, 7, 9, 10]
+            .into_iter()
+            .map(|number| (number, XrefEntry::Free))
+            .collect();
+        assert_eq!(subsections(&entries), vec![(0, 3), (7, 1), (9, 2)]);
+        assert_eq!(last_startxref(b"startxref\n12\n%%EOF\nstartxref\r\n345\n%%EOF"), Some(345));
+        assert_eq!(last_startxref(b"%PDF-1.7"), None);
+    }
+
+    #[test]
+    fn test_xref_fields_widen_for_large_offsets() {
+        let written = |offset: usize| {
+            let entries = BTreeMap::from([(0, XrefEntry::Free), (1, XrefEntry::Offset(offset, 0))]);
+            let mut out = Vec::new();
+            write_xref_stream(&mut out, 2, &entries, Dictionary::new()).unwrap();
+            let widths = out.windows(4).position(|window| window == b"/W [").unwrap();
+            String::from_utf8_lossy(&out[widths..widths + 11]).into_owned()
+        };
+        assert!(written(4096).starts_with("/W [1 4 2]"));
+        assert!(written(5 << 30).starts_with("/W [1 8 2]"));
+
+        let entries = BTreeMap::from([(0, XrefEntry::Free), (1, XrefEntry::Offset(11 << 30, 0))]);
+        assert!(write_xref_table(&mut Vec::new(), &entries, &Dictionary::new()).is_err());
+    }
+}
@@ -51,7 +51,7 @@ fn test_convert_real_pdf_to_epub() {
     // Step 3: Extract ALL text (this is what the converter does)
     println!("Step 3: Extracting all text ({} pages)...", page_count);
     let start = std::time::Instant::now();
-    let result = pdfcraft_lib::services::pdf_parser::PdfParserService::extract_all_text(input);
+    let result = pdfcraft_lib::services::pdf_parser::PdfParserService::extract_all_text(input, None);
     match &result {
         Ok(texts) => {
             let total_chars: usize = texts.iter().map(|t| t.len()).sum();
@@ -68,7 +68,7 @@ fn test_convert_real_pdf_to_epub() {
     // Step 4: Test is_scanned_pdf
     println!("Step 4: Checking if scanned...");
     let start = std::time::Instant::now();
-    let is_scanned = pdfcraft_lib::services::pdf_parser::PdfParserService::is_scanned_pdf(input);
+    let is_scanned = pdfcraft_lib::services::pdf_parser::PdfParserService::is_scanned_pdf(input, None);
     match &is_scanned {
         Ok(v) => println!("  is_scanned: {} in {:?}", v, start.elapsed()),
         Err(e) => println!("  FAILED: {:?}", e),
@@ -105,6 +105,7 @@ fn test_convert_real_pdf_to_epub() {
             keep_images: true,
             detect_tables: false,
             font_size: "medium".to_string(),
+            password: None,
         };
 
         let texts = result.unwrap();
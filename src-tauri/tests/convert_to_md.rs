@@ -11,7 +11,7 @@ fn test_convert_real_pdf_to_md() {
 
     // Since we fixed the conversion logic in commands::convert_pdf_to_ebook,
     // let's test just the text_to_chapters part which will be used in MD conversion
-    let texts = pdfcraft_lib::services::pdf_parser::PdfParserService::extract_all_text(input).unwrap();
+    let texts = pdfcraft_lib::services::pdf_parser::PdfParserService::extract_all_text(input, None).unwrap();
     let chapters = pdfcraft_lib::services::epub_builder::EpubBuilderService::text_to_chapters(&texts);
     
     let mut md_content = String::new();
@@ -0,0 +1,130 @@
+//! Timings of the shared document cache on large synthetic files. Run with
+//! `cargo test --release --test document_cache_bench -- --ignored --nocapture`.
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use lopdf::{Dictionary, Document, Object, Stream};
+use pdfcraft_lib::services::document_cache::DocumentCacheService;
+use pdfcraft_lib::services::pdf_parser::PdfParserService;
+use pdfcraft_lib::services::pdf_security::PdfSecurityService;
+
+/// Write a PDF with `page_count` pages, each with 40 lines of text and an
+/// uncompressed image of about `image_bytes` bytes of noise
+fn build_large_pdf(path: &Path, page_count: usize, image_bytes: usize) {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Font".to_vec())),
+        ("Subtype", Object::Name(b"Type1".to_vec())),
+        ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+    ]));
+    let side = ((image_bytes / 3) as f64).sqrt() as i64;
+    let mut seed = 0x2545_F491_4F6C_DD1Du64;
+    let mut kids = Vec::new();
+    for page in 0..page_count {
+        let noise: Vec<u8> = (0..side * side * 3)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect();
+        let image_id = doc.add_object(Stream::new(
+            Dictionary::from_iter(vec![
+                ("Type", Object::Name(b"XObject".to_vec())),
+                ("Subtype", Object::Name(b"Image".to_vec())),
+                ("Width", Object::Integer(side)),
+                ("Height", Object::Integer(side)),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+            ]),
+            noise,
+        ));
+
+        let mut content = String::from("q 200 0 0 200 72 520 cm /Im1 Do Q\n");
+        for line in 0..40 {
+            content.push_str(&format!(
+                "BT /F1 10 Tf 72 {} Td (Page {} line {}: the quick brown fox jumps over the lazy dog) Tj ET\n",
+                500 - line * 12,
+                page + 1,
+                line + 1
+            ));
+        }
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content.into_bytes()));
+        let resources = Dictionary::from_iter(vec![
+            ("Font", Object::Dictionary(Dictionary::from_iter(vec![("F1", Object::Reference(font_id))]))),
+            ("XObject", Object::Dictionary(Dictionary::from_iter(vec![("Im1", Object::Reference(image_id))]))),
+        ]);
+        let page = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", Object::Reference(pages_id)),
+            ("MediaBox", Object::Array(vec![0.into(), 0.into(), 612.into(), 792.into()])),
+            ("Contents", Object::Reference(content_id)),
+            ("Resources", Object::Dictionary(resources)),
+        ]);
+        kids.push(Object::Reference(doc.add_object(page)));
+    }
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Pages".to_vec())),
+            ("Count", Object::Integer(page_count as i64)),
+            ("Kids", Object::Array(kids)),
+        ])),
+    );
+    let catalog_id = doc.add_object(Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Catalog".to_vec())),
+        ("Pages", Object::Reference(pages_id)),
+    ]));
+    doc.trailer.set("Root", Object::Reference(catalog_id));
+    doc.save(path).unwrap();
+}
+
+/// What a conversion of a file listed in the UI asks of the parser
+fn convert_flow(path: &str, cached: bool) -> Duration {
+    let clear = || {
+        if !cached {
+            DocumentCacheService::clear();
+        }
+    };
+    let start = Instant::now();
+    clear();
+    PdfParserService::get_info(path, None, 160).unwrap();
+    clear();
+    PdfParserService::is_scanned_pdf(path, None).unwrap();
+    clear();
+    PdfParserService::extract_all_text(path, None).unwrap();
+    clear();
+    PdfParserService::get_info(path, None, 160).unwrap();
+    start.elapsed()
+}
+
+#[test]
+#[ignore]
+fn bench_document_cache_on_large_files() {
+    let dir = tempfile::tempdir().unwrap();
+    for (page_count, image_bytes) in [(200, 64 * 1024), (1000, 96 * 1024), (2000, 16 * 1024)] {
+        let path = dir.path().join(format!("synthetic-{}.pdf", page_count));
+        build_large_pdf(&path, page_count, image_bytes);
+        let path = path.to_string_lossy().to_string();
+        let size = std::fs::metadata(&path).unwrap().len();
+        println!("{} pages, {:.1} MB", page_count, size as f64 / 1_048_576.0);
+
+        let start = Instant::now();
+        PdfSecurityService::load(&path, None).unwrap();
+        println!("  parse once:             {:?}", start.elapsed());
+
+        DocumentCacheService::clear();
+        let uncached = convert_flow(&path, false);
+        DocumentCacheService::clear();
+        let cached = convert_flow(&path, true);
+        println!("  info + text, uncached:  {:?}", uncached);
+        println!("  info + text, cached:    {:?}", cached);
+
+        let start = Instant::now();
+        DocumentCacheService::load(&path, None).unwrap();
+        println!("  cache hit:              {:?}", start.elapsed());
+        DocumentCacheService::evict(&path);
+    }
+}
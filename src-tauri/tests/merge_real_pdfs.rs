@@ -38,11 +38,13 @@ fn test_merge_real_pdfs() {
             .map(|p| pdfcraft_lib::models::pdf::PdfFileEntry {
                 path: p.clone(),
                 selected_pages: None,
+                ..Default::default()
             })
             .collect(),
         output_path: output.to_string(),
         keep_bookmarks: true,
         page_size: "original".to_string(),
+        ..Default::default()
     };
 
     let result = pdfcraft_lib::services::pdf_merger::PdfMergerService::merge(&config);
@@ -1,16 +1,121 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { PdfInfo, MergeConfig } from '../types';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type {
+  PdfInfo,
+  MergeConfig,
+  MergeResult,
+  SplitConfig,
+  SplitPart,
+  OrganizeConfig,
+  OrganizeResult,
+  StampConfig,
+  StampResult,
+  ImpositionConfig,
+  ImpositionResult,
+  OptimizeConfig,
+  OptimizeResult,
+  PdfMetadata,
+  MetadataUpdate,
+  PageThumbnailRequest,
+  PageThumbnail,
+  PageThumbnailsResult,
+} from '../types';
 
 /**
- * Get PDF file information (page count, metadata, thumbnail)
+ * Get PDF file information (page count, metadata, thumbnail). The thumbnail
+ * is a PNG data URL whose longer side is `thumbnailSize` pixels (160 by
+ * default), or empty when the first page can't be rendered.
  */
-export async function getPdfInfo(path: string): Promise<PdfInfo> {
-  return invoke<PdfInfo>('get_pdf_info', { path });
+export async function getPdfInfo(path: string, password?: string, thumbnailSize?: number): Promise<PdfInfo> {
+  return invoke<PdfInfo>('get_pdf_info', { path, password, thumbnailSize });
 }
 
 /**
  * Merge multiple PDF files into one
  */
-export async function mergePdfs(config: MergeConfig): Promise<string> {
-  return invoke<string>('merge_pdfs', { config });
+export async function mergePdfs(config: MergeConfig): Promise<MergeResult> {
+  return invoke<MergeResult>('merge_pdfs', { config });
+}
+
+/**
+ * Split one PDF into several files
+ */
+export async function splitPdf(config: SplitConfig): Promise<SplitPart[]> {
+  return invoke<SplitPart[]>('split_pdf', { config });
+}
+
+/**
+ * Delete, reorder, duplicate, rotate or extract pages of one PDF
+ */
+export async function organizePages(config: OrganizeConfig): Promise<OrganizeResult> {
+  return invoke<OrganizeResult>('organize_pages', { config });
+}
+
+/**
+ * Draw watermarks and stamps onto an existing PDF
+ */
+export async function stampPdf(config: StampConfig): Promise<StampResult> {
+  return invoke<StampResult>('stamp_pdf', { config });
+}
+
+/**
+ * Lay out the pages of a PDF N-up or as a booklet
+ */
+export async function imposePdf(config: ImpositionConfig): Promise<ImpositionResult> {
+  return invoke<ImpositionResult>('impose_pdf', { config });
+}
+
+/**
+ * Shrink a PDF and report its size before and after
+ */
+export async function optimizePdf(config: OptimizeConfig): Promise<OptimizeResult> {
+  return invoke<OptimizeResult>('optimize_pdf', { config });
+}
+
+/**
+ * Read the document properties, XMP and viewer settings of a PDF
+ */
+export async function getPdfMetadata(path: string, password?: string): Promise<PdfMetadata> {
+  return invoke<PdfMetadata>('get_pdf_metadata', { path, password });
+}
+
+/**
+ * Replace the metadata of a PDF, appending an incremental update so
+ * signatures stay valid
+ */
+export async function updatePdfMetadata(update: MetadataUpdate): Promise<PdfMetadata> {
+  return invoke<PdfMetadata>('update_pdf_metadata', { update });
+}
+
+/**
+ * Render thumbnails for a page range. Each page arrives as a
+ * `page-thumbnail` event (see `onPageThumbnail`) as soon as it is ready; the
+ * promise resolves once all pages are sent.
+ */
+export async function renderPageThumbnails(request: PageThumbnailRequest): Promise<PageThumbnailsResult> {
+  return invoke<PageThumbnailsResult>('render_page_thumbnails', { request });
+}
+
+/**
+ * Stop a running thumbnail task
+ */
+export async function cancelPageThumbnails(taskId: string): Promise<void> {
+  return invoke('cancel_page_thumbnails', { taskId });
+}
+
+/**
+ * Listen for page thumbnails rendered by `renderPageThumbnails`
+ */
+export async function onPageThumbnail(callback: (thumbnail: PageThumbnail) => void): Promise<UnlistenFn> {
+  return listen<PageThumbnail>('page-thumbnail', (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Release the parsed copy of a PDF the backend keeps between operations,
+ * e.g. when the file is removed from the list; every PDF when no path is given
+ */
+export async function clearDocumentCache(path?: string): Promise<void> {
+  return invoke('clear_document_cache', { path });
 }
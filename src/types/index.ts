@@ -14,16 +14,144 @@ export interface PdfInfo {
   title?: string;
   author?: string;
   thumbnail: string;
+  encrypted: boolean;
+  permissions?: PdfPermissions;
 }
 
-export interface MergeConfig {
+export interface PageThumbnailRequest {
+  /** Chosen by the caller to match events and cancel the task */
+  taskId: string;
+  path: string;
+  password?: string;
+  /** Page range such as "1-3,7"; every page when not set */
+  pages?: string;
+  /** Longer side of each thumbnail in pixels (160 by default) */
+  size?: number;
+}
+
+export interface PageThumbnail {
+  taskId: string;
+  page: number;
+  /** PNG data URL */
+  thumbnail: string;
+}
+
+export interface PageThumbnailsResult {
+  taskId: string;
+  rendered: number;
+  cached: number;
+}
+
+export interface PdfPermissions {
+  print: boolean;
+  modify: boolean;
+  copy: boolean;
+  annotate: boolean;
+  fillForms: boolean;
+  extractForAccessibility: boolean;
+  assemble: boolean;
+  printHighQuality: boolean;
+}
+
+export interface PageSelection {
+  page: number;
+  rotation?: 0 | 90 | 180 | 270;
+  blankAfter?: boolean;
+}
+
+export interface DocumentInfo {
+  title?: string;
+  author?: string;
+  subject?: string;
+  keywords?: string;
+  creator?: string;
+  producer?: string;
+}
+
+export interface MergeConfig extends DocumentInfo {
   files: Array<{
     path: string;
     selectedPages?: number[];
+    password?: string;
+    pageRange?: string;
+    pages?: PageSelection[];
+    reverse?: boolean;
+    padToEven?: boolean;
   }>;
   outputPath: string;
   keepBookmarks: boolean;
   pageSize: 'original' | 'a4' | 'letter';
+  mergeMode?: 'concatenate' | 'interleave';
+  restartPageLabels?: boolean;
+  pageLabelPrefix?: string;
+  security?: OutputSecurity;
+  stamps?: Stamp[];
+  headersFooters?: HeaderFooter[];
+  optimize?: OptimizeOptions;
+}
+
+export interface OutputSecurity {
+  userPassword?: string;
+  ownerPassword?: string;
+  allowPrint?: boolean;
+  allowCopy?: boolean;
+  allowModify?: boolean;
+  allowAnnotate?: boolean;
+}
+
+export type StampContent =
+  | { kind: 'text'; text: string; fontSize?: number; color?: string; fontPath?: string }
+  | { kind: 'image'; path: string }
+  | { kind: 'pdf'; path: string; page?: number; password?: string };
+
+export type StampPosition =
+  | 'center'
+  | 'top'
+  | 'bottom'
+  | 'top_left'
+  | 'top_right'
+  | 'bottom_left'
+  | 'bottom_right';
+
+export type Stamp = StampContent & {
+  pages?: string;
+  position?: StampPosition;
+  rotation?: number;
+  opacity?: number;
+  scale?: number;
+  behind?: boolean;
+};
+
+export interface HeaderFooter {
+  template: string;
+  position?: StampPosition;
+  marginX?: number;
+  marginY?: number;
+  fontSize?: number;
+  color?: string;
+  fontPath?: string;
+  startNumber?: number;
+  zeroPadding?: number;
+  date?: string;
+}
+
+export interface StampConfig {
+  inputPath: string;
+  password?: string;
+  outputPath: string;
+  stamps: Stamp[];
+}
+
+export interface StampResult {
+  outputPath: string;
+  stampedPages: number;
+}
+
+export interface MergeResult {
+  outputPath: string;
+  deduplicatedObjects: number;
+  bytesSaved: number;
+  warnings: string[];
 }
 
 export type OutputFormat = 'epub' | 'mobi' | 'azw3' | 'docx' | 'txt' | 'fb2' | 'html' | 'md';
@@ -40,6 +168,144 @@ export interface OcrConfig {
   languages: string[];
 }
 
+export type SplitMode = 'every_n_pages' | 'ranges' | 'bookmarks' | 'max_size';
+
+export interface SplitConfig {
+  inputPath: string;
+  password?: string;
+  outputDir: string;
+  mode: SplitMode;
+  pagesPerPart?: number;
+  ranges?: string[];
+  maxFileSize?: number;
+  nameTemplate?: string;
+  overwrite?: boolean;
+}
+
+export interface SplitPart {
+  path: string;
+  pages: number[];
+  title?: string;
+  fileSize: number;
+}
+
+export type PageOperation =
+  | { op: 'delete'; pages: string }
+  | { op: 'reorder'; order: string }
+  | { op: 'duplicate'; pages: string; copies?: number }
+  | { op: 'rotate'; pages: string; degrees: number }
+  | { op: 'extract'; pages: string };
+
+export interface OrganizeConfig {
+  inputPath: string;
+  password?: string;
+  outputPath: string;
+  operations: PageOperation[];
+}
+
+export interface OrganizeResult {
+  outputPath: string;
+  pageCount: number;
+}
+
+export type ImpositionLayout = 'n_up' | 'booklet';
+export type GridOrder = 'left_to_right' | 'top_to_bottom' | 'right_to_left';
+
+export interface ImpositionConfig {
+  inputPath: string;
+  password?: string;
+  outputPath: string;
+  layout?: ImpositionLayout;
+  columns?: number;
+  rows?: number;
+  order?: GridOrder;
+  sheetSize?: 'original' | 'a4' | 'letter';
+  margin?: number;
+  gutter?: number;
+  cropMarks?: boolean;
+  signatureSize?: number;
+}
+
+export interface ImpositionResult {
+  outputPath: string;
+  pageCount: number;
+}
+
+export interface OptimizeOptions {
+  recompressImages?: boolean;
+  imageDpi?: number;
+  jpegQuality?: number;
+  objectStreams?: boolean;
+}
+
+export interface OptimizeConfig extends OptimizeOptions {
+  inputPath: string;
+  password?: string;
+  outputPath: string;
+}
+
+export interface OptimizeResult {
+  outputPath: string;
+  originalSize: number;
+  optimizedSize: number;
+  imagesRecompressed: number;
+  objectsRemoved: number;
+}
+
+export type PageLayout =
+  | 'single_page'
+  | 'one_column'
+  | 'two_column_left'
+  | 'two_column_right'
+  | 'two_page_left'
+  | 'two_page_right';
+
+export type PageMode =
+  | 'use_none'
+  | 'use_outlines'
+  | 'use_thumbs'
+  | 'full_screen'
+  | 'use_oc'
+  | 'use_attachments';
+
+export interface ViewerPreferences {
+  hideToolbar?: boolean;
+  hideMenubar?: boolean;
+  hideWindowUi?: boolean;
+  fitWindow?: boolean;
+  centerWindow?: boolean;
+  displayDocTitle?: boolean;
+  rightToLeft?: boolean;
+}
+
+export interface PdfMetadata extends DocumentInfo {
+  creationDate?: string;
+  modificationDate?: string;
+  xmp?: string;
+  language?: string;
+  pageLayout?: PageLayout;
+  pageMode?: PageMode;
+  viewerPreferences: ViewerPreferences;
+  pdfVersion: string;
+  pageCount: number;
+  fileSize: number;
+  encrypted: boolean;
+  signatures: number;
+}
+
+export interface MetadataUpdate extends DocumentInfo {
+  inputPath: string;
+  password?: string;
+  /** The input file is updated in place when not set */
+  outputPath?: string;
+  /** Regenerated from the Info fields when not set */
+  xmp?: string;
+  language?: string;
+  pageLayout?: PageLayout;
+  pageMode?: PageMode;
+  viewerPreferences?: ViewerPreferences;
+}
+
 export interface ConvertConfig {
   inputPath: string;
   outputPath: string;
@@ -50,6 +316,7 @@ export interface ConvertConfig {
   keepImages: boolean;
   detectTables: boolean;
   fontSize: 'small' | 'medium' | 'large';
+  password?: string;
 }
 
 export interface ConversionProgress {